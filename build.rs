@@ -261,6 +261,7 @@ fn build_bindings(libcef_include_dir: &Path) {
         .allowlist_function("cef_interface_.*")
         .allowlist_type("RustSchemeReturn")
         .rustified_enum("FFIRustV8ValueTag")
+        .rustified_enum("FFIResourceType")
         .generate()
         .expect("Unable to generate bindings") 
         .write_to_file(PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs"))
@@ -31,6 +31,17 @@ CefRefPtr<CefDialogHandler> MyClient::GetDialogHandler() {
 CefRefPtr<CefDownloadHandler> MyClient::GetDownloadHandler() {
   return this;
 }
+CefRefPtr<CefAudioHandler> MyClient::GetAudioHandler() {
+  if (audio_capture_enabled) {
+    return this;
+  }
+  return nullptr;
+}
+
+void MyClient::SetAudioCapture(bool enabled, int sample_rate) {
+  audio_capture_enabled = enabled;
+  audio_sample_rate = sample_rate;
+}
 
 bool MyClient::OnProcessMessageReceived(CefRefPtr<CefBrowser> browser,
                                         CefRefPtr<CefFrame> frame,
@@ -68,6 +79,15 @@ void MyClient::OnTitleChange(CefRefPtr<CefBrowser> browser,
                               title_utf8.c_str());
   }
 }
+void MyClient::OnAddressChange(CefRefPtr<CefBrowser> browser,
+                               CefRefPtr<CefFrame> frame,
+                               const CefString& url) {
+  if (callbacks.on_address_change && frame->IsMain()) {
+    auto url_utf8 = url.ToString();
+    callbacks.on_address_change(cef_interface_add_ref_browser(browser.get()),
+                                url_utf8.c_str());
+  }
+}
 void MyClient::OnLoadingProgressChange(CefRefPtr<CefBrowser> browser,
                                        double progress) {
   // auto ag = std::to_string(progress);
@@ -110,9 +130,19 @@ bool MyClient::OnBeforePopup(
     bool* no_javascript_access) {
   rust_debug("popup detected");
 
-  frame->LoadURL(target_url);
+  bool load_in_frame = true;
+  if (callbacks.on_before_popup) {
+    auto target_url_utf8 = target_url.ToString();
+    load_in_frame = callbacks.on_before_popup(
+        cef_interface_add_ref_browser(browser.get()), target_url_utf8.c_str(),
+        user_gesture);
+  }
 
-  // block the popup
+  if (load_in_frame) {
+    frame->LoadURL(target_url);
+  }
+
+  // block the popup, there's nowhere to show a window offscreen
   return true;
 }
 #else
@@ -131,9 +161,19 @@ bool MyClient::OnBeforePopup(
     bool* no_javascript_access) {
   rust_debug("popup detected");
 
-  frame->LoadURL(target_url);
+  bool load_in_frame = true;
+  if (callbacks.on_before_popup) {
+    auto target_url_utf8 = target_url.ToString();
+    load_in_frame = callbacks.on_before_popup(
+        cef_interface_add_ref_browser(browser.get()), target_url_utf8.c_str(),
+        user_gesture);
+  }
 
-  // block the popup
+  if (load_in_frame) {
+    frame->LoadURL(target_url);
+  }
+
+  // block the popup, there's nowhere to show a window offscreen
   return true;
 }
 #endif
@@ -187,6 +227,10 @@ CefRefPtr<CefResourceRequestHandler> MyClient::GetResourceRequestHandler(
     bool is_download,
     const CefString& request_initiator,
     bool& disable_default_handling) {
+  // browser is null for service worker requests
+  if (callbacks.on_before_resource_load && browser) {
+    return this;
+  }
   return nullptr;
 }
 
@@ -212,6 +256,62 @@ bool MyClient::OnCertificateError(CefRefPtr<CefBrowser> browser,
   return false;
 }
 
+// CefResourceRequestHandler methods:
+static FFIResourceType get_resource_type(CefRequest::ResourceType type) {
+  switch (type) {
+    case RT_MAIN_FRAME:
+      return FFIResourceType::Document;
+    case RT_SUB_FRAME:
+      return FFIResourceType::Subdocument;
+    case RT_STYLESHEET:
+      return FFIResourceType::Stylesheet;
+    case RT_SCRIPT:
+    case RT_WORKER:
+    case RT_SHARED_WORKER:
+    case RT_SERVICE_WORKER:
+      return FFIResourceType::Script;
+    case RT_IMAGE:
+    case RT_FAVICON:
+      return FFIResourceType::Image;
+    case RT_FONT_RESOURCE:
+      return FFIResourceType::Font;
+    case RT_OBJECT:
+    case RT_PLUGIN_RESOURCE:
+      return FFIResourceType::Object;
+    case RT_MEDIA:
+      return FFIResourceType::Media;
+    case RT_XHR:
+      return FFIResourceType::XmlHttpRequest;
+    case RT_PING:
+    case RT_CSP_REPORT:
+      return FFIResourceType::Ping;
+    default:
+      return FFIResourceType::Other;
+  }
+}
+
+CefResourceRequestHandler::ReturnValue MyClient::OnBeforeResourceLoad(
+    CefRefPtr<CefBrowser> browser,
+    CefRefPtr<CefFrame> frame,
+    CefRefPtr<CefRequest> request,
+    CefRefPtr<CefCallback> callback) {
+  // this is called on the IO thread
+  if (callbacks.on_before_resource_load && browser) {
+    auto url_utf8 = request->GetURL().ToString();
+    auto first_party_url_utf8 = request->GetFirstPartyForCookies().ToString();
+    bool cancel = callbacks.on_before_resource_load(
+        cef_interface_add_ref_browser(browser.get()), url_utf8.c_str(),
+        first_party_url_utf8.c_str(),
+        get_resource_type(request->GetResourceType()));
+
+    if (cancel) {
+      return RV_CANCEL;
+    }
+  }
+
+  return RV_CONTINUE;
+}
+
 // CefJSDialogHandler methods:
 bool MyClient::OnBeforeUnloadDialog(CefRefPtr<CefBrowser> browser,
                                     const CefString& message_text,
@@ -314,3 +414,48 @@ void MyClient::OnDownloadUpdated(CefRefPtr<CefBrowser> browser,
   // download if desired.
   callback->Cancel();
 }
+
+// CefAudioHandler methods:
+bool MyClient::GetAudioParameters(CefRefPtr<CefBrowser> browser,
+                                  CefAudioParameters& params) {
+  // match the plugin's output so it doesn't have to resample
+  params.sample_rate = audio_sample_rate;
+  params.channel_layout = CEF_CHANNEL_LAYOUT_STEREO;
+  return true;
+}
+
+void MyClient::OnAudioStreamStarted(CefRefPtr<CefBrowser> browser,
+                                    const CefAudioParameters& params,
+                                    int channels) {
+  // these are called on the audio capture thread
+  if (callbacks.on_audio_stream_started) {
+    callbacks.on_audio_stream_started(
+        cef_interface_add_ref_browser(browser.get()), params.sample_rate,
+        channels);
+  }
+}
+
+void MyClient::OnAudioStreamPacket(CefRefPtr<CefBrowser> browser,
+                                   const float** data,
+                                   int frames,
+                                   int64_t pts) {
+  if (callbacks.on_audio_stream_packet) {
+    callbacks.on_audio_stream_packet(
+        cef_interface_add_ref_browser(browser.get()), data, frames);
+  }
+}
+
+void MyClient::OnAudioStreamStopped(CefRefPtr<CefBrowser> browser) {
+  if (callbacks.on_audio_stream_stopped) {
+    callbacks.on_audio_stream_stopped(
+        cef_interface_add_ref_browser(browser.get()));
+  }
+}
+
+void MyClient::OnAudioStreamError(CefRefPtr<CefBrowser> browser,
+                                  const CefString& message) {
+  auto message_utf8 = message.ToString();
+  rust_warn(message_utf8.c_str());
+
+  OnAudioStreamStopped(browser);
+}
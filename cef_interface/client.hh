@@ -1,5 +1,6 @@
 #pragma once
 
+#include <include/cef_audio_handler.h>
 #include <include/cef_client.h>
 #include <include/cef_version.h>
 #include <include/wrapper/cef_helpers.h>
@@ -14,12 +15,16 @@ class MyClient : public CefClient,
                  public CefRenderHandler,
                  public CefLoadHandler,
                  public CefRequestHandler,
+                 public CefResourceRequestHandler,
                  public CefJSDialogHandler,
                  public CefDialogHandler,
-                 public CefDownloadHandler {
+                 public CefDownloadHandler,
+                 public CefAudioHandler {
  public:
   MyClient(Callbacks callbacks);
 
+  void SetAudioCapture(bool enabled, int sample_rate);
+
   // CefClient methods:
   CefRefPtr<CefDisplayHandler> GetDisplayHandler() override;
   CefRefPtr<CefLifeSpanHandler> GetLifeSpanHandler() override;
@@ -29,6 +34,7 @@ class MyClient : public CefClient,
   CefRefPtr<CefJSDialogHandler> GetJSDialogHandler() override;
   CefRefPtr<CefDialogHandler> GetDialogHandler() override;
   CefRefPtr<CefDownloadHandler> GetDownloadHandler() override;
+  CefRefPtr<CefAudioHandler> GetAudioHandler() override;
 
   bool OnProcessMessageReceived(CefRefPtr<CefBrowser> browser,
                                 CefRefPtr<CefFrame> frame,
@@ -39,6 +45,10 @@ class MyClient : public CefClient,
   void OnTitleChange(CefRefPtr<CefBrowser> browser,
                      const CefString& title) override;
 
+  void OnAddressChange(CefRefPtr<CefBrowser> browser,
+                       CefRefPtr<CefFrame> frame,
+                       const CefString& url) override;
+
   void OnLoadingProgressChange(CefRefPtr<CefBrowser> browser,
                                double progress) override;
 
@@ -107,6 +117,12 @@ class MyClient : public CefClient,
                           CefRefPtr<CefSSLInfo> ssl_info,
                           CefRefPtr<CefCallback> callback) override;
 
+  // CefResourceRequestHandler methods:
+  ReturnValue OnBeforeResourceLoad(CefRefPtr<CefBrowser> browser,
+                                   CefRefPtr<CefFrame> frame,
+                                   CefRefPtr<CefRequest> request,
+                                   CefRefPtr<CefCallback> callback) override;
+
   // CefJSDialogHandler methods:
   bool OnBeforeUnloadDialog(CefRefPtr<CefBrowser> browser,
                             const CefString& message_text,
@@ -170,9 +186,27 @@ class MyClient : public CefClient,
                          CefRefPtr<CefDownloadItem> download_item,
                          CefRefPtr<CefDownloadItemCallback> callback) override;
 
+  // CefAudioHandler methods:
+  bool GetAudioParameters(CefRefPtr<CefBrowser> browser,
+                          CefAudioParameters& params) override;
+  void OnAudioStreamStarted(CefRefPtr<CefBrowser> browser,
+                            const CefAudioParameters& params,
+                            int channels) override;
+  void OnAudioStreamPacket(CefRefPtr<CefBrowser> browser,
+                           const float** data,
+                           int frames,
+                           int64_t pts) override;
+  void OnAudioStreamStopped(CefRefPtr<CefBrowser> browser) override;
+  void OnAudioStreamError(CefRefPtr<CefBrowser> browser,
+                          const CefString& message) override;
+
  private:
   Callbacks callbacks;
 
+  // read when a browser is created, so changes only affect new browsers
+  bool audio_capture_enabled = false;
+  int audio_sample_rate = 48000;
+
   IMPLEMENT_REFCOUNTING(MyClient);
   DISALLOW_COPY_AND_ASSIGN(MyClient);
 };
@@ -169,6 +169,83 @@ extern "C" RustRefApp cef_interface_create_app(Callbacks callbacks) {
   return cef_interface_add_ref_app(app.get());
 }
 
+// streams a file from disk, so big videos aren't read into memory
+class LocalFileResourceHandler : public CefResourceHandler {
+ public:
+  explicit LocalFileResourceHandler(const RustSchemeReturn& ret)
+      : reader_(CefStreamReader::CreateForFile(ret.file_path)),
+        mime_type_(ret.mime_type),
+        status_code_(ret.status_code),
+        response_length_(ret.response_length),
+        bytes_left_(ret.response_length) {
+    if (ret.content_range) {
+      content_range_ = ret.content_range;
+    }
+  }
+
+  bool Open(CefRefPtr<CefRequest> request,
+            bool& handle_request,
+            CefRefPtr<CefCallback> callback) override {
+    handle_request = true;
+    return reader_ != nullptr;
+  }
+
+  void GetResponseHeaders(CefRefPtr<CefResponse> response,
+                          int64_t& response_length,
+                          CefString& redirectUrl) override {
+    CefResponse::HeaderMap headers;
+    headers.insert(std::make_pair("Accept-Ranges", "bytes"));
+    if (!content_range_.empty()) {
+      headers.insert(std::make_pair("Content-Range", content_range_));
+    }
+
+    response->SetStatus(status_code_);
+    response->SetStatusText(status_code_ == 206 ? "Partial Content" : "OK");
+    response->SetMimeType(mime_type_);
+    response->SetHeaderMap(headers);
+    response_length = response_length_;
+  }
+
+  // CEF skips to the start of a Range itself
+  bool Skip(int64_t bytes_to_skip,
+            int64_t& bytes_skipped,
+            CefRefPtr<CefResourceSkipCallback> callback) override {
+    if (reader_->Seek(bytes_to_skip, SEEK_CUR) != 0) {
+      bytes_skipped = ERR_FAILED;
+      return false;
+    }
+
+    bytes_skipped = bytes_to_skip;
+    return true;
+  }
+
+  bool Read(void* data_out,
+            int bytes_to_read,
+            int& bytes_read,
+            CefRefPtr<CefResourceReadCallback> callback) override {
+    // don't read past the end of a Range
+    if (bytes_left_ < bytes_to_read) {
+      bytes_to_read = static_cast<int>(bytes_left_);
+    }
+
+    bytes_read = static_cast<int>(reader_->Read(data_out, 1, bytes_to_read));
+    bytes_left_ -= bytes_read;
+    return bytes_read > 0;
+  }
+
+  void Cancel() override {}
+
+ private:
+  CefRefPtr<CefStreamReader> reader_;
+  CefString mime_type_;
+  int status_code_;
+  CefString content_range_;
+  int64_t response_length_;
+  int64_t bytes_left_;
+
+  IMPLEMENT_REFCOUNTING(LocalFileResourceHandler);
+};
+
 // Implementation of the factory for for creating schema handlers.
 class LocalSchemeHandlerFactory : public CefSchemeHandlerFactory {
  public:
@@ -183,24 +260,69 @@ class LocalSchemeHandlerFactory : public CefSchemeHandlerFactory {
 
     std::string scheme_name_utf8 = scheme_name.ToString();
     std::string url_utf8 = request->GetURL().ToString();
-    auto ret =
-        rust_handle_scheme_create(cef_interface_add_ref_browser(browser.get()),
-                                  scheme_name_utf8.c_str(), url_utf8.c_str());
+    std::string range_utf8 = request->GetHeaderByName("Range").ToString();
+    auto ret = rust_handle_scheme_create(
+        cef_interface_add_ref_browser(browser.get()), scheme_name_utf8.c_str(),
+        url_utf8.c_str(), range_utf8.c_str());
 
     if (!ret.mime_type) {
       // an empty reference to allow default handling of the request
       return nullptr;
-    } else {
+    }
+
+    if (!ret.file_path) {
       return new CefStreamResourceHandler(
           ret.mime_type,
           CefStreamReader::CreateForData(ret.data, ret.data_size));
     }
+
+    CefRefPtr<CefResourceHandler> handler = new LocalFileResourceHandler(ret);
+    rust_free_scheme_return(ret);
+    return handler;
   }
 
   IMPLEMENT_REFCOUNTING(LocalSchemeHandlerFactory);
   DISALLOW_COPY_AND_ASSIGN(LocalSchemeHandlerFactory);
 };
 
+// nullptr registers on the global request context, other contexts don't
+// share its handlers so each needs its own
+static bool register_scheme_handlers(
+    CefRefPtr<CefRequestContext> request_context) {
+  auto register_factory = [&](const CefString& scheme_name,
+                              const CefString& domain_name) {
+    CefRefPtr<CefSchemeHandlerFactory> factory =
+        new LocalSchemeHandlerFactory();
+    if (request_context) {
+      return request_context->RegisterSchemeHandlerFactory(
+          scheme_name, domain_name, factory);
+    }
+    return CefRegisterSchemeHandlerFactory(scheme_name, domain_name, factory);
+  };
+
+  // Most player pages use the `local://` custom scheme so they can load
+  // mixed-content resources (e.g. plain http:// media streams) without
+  // Chromium blocking them.
+  if (!register_factory("local", "")) {
+    rust_warn("RegisterSchemeHandlerFactory(local) failed!");
+    return false;
+  }
+
+  // YouTube's embedder identity check (Error 152,
+  // PLAYABILITY_ERROR_CODE_EMBEDDER_IDENTITY_DENIED) reads
+  // window.location.ancestorOrigins — set by the browser from the
+  // actual frame tree — and rejects any non-http(s) ancestor. So the
+  // YouTube page alone is served from a synthetic HTTPS host, giving
+  // it a real https:// origin string. The host never resolves in DNS;
+  // the factory answers all requests for it directly.
+  if (!register_factory("https", "classicube-cef.invalid")) {
+    rust_warn("RegisterSchemeHandlerFactory(https) failed!");
+    return false;
+  }
+
+  return true;
+}
+
 extern "C" int cef_interface_initialize(MyApp* app, CefInitializePaths paths) {
 #if defined(_WIN64) || defined(_WIN32)
   set_process_dpi_unaware();
@@ -253,25 +375,7 @@ extern "C" int cef_interface_initialize(MyApp* app, CefInitializePaths paths) {
     return -1;
   }
 
-  // Most player pages use the `local://` custom scheme so they can load
-  // mixed-content resources (e.g. plain http:// media streams) without
-  // Chromium blocking them.
-  if (!CefRegisterSchemeHandlerFactory("local", "",
-                                       new LocalSchemeHandlerFactory())) {
-    rust_warn("CefRegisterSchemeHandlerFactory(local) failed!");
-    return -1;
-  }
-
-  // YouTube's embedder identity check (Error 152,
-  // PLAYABILITY_ERROR_CODE_EMBEDDER_IDENTITY_DENIED) reads
-  // window.location.ancestorOrigins — set by the browser from the
-  // actual frame tree — and rejects any non-http(s) ancestor. So the
-  // YouTube page alone is served from a synthetic HTTPS host, giving
-  // it a real https:// origin string. The host never resolves in DNS;
-  // the factory answers all requests for it directly.
-  if (!CefRegisterSchemeHandlerFactory("https", "classicube-cef.invalid",
-                                       new LocalSchemeHandlerFactory())) {
-    rust_warn("CefRegisterSchemeHandlerFactory(https) failed!");
+  if (!register_scheme_handlers(nullptr)) {
     return -1;
   }
 
@@ -280,11 +384,19 @@ extern "C" int cef_interface_initialize(MyApp* app, CefInitializePaths paths) {
 
 // Browser
 
+extern "C" int cef_interface_client_set_audio_capture(MyClient* client,
+                                                      bool enabled,
+                                                      int sample_rate) {
+  client->SetAudioCapture(enabled, sample_rate);
+  return 0;
+}
+
 extern "C" int cef_interface_create_browser(MyClient* client,
                                             const char* startup_url,
                                             int frame_rate,
                                             bool insecure,
-                                            uint32_t background_color) {
+                                            uint32_t background_color,
+                                            const char* cache_path) {
   // Create the browser window.
   CefWindowInfo window_info;
   window_info.SetAsWindowless(0);
@@ -300,25 +412,28 @@ extern "C" int cef_interface_create_browser(MyClient* client,
   settings.javascript_access_clipboard = STATE_DISABLED;
 
   CefRefPtr<CefDictionaryValue> extra_info = nullptr;
-  CefRefPtr<CefRequestContext> request_context = nullptr;
+
+  // every browser gets its own context so incognito ones don't share
+  // anything; contexts with the same cache_path share storage
+  CefRequestContextSettings request_context_settings;
+  if (cache_path) {
+    CefString(&request_context_settings.cache_path).FromString(cache_path);
+    request_context_settings.persist_session_cookies = true;
+  }
 
   // if (insecure) {
   // this is pretty useless because it needs to be on the subprocess's
   // OnBeforeCommandLineProcessing
   // settings.web_security = STATE_DISABLED;
-
-  // CefRequestContextSettings request_context_settings;
   // request_context_settings.ignore_certificate_errors = true;
-
-  // request_context =
-  //     CefRequestContext::CreateContext(request_context_settings, nullptr);
-
-  // if (!request_context->RegisterSchemeHandlerFactory(
-  //         "test", "", new TestSchemeFactory())) {
-  //   rust_warn("CefRegisterSchemeHandlerFactory");
-  // }
   // }
 
+  CefRefPtr<CefRequestContext> request_context =
+      CefRequestContext::CreateContext(request_context_settings, nullptr);
+  if (!register_scheme_handlers(request_context)) {
+    return -1;
+  }
+
   bool browser = CefBrowserHost::CreateBrowser(
       window_info, client, url, settings, extra_info, request_context);
 
@@ -485,11 +600,86 @@ extern "C" int cef_interface_browser_reload(CefBrowser* browser) {
   return 0;
 }
 
+extern "C" bool cef_interface_browser_can_go_back(CefBrowser* browser) {
+  return browser->CanGoBack();
+}
+
+extern "C" bool cef_interface_browser_can_go_forward(CefBrowser* browser) {
+  return browser->CanGoForward();
+}
+
+extern "C" int cef_interface_browser_go_back(CefBrowser* browser) {
+  if (!browser->CanGoBack()) {
+    return -1;
+  }
+
+  browser->GoBack();
+  return 0;
+}
+
+extern "C" int cef_interface_browser_go_forward(CefBrowser* browser) {
+  if (!browser->CanGoForward()) {
+    return -1;
+  }
+
+  browser->GoForward();
+  return 0;
+}
+
+extern "C" RustRefString cef_interface_browser_get_url(CefBrowser* browser) {
+  auto url = browser->GetMainFrame()->GetURL().ToString();
+  return cef_interface_new_ref_string(url.c_str(), url.length());
+}
+
+class NavigationEntryVisitor : public CefNavigationEntryVisitor {
+ public:
+  NavigationEntryVisitor(NavigationEntryCallback callback, void* data)
+      : callback(callback), data(data) {}
+
+  bool Visit(CefRefPtr<CefNavigationEntry> entry,
+             bool current,
+             int index,
+             int total) override {
+    auto url = entry->GetURL().ToString();
+    auto title = entry->GetTitle().ToString();
+    callback(data, url.c_str(), title.c_str(), current);
+
+    // keep visiting
+    return true;
+  }
+
+ private:
+  NavigationEntryCallback callback;
+  void* data;
+
+  IMPLEMENT_REFCOUNTING(NavigationEntryVisitor);
+  DISALLOW_COPY_AND_ASSIGN(NavigationEntryVisitor);
+};
+
+extern "C" int cef_interface_browser_get_navigation_entries(
+    CefBrowser* browser,
+    NavigationEntryCallback callback,
+    void* data) {
+  if (!CefCurrentlyOn(TID_UI)) {
+    // the visitor would run later, after `data` is gone
+    return -1;
+  }
+
+  browser->GetHost()->GetNavigationEntries(
+      new NavigationEntryVisitor(callback, data), false);
+  return 0;
+}
+
 extern "C" int cef_interface_browser_was_resized(CefBrowser* browser) {
   browser->GetHost()->WasResized();
   return 0;
 }
 
+extern "C" int cef_interface_browser_invalidate(CefBrowser* browser) {
+  browser->GetHost()->Invalidate(PET_VIEW);
+  return 0;
+}
+
 extern "C" int cef_interface_browser_open_dev_tools(CefBrowser* browser) {
   auto browser_host = browser->GetHost();
 
@@ -63,6 +63,17 @@ typedef void (*OnLoadEndCallback)(RustRefBrowser browser);
 typedef void (*OnTitleChangeCallback)(RustRefBrowser browser,
                                       const char* title);
 
+/// Called when the MAIN frame navigates, including back/forward and
+/// in-page changes.
+typedef void (*OnAddressChangeCallback)(RustRefBrowser browser,
+                                        const char* url);
+
+/// Called once for each history entry, oldest first.
+typedef void (*NavigationEntryCallback)(void* data,
+                                        const char* url,
+                                        const char* title,
+                                        bool current);
+
 struct RustRect {
   int x;
   int y;
@@ -113,6 +124,48 @@ typedef void (*OnJavascriptCallback)(RustRefBrowser browser,
 
 typedef bool (*OnCertificateErrorCallback)(RustRefBrowser browser);
 
+/// Called instead of opening a popup window, return true to load
+/// `target_url` in the frame that opened it.
+typedef bool (*OnBeforePopupCallback)(RustRefBrowser browser,
+                                      const char* target_url,
+                                      bool user_gesture);
+
+enum class FFIResourceType : uint8_t {
+  Document,
+  Subdocument,
+  Stylesheet,
+  Script,
+  Image,
+  Font,
+  Object,
+  Media,
+  XmlHttpRequest,
+  Ping,
+  Other,
+};
+
+/// Called on the IO thread before each request a browser makes, return true
+/// to cancel it. `first_party_url` is the page it's for, and can be empty.
+typedef bool (*OnBeforeResourceLoadCallback)(RustRefBrowser browser,
+                                             const char* url,
+                                             const char* first_party_url,
+                                             FFIResourceType resource_type);
+
+/// Called on the audio capture thread when a browser starts playing audio.
+typedef void (*OnAudioStreamStartedCallback)(RustRefBrowser browser,
+                                             int sample_rate,
+                                             int channels);
+
+/// Called on the audio capture thread with planar float PCM,
+/// `data[channel][frame]`, using the channel count from stream started.
+typedef void (*OnAudioStreamPacketCallback)(RustRefBrowser browser,
+                                            const float** data,
+                                            int frames);
+
+/// Called on the audio capture thread when a browser stops playing audio,
+/// or the stream errored.
+typedef void (*OnAudioStreamStoppedCallback)(RustRefBrowser browser);
+
 struct Callbacks {
   OnContextInitializedCallback on_context_initialized;
   OnAfterCreatedCallback on_after_created;
@@ -120,9 +173,15 @@ struct Callbacks {
   OnPaintCallback on_paint;
   OnLoadEndCallback on_load_end;
   OnTitleChangeCallback on_title_change;
+  OnAddressChangeCallback on_address_change;
   GetViewRectCallback get_view_rect;
   OnJavascriptCallback on_javascript;
   OnCertificateErrorCallback on_certificate_error;
+  OnBeforePopupCallback on_before_popup;
+  OnBeforeResourceLoadCallback on_before_resource_load;
+  OnAudioStreamStartedCallback on_audio_stream_started;
+  OnAudioStreamPacketCallback on_audio_stream_packet;
+  OnAudioStreamStoppedCallback on_audio_stream_stopped;
 };
 
 struct CefInitializePaths {
@@ -150,13 +209,22 @@ extern "C" int cef_interface_step();
 
 extern "C" int cef_interface_initialize(MyApp* app, CefInitializePaths paths);
 
+// Client
+
+/// Send audio of browsers created after this to the audio callbacks instead
+/// of the speakers.
+extern "C" int cef_interface_client_set_audio_capture(MyClient* client,
+                                                      bool enabled,
+                                                      int sample_rate);
+
 // Browser
 
 extern "C" int cef_interface_create_browser(MyClient* client,
                                             const char* startup_url,
                                             int frame_rate,
                                             bool insecure,
-                                            uint32_t background_color);
+                                            uint32_t background_color,
+                                            const char* cache_path);
 extern "C" int cef_interface_browser_get_identifier(CefBrowser* browser);
 extern "C" int cef_interface_browser_load_url(CefBrowser* browser,
                                               const char* url);
@@ -180,8 +248,22 @@ extern "C" int cef_interface_browser_send_click(CefBrowser* browser,
 extern "C" int cef_interface_browser_send_text(CefBrowser* browser,
                                                const char* text);
 extern "C" int cef_interface_browser_reload(CefBrowser* browser);
+extern "C" bool cef_interface_browser_can_go_back(CefBrowser* browser);
+extern "C" bool cef_interface_browser_can_go_forward(CefBrowser* browser);
+extern "C" int cef_interface_browser_go_back(CefBrowser* browser);
+extern "C" int cef_interface_browser_go_forward(CefBrowser* browser);
+/// must call cef_interface_delete_ref_string
+extern "C" RustRefString cef_interface_browser_get_url(CefBrowser* browser);
+/// Calls `callback` for every history entry before returning, must be on the
+/// UI thread.
+extern "C" int cef_interface_browser_get_navigation_entries(
+    CefBrowser* browser,
+    NavigationEntryCallback callback,
+    void* data);
 
 extern "C" int cef_interface_browser_was_resized(CefBrowser* browser);
+/// Force a repaint, OnPaint will be called soon
+extern "C" int cef_interface_browser_invalidate(CefBrowser* browser);
 extern "C" int cef_interface_browser_open_dev_tools(CefBrowser* browser);
 extern "C" int cef_interface_browser_set_audio_muted(CefBrowser* browser,
                                                      bool mute);
@@ -195,12 +277,25 @@ extern "C" void rust_debug(const char* c_str);
 extern "C" void rust_warn(const char* c_str);
 
 struct RustSchemeReturn {
-  /// must be a static data
+  /// must be a static data, or null to stream `file_path` instead
   void* data;
   size_t data_size;
+  /// owned, give it back with rust_free_scheme_return
+  char* file_path;
 
+  /// must be static
   const char* mime_type;
+
+  /// 200, or 206 for part of a file
+  int status_code;
+  /// owned, null unless 206
+  char* content_range;
+  /// bytes sent, for files
+  int64_t response_length;
 };
+/// `range` is the request's Range header, or empty
 extern "C" RustSchemeReturn rust_handle_scheme_create(RustRefBrowser browser,
                                                       const char* scheme_name,
-                                                      const char* url);
+                                                      const char* url,
+                                                      const char* range);
+extern "C" void rust_free_scheme_return(RustSchemeReturn ret);
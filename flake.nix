@@ -94,7 +94,7 @@
               rustPlatform.bindgenHook
             ];
 
-            buildInputs = with pkgs; [ cb openssl zstd ];
+            buildInputs = with pkgs; [ alsa-lib cb openssl zstd ];
 
             LIBCEF_LIB_DIR = "${cb}/lib";
             LIBCEF_INCLUDE_DIR = "${cb}/include";
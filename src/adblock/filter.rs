@@ -0,0 +1,779 @@
+//! EasyList style request filters
+//!
+//! supports `||domain^` and `|` anchors, `*` and `^`, `/regex/`, `@@`
+//! exceptions and the `$third-party`, `$domain=`, `$match-case` and
+//! resource type options. element hiding rules, and rules with options we
+//! don't know, are skipped instead of blocking more than they should.
+
+use std::collections::HashMap;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceType {
+    /// the page itself, never blocked
+    Document,
+    Subdocument,
+    Stylesheet,
+    Script,
+    Image,
+    Font,
+    Object,
+    Media,
+    XmlHttpRequest,
+    Ping,
+    Other,
+}
+
+impl ResourceType {
+    const fn bit(self) -> u16 {
+        1 << self as u16
+    }
+
+    fn from_option(name: &str) -> Option<Self> {
+        Some(match name {
+            "document" | "doc" => Self::Document,
+            "subdocument" | "frame" => Self::Subdocument,
+            "stylesheet" | "css" => Self::Stylesheet,
+            "script" => Self::Script,
+            "image" => Self::Image,
+            "font" => Self::Font,
+            "object" => Self::Object,
+            "media" => Self::Media,
+            "xmlhttprequest" | "xhr" => Self::XmlHttpRequest,
+            "ping" => Self::Ping,
+            "other" | "websocket" => Self::Other,
+            _ => return None,
+        })
+    }
+}
+
+/// every type but `Document`, what rules without type options apply to
+const DEFAULT_TYPES: u16 = !ResourceType::Document.bit();
+
+#[derive(Debug)]
+enum Anchor {
+    None,
+    /// `|`, the start of the url
+    Start,
+    /// `||`, the start of the host or any of its subdomains
+    Domain,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Wildcard {
+        anchor: Anchor,
+        /// lowercase unless `$match-case`
+        text: String,
+        /// ends with `|`
+        end_anchor: bool,
+    },
+    Regex(Regex),
+}
+
+/// what comes after `$`
+#[derive(Debug)]
+struct FilterOptions {
+    match_case: bool,
+    types: u16,
+    third_party: Option<bool>,
+    /// domain, whether it's included or excluded with `~`
+    domains: Vec<(String, bool)>,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        Self {
+            match_case: false,
+            types: DEFAULT_TYPES,
+            third_party: None,
+            domains: Vec::new(),
+        }
+    }
+}
+
+impl FilterOptions {
+    /// None if we don't support one of them
+    fn parse(options: &str) -> Option<Self> {
+        let mut parsed = Self::default();
+        let mut included_types = 0;
+        let mut excluded_types = 0;
+
+        for option in options.split(',') {
+            let option = option.trim().to_ascii_lowercase();
+            let (inverted, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, option.as_str()),
+            };
+
+            if let Some(resource_type) = ResourceType::from_option(name) {
+                if inverted {
+                    excluded_types |= resource_type.bit();
+                } else {
+                    included_types |= resource_type.bit();
+                }
+            } else if let Some(domains) = name.strip_prefix("domain=") {
+                parsed.domains = domains
+                    .split('|')
+                    .filter(|domain| !domain.is_empty())
+                    .map(|domain| match domain.strip_prefix('~') {
+                        Some(domain) => (domain.to_string(), false),
+                        None => (domain.to_string(), true),
+                    })
+                    .collect();
+            } else {
+                match (name, inverted) {
+                    ("third-party" | "3p", false) | ("first-party" | "1p", true) => {
+                        parsed.third_party = Some(true);
+                    }
+                    ("third-party" | "3p", true) | ("first-party" | "1p", false) => {
+                        parsed.third_party = Some(false);
+                    }
+                    ("match-case", false) => parsed.match_case = true,
+                    // only changes which of two matching rules wins
+                    ("important", false) => {}
+                    _ => return None,
+                }
+            }
+        }
+
+        if included_types != 0 {
+            parsed.types = included_types;
+        }
+        parsed.types &= !excluded_types;
+
+        Some(parsed)
+    }
+
+    fn is_match(&self, request: &Request) -> bool {
+        if self.types & request.resource_type.bit() == 0 {
+            return false;
+        }
+
+        if let Some(third_party) = self.third_party
+            && request.is_third_party() != Some(third_party)
+        {
+            return false;
+        }
+
+        if self.domains.is_empty() {
+            return true;
+        }
+
+        let on_domain = |domain: &str| is_same_or_subdomain(&request.first_party_host, domain);
+        let mut includes = self
+            .domains
+            .iter()
+            .filter(|(_, include)| *include)
+            .peekable();
+        let is_included =
+            includes.peek().is_none() || includes.any(|(domain, _)| on_domain(domain));
+        let is_excluded = self
+            .domains
+            .iter()
+            .any(|(domain, include)| !include && on_domain(domain));
+
+        is_included && !is_excluded
+    }
+}
+
+#[derive(Debug)]
+struct Filter {
+    pattern: Pattern,
+    options: FilterOptions,
+}
+
+impl Filter {
+    /// whether it's an exception, None if it isn't a request filter or uses
+    /// options we don't support
+    fn parse(line: &str) -> Option<(bool, Self)> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+            return None;
+        }
+
+        // element hiding and scriptlets
+        if ["##", "#@#", "#?#", "#$#", "#%#"]
+            .iter()
+            .any(|separator| line.contains(separator))
+        {
+            return None;
+        }
+
+        let (exception, line) = match line.strip_prefix("@@") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+
+        // a regex can have `$` in it, options only come after its last `/`
+        let options_start = if line.starts_with('/') {
+            line.rfind('/')
+                .and_then(|end| line[end..].find('$').map(|i| end + i))
+        } else {
+            line.rfind('$')
+        };
+        let (pattern, options) = match options_start {
+            Some(i) => (&line[..i], Some(&line[i + 1..])),
+            None => (line, None),
+        };
+
+        let options = match options {
+            Some(options) => FilterOptions::parse(options)?,
+            None => FilterOptions::default(),
+        };
+        let pattern = parse_pattern(pattern, options.match_case)?;
+
+        Some((exception, Self { pattern, options }))
+    }
+
+    fn is_match(&self, request: &Request) -> bool {
+        if !self.options.is_match(request) {
+            return false;
+        }
+
+        let url = if self.options.match_case {
+            &request.url
+        } else {
+            &request.lowercase_url
+        };
+
+        match &self.pattern {
+            Pattern::Regex(regex) => regex.is_match(url),
+
+            Pattern::Wildcard {
+                anchor,
+                text,
+                end_anchor,
+            } => {
+                let text = text.as_bytes();
+                let url = url.as_bytes();
+
+                match anchor {
+                    Anchor::Start => wildcard_match(text, url, *end_anchor),
+
+                    Anchor::Domain => request
+                        .host_label_starts()
+                        .any(|start| wildcard_match(text, &url[start..], *end_anchor)),
+
+                    Anchor::None => (0..=url.len())
+                        .any(|start| wildcard_match(text, &url[start..], *end_anchor)),
+                }
+            }
+        }
+    }
+
+    /// a word the url must contain for this to match, used to skip most
+    /// rules without trying them
+    fn get_keyword(&self) -> Option<String> {
+        let Pattern::Wildcard {
+            anchor,
+            text,
+            end_anchor,
+        } = &self.pattern
+        else {
+            return None;
+        };
+
+        let text = text.to_ascii_lowercase();
+        let bytes = text.as_bytes();
+
+        let mut best: Option<&str> = None;
+        let mut start = 0;
+        for end in 0..=bytes.len() {
+            if end < bytes.len() && is_token_char(bytes[end]) {
+                continue;
+            }
+
+            // the whole token has to be in the pattern, not a piece of a
+            // longer word in the url
+            let bounded_before = if start == 0 {
+                !matches!(anchor, Anchor::None)
+            } else {
+                bytes[start - 1] != b'*'
+            };
+            let bounded_after = if end == bytes.len() {
+                *end_anchor
+            } else {
+                bytes[end] != b'*'
+            };
+
+            let token = &text[start..end];
+            if bounded_before
+                && bounded_after
+                && token.len() >= 2
+                && best.is_none_or(|best| token.len() > best.len())
+            {
+                best = Some(token);
+            }
+
+            start = end + 1;
+        }
+
+        best.map(ToString::to_string)
+    }
+}
+
+fn parse_pattern(pattern: &str, match_case: bool) -> Option<Pattern> {
+    if pattern.len() > 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+        let regex = RegexBuilder::new(&pattern[1..pattern.len() - 1])
+            .case_insensitive(!match_case)
+            .build()
+            .ok()?;
+        return Some(Pattern::Regex(regex));
+    }
+
+    let (anchor, pattern) = if let Some(pattern) = pattern.strip_prefix("||") {
+        (Anchor::Domain, pattern)
+    } else if let Some(pattern) = pattern.strip_prefix('|') {
+        (Anchor::Start, pattern)
+    } else {
+        (Anchor::None, pattern)
+    };
+
+    let (end_anchor, pattern) = match pattern.strip_suffix('|') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+
+    let mut text = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if c == '*' && text.ends_with('*') {
+            continue;
+        }
+        text.push(if match_case {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        });
+    }
+
+    // a `*` at the end is the same as nothing
+    if !end_anchor {
+        while text.ends_with('*') {
+            text.pop();
+        }
+    }
+
+    Some(Pattern::Wildcard {
+        anchor,
+        text,
+        end_anchor,
+    })
+}
+
+/// whether `pattern` matches the start of `text`, or all of it with
+/// `end_anchor`
+///
+/// `*` matches anything, `^` matches a separator or the end of the url
+fn wildcard_match(pattern: &[u8], text: &[u8], end_anchor: bool) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // where to retry from if what follows the last `*` doesn't match
+    let mut star: Option<(usize, usize)> = None;
+
+    loop {
+        if p == pattern.len() {
+            if !end_anchor || t == text.len() {
+                return true;
+            }
+        } else if pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+            continue;
+        } else if t < text.len() && char_match(pattern[p], text[t]) {
+            p += 1;
+            t += 1;
+            continue;
+        } else if pattern[p] == b'^' && t == text.len() {
+            p += 1;
+            continue;
+        }
+
+        match star {
+            Some((star_p, star_t)) if star_t < text.len() => {
+                star = Some((star_p, star_t + 1));
+                p = star_p + 1;
+                t = star_t + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn char_match(pattern: u8, c: u8) -> bool {
+    if pattern == b'^' {
+        is_separator(c)
+    } else {
+        pattern == c
+    }
+}
+
+fn is_separator(c: u8) -> bool {
+    !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'%'))
+}
+
+fn is_token_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'%'
+}
+
+fn is_same_or_subdomain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+}
+
+/// close enough without a public suffix list, `a.b.example.com` and
+/// `example.co.uk` give `example.com` and `example.co.uk`
+fn get_base_domain(host: &str) -> &str {
+    let labels = host.rsplit('.').collect::<Vec<_>>();
+    let count = match labels.as_slice() {
+        [top, second, _, ..] if top.len() == 2 && second.len() <= 3 => 3,
+        _ => 2,
+    };
+
+    let len = labels
+        .iter()
+        .take(count)
+        .map(|label| label.len() + 1)
+        .sum::<usize>()
+        .saturating_sub(1);
+    &host[host.len().saturating_sub(len)..]
+}
+
+/// lowercase host and where it is in the url
+fn get_host(url: &str) -> (String, usize) {
+    let Some(scheme_end) = url.find("://") else {
+        return (String::new(), 0);
+    };
+
+    let start = scheme_end + 3;
+    let authority = &url[start..];
+    let authority = &authority[..authority.find(['/', '?', '#']).unwrap_or(authority.len())];
+
+    // user:pass@
+    let (start, authority) = match authority.rfind('@') {
+        Some(i) => (start + i + 1, &authority[i + 1..]),
+        None => (start, authority),
+    };
+    let host = &authority[..authority.find(':').unwrap_or(authority.len())];
+
+    (host.to_ascii_lowercase(), start)
+}
+
+pub struct Request {
+    url: String,
+    lowercase_url: String,
+    host: String,
+    host_start: usize,
+    first_party_url: String,
+    first_party_host: String,
+    resource_type: ResourceType,
+}
+
+impl Request {
+    /// `first_party_url` is the page the request is for
+    pub fn new(url: &str, first_party_url: &str, resource_type: ResourceType) -> Self {
+        let (host, host_start) = get_host(url);
+        let (first_party_host, _) = get_host(first_party_url);
+
+        Self {
+            url: url.to_string(),
+            lowercase_url: url.to_ascii_lowercase(),
+            host,
+            host_start,
+            first_party_url: first_party_url.to_string(),
+            first_party_host,
+            resource_type,
+        }
+    }
+
+    /// None if we don't know the page
+    fn is_third_party(&self) -> Option<bool> {
+        if self.first_party_host.is_empty() {
+            return None;
+        }
+
+        Some(get_base_domain(&self.host) != get_base_domain(&self.first_party_host))
+    }
+
+    /// where `||` rules can start, the host and each of its subdomains
+    fn host_label_starts(&self) -> impl Iterator<Item = usize> + '_ {
+        let dots = self
+            .host
+            .match_indices('.')
+            .map(|(i, _)| self.host_start + i + 1);
+        (!self.host.is_empty())
+            .then_some(self.host_start)
+            .into_iter()
+            .chain(dots)
+    }
+
+    fn keywords(&self) -> impl Iterator<Item = &str> {
+        self.lowercase_url
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '%'))
+            .filter(|token| token.len() >= 2)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FilterIndex {
+    by_keyword: HashMap<String, Vec<Filter>>,
+    /// no keyword to look them up by, always tried
+    other: Vec<Filter>,
+}
+
+impl FilterIndex {
+    fn insert(&mut self, filter: Filter) {
+        match filter.get_keyword() {
+            Some(keyword) => self.by_keyword.entry(keyword).or_default().push(filter),
+            None => self.other.push(filter),
+        }
+    }
+
+    fn is_match(&self, request: &Request) -> bool {
+        request
+            .keywords()
+            .filter_map(|keyword| self.by_keyword.get(keyword))
+            .flatten()
+            .chain(&self.other)
+            .any(|filter| filter.is_match(request))
+    }
+
+    fn len(&self) -> usize {
+        self.by_keyword.values().map(Vec::len).sum::<usize>() + self.other.len()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterList {
+    blocking: FilterIndex,
+    exceptions: FilterIndex,
+}
+
+impl FilterList {
+    /// adds the rules of a filter list file, returns how many were used
+    pub fn add_rules(&mut self, text: &str) -> usize {
+        let mut count = 0;
+        for line in text.lines() {
+            if let Some((exception, filter)) = Filter::parse(line) {
+                if exception {
+                    self.exceptions.insert(filter);
+                } else {
+                    self.blocking.insert(filter);
+                }
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocking.len() + self.exceptions.len()
+    }
+
+    pub fn should_block(&self, request: &Request) -> bool {
+        if request.resource_type == ResourceType::Document
+            || !self.blocking.is_match(request)
+            || self.exceptions.is_match(request)
+        {
+            return false;
+        }
+
+        // `@@||site^$document` turns blocking off for the whole page
+        let page = Request::new(
+            &request.first_party_url,
+            &request.first_party_url,
+            ResourceType::Document,
+        );
+        !self.exceptions.is_match(&page)
+    }
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match(b"ads", b"ads/banner.png", false));
+    assert!(!wildcard_match(b"ads", b"ads/banner.png", true));
+    assert!(wildcard_match(b"ads*.png", b"ads/banner.png", true));
+    assert!(wildcard_match(b"example.com^", b"example.com/a", false));
+    assert!(wildcard_match(b"example.com^", b"example.com", false));
+    assert!(wildcard_match(b"example.com^", b"example.com:8080/", false));
+    assert!(!wildcard_match(
+        b"example.com^",
+        b"example.com.evil/",
+        false
+    ));
+    assert!(!wildcard_match(
+        b"example.com^",
+        b"example.community/",
+        false
+    ));
+    assert!(wildcard_match(b"a*b*c", b"aXbYbZc", true));
+    assert!(!wildcard_match(b"a*b*c", b"aXbYbZ", true));
+}
+
+#[test]
+fn test_get_base_domain() {
+    assert_eq!(get_base_domain("ads.example.com"), "example.com");
+    assert_eq!(get_base_domain("example.com"), "example.com");
+    assert_eq!(get_base_domain("cdn.example.co.uk"), "example.co.uk");
+    assert_eq!(get_base_domain("localhost"), "localhost");
+    assert_eq!(
+        get_host("https://user@Ads.Example.com:443/x").0,
+        "ads.example.com"
+    );
+}
+
+#[test]
+fn test_filter_list() {
+    let mut list = FilterList::default();
+    let count = list.add_rules(
+        "[Adblock Plus 2.0]\n\
+         ! comment\n\
+         ||doubleclick.net^\n\
+         ||tracker.com^$third-party\n\
+         /banner/*/ad_\n\
+         |http://plain.example/\n\
+         .swf|\n\
+         ||cdn.site.com/ads/$script,domain=news.com|~sports.news.com\n\
+         /\\/pixel\\d+\\.gif/\n\
+         @@||doubleclick.net/allowed/\n\
+         @@||trusted.org^$document\n\
+         ##.ad-banner\n\
+         ||example.com^$redirect=noop.js\n\
+         ||popups.com^$popup\n",
+    );
+    assert_eq!(count, 9);
+    assert_eq!(list.len(), 9);
+
+    let blocked = |url: &str, page: &str, resource_type: ResourceType| {
+        list.should_block(&Request::new(url, page, resource_type))
+    };
+    let page = "https://news.com/article";
+
+    // domain anchors
+    assert!(blocked(
+        "https://doubleclick.net/ad.js",
+        page,
+        ResourceType::Script
+    ));
+    assert!(blocked(
+        "https://ad.g.DoubleClick.net/x",
+        page,
+        ResourceType::Image
+    ));
+    assert!(!blocked(
+        "https://notdoubleclick.net/x",
+        page,
+        ResourceType::Image
+    ));
+    assert!(!blocked(
+        "https://doubleclick.network/x",
+        page,
+        ResourceType::Image
+    ));
+
+    // never the page itself
+    assert!(!blocked(
+        "https://doubleclick.net/",
+        page,
+        ResourceType::Document
+    ));
+
+    // exceptions
+    assert!(!blocked(
+        "https://doubleclick.net/allowed/x.js",
+        page,
+        ResourceType::Script
+    ));
+    assert!(!blocked(
+        "https://doubleclick.net/ad.js",
+        "https://www.trusted.org/",
+        ResourceType::Script
+    ));
+
+    // third-party
+    assert!(blocked(
+        "https://tracker.com/t.js",
+        page,
+        ResourceType::Script
+    ));
+    assert!(!blocked(
+        "https://tracker.com/t.js",
+        "https://www.tracker.com/",
+        ResourceType::Script
+    ));
+
+    // wildcards, start and end anchors
+    assert!(blocked(
+        "https://site.com/banner/123/ad_1.png",
+        page,
+        ResourceType::Image
+    ));
+    assert!(!blocked(
+        "https://site.com/banner/ad_1.png",
+        page,
+        ResourceType::Image
+    ));
+    assert!(blocked("http://plain.example/x", page, ResourceType::Other));
+    assert!(!blocked(
+        "https://plain.example/x",
+        page,
+        ResourceType::Other
+    ));
+    assert!(blocked(
+        "https://a.com/movie.swf",
+        page,
+        ResourceType::Object
+    ));
+    assert!(!blocked(
+        "https://a.com/movie.swf?x",
+        page,
+        ResourceType::Object
+    ));
+
+    // types and domains
+    assert!(blocked(
+        "https://cdn.site.com/ads/a.js",
+        page,
+        ResourceType::Script
+    ));
+    assert!(!blocked(
+        "https://cdn.site.com/ads/a.png",
+        page,
+        ResourceType::Image
+    ));
+    assert!(!blocked(
+        "https://cdn.site.com/ads/a.js",
+        "https://other.com/",
+        ResourceType::Script
+    ));
+    assert!(!blocked(
+        "https://cdn.site.com/ads/a.js",
+        "https://sports.news.com/",
+        ResourceType::Script
+    ));
+
+    // regex
+    assert!(blocked(
+        "https://a.com/pixel42.gif",
+        page,
+        ResourceType::Image
+    ));
+    assert!(!blocked(
+        "https://a.com/pixel.gif",
+        page,
+        ResourceType::Image
+    ));
+
+    // skipped rules
+    assert!(!blocked(
+        "https://example.com/x.js",
+        page,
+        ResourceType::Script
+    ));
+    assert!(!blocked(
+        "https://popups.com/",
+        page,
+        ResourceType::Subdocument
+    ));
+}
@@ -0,0 +1,190 @@
+//! cancels requests for ads and trackers, using EasyList style filter lists
+//! from `cef/filters/*.txt`
+
+mod filter;
+
+use std::{
+    collections::HashMap,
+    env,
+    ffi::CStr,
+    fs, io,
+    os::raw::{c_char, c_int},
+    path::PathBuf,
+    sync::{Mutex, RwLock},
+};
+
+use classicube_helpers::async_manager;
+use lazy_static::lazy_static;
+use tracing::{debug, warn};
+
+use self::filter::{FilterList, Request, ResourceType};
+use crate::{
+    cef::{FFIResourceType, RustRefBrowser},
+    error::{Error, Result, ResultExt},
+    options::ADBLOCK,
+};
+
+lazy_static! {
+    // read on CEF's IO thread
+    static ref FILTERS: RwLock<FilterList> = RwLock::default();
+
+    // browser id, shared with CEF's IO thread
+    static ref BROWSERS: Mutex<HashMap<c_int, BrowserState>> = Mutex::default();
+}
+
+#[derive(Debug, Default)]
+struct BrowserState {
+    enabled: bool,
+    blocked_count: usize,
+}
+
+fn get_dir() -> Result<PathBuf> {
+    Ok(env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("filters"))
+}
+
+/// reads the filter lists again, returns how many rules are used
+pub async fn reload() -> Result<usize> {
+    let dir = get_dir()?;
+
+    let filters = async_manager::spawn(async move {
+        let mut filters = FilterList::default();
+        if !dir.is_dir() {
+            return Ok::<_, Error>(filters);
+        }
+
+        let mut paths = fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        for path in paths {
+            if !path.is_file() || path.extension().is_none_or(|extension| extension != "txt") {
+                continue;
+            }
+
+            let text = fs::read_to_string(&path)
+                .chain_err(|| format!("couldn't read {}", path.display()))?;
+            let count = filters.add_rules(&text);
+            debug!("{} rules from {}", count, path.display());
+        }
+
+        Ok(filters)
+    })
+    .await??;
+
+    let count = filters.len();
+    *FILTERS.write().unwrap() = filters;
+
+    Ok(count)
+}
+
+pub fn initialize() {
+    async_manager::spawn_local_on_main_thread(async {
+        match reload().await {
+            Ok(count) => debug!("{} filter rules", count),
+            Err(e) => warn!("couldn't load filter lists: {}", e),
+        }
+    });
+}
+
+pub fn shutdown() {
+    BROWSERS.lock().unwrap().clear();
+}
+
+pub fn on_browser_created(browser_id: c_int) {
+    let enabled = ADBLOCK.get().unwrap_or_else(|e| {
+        warn!("{}", e);
+        ADBLOCK.default()
+    });
+
+    BROWSERS.lock().unwrap().insert(
+        browser_id,
+        BrowserState {
+            enabled,
+            blocked_count: 0,
+        },
+    );
+}
+
+pub fn on_browser_closed(browser_id: c_int) {
+    BROWSERS.lock().unwrap().remove(&browser_id);
+}
+
+pub fn set_enabled(browser_id: c_int, enabled: bool) {
+    BROWSERS
+        .lock()
+        .unwrap()
+        .entry(browser_id)
+        .or_default()
+        .enabled = enabled;
+}
+
+pub fn is_enabled(browser_id: c_int) -> bool {
+    BROWSERS
+        .lock()
+        .is_ok_and(|browsers| browsers.get(&browser_id).is_some_and(|state| state.enabled))
+}
+
+/// requests cancelled since the browser was created
+pub fn get_blocked_count(browser_id: c_int) -> usize {
+    BROWSERS.lock().map_or(0, |browsers| {
+        browsers
+            .get(&browser_id)
+            .map_or(0, |state| state.blocked_count)
+    })
+}
+
+impl From<FFIResourceType> for ResourceType {
+    fn from(resource_type: FFIResourceType) -> Self {
+        match resource_type {
+            FFIResourceType::Document => Self::Document,
+            FFIResourceType::Subdocument => Self::Subdocument,
+            FFIResourceType::Stylesheet => Self::Stylesheet,
+            FFIResourceType::Script => Self::Script,
+            FFIResourceType::Image => Self::Image,
+            FFIResourceType::Font => Self::Font,
+            FFIResourceType::Object => Self::Object,
+            FFIResourceType::Media => Self::Media,
+            FFIResourceType::XmlHttpRequest => Self::XmlHttpRequest,
+            FFIResourceType::Ping => Self::Ping,
+            FFIResourceType::Other => Self::Other,
+        }
+    }
+}
+
+// called on CEF's IO thread, only touch FILTERS and BROWSERS!
+
+/// returns whether to cancel the request
+pub extern "C" fn on_before_resource_load(
+    browser: RustRefBrowser,
+    url: *const c_char,
+    first_party_url: *const c_char,
+    resource_type: FFIResourceType,
+) -> bool {
+    let browser_id = browser.get_identifier();
+    if !is_enabled(browser_id) {
+        return false;
+    }
+
+    let url = unsafe { CStr::from_ptr(url) }.to_string_lossy();
+    let first_party_url = unsafe { CStr::from_ptr(first_party_url) }.to_string_lossy();
+    let request = Request::new(&url, &first_party_url, resource_type.into());
+
+    let Ok(filters) = FILTERS.read() else {
+        return false;
+    };
+    if !filters.should_block(&request) {
+        return false;
+    }
+
+    if let Ok(mut browsers) = BROWSERS.lock()
+        && let Some(state) = browsers.get_mut(&browser_id)
+    {
+        state.blocked_count += 1;
+    }
+
+    true
+}
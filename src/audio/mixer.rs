@@ -0,0 +1,329 @@
+//! mixes every captured browser stream down to one stereo output
+
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::PI,
+    os::raw::c_int,
+};
+
+/// drop the oldest audio if a stream gets further ahead than this,
+/// so a stalled output doesn't leave us seconds behind the video
+const MAX_LATENCY_SECONDS: f32 = 0.2;
+
+/// gain changes are smoothed over roughly this long to avoid clicks
+const GAIN_SMOOTHING_SECONDS: f32 = 0.02;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StereoGain {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl StereoGain {
+    pub const SILENT: Self = Self {
+        left: 0.0,
+        right: 0.0,
+    };
+
+    /// `volume` 0-1, `pan` -1 left to 1 right, same balance law as
+    /// WebAudio's StereoPannerNode for stereo input
+    pub fn new(volume: f32, pan: f32) -> Self {
+        let pan = pan.clamp(-1.0, 1.0);
+
+        Self {
+            left: volume * (1.0 - pan).min(1.0),
+            right: volume * (1.0 + pan).min(1.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Stream {
+    channels: usize,
+    buffer: VecDeque<[f32; 2]>,
+
+    gain: StereoGain,
+    target_gain: StereoGain,
+
+    /// one-pole coefficient, 1.0 passes everything through
+    low_pass_alpha: f32,
+    low_pass_state: [f32; 2],
+}
+
+#[derive(Debug)]
+pub struct Mixer {
+    sample_rate: u32,
+
+    // browser_id, stream
+    streams: HashMap<c_int, Stream>,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// drops every stream and switches to a new output rate
+    pub fn reset(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.streams.clear();
+    }
+
+    pub fn start_stream(&mut self, id: c_int, channels: usize) {
+        self.streams.insert(
+            id,
+            Stream {
+                channels,
+                buffer: VecDeque::new(),
+                // silent until the first update tells us how loud it should be
+                gain: StereoGain::SILENT,
+                target_gain: StereoGain::SILENT,
+                low_pass_alpha: 1.0,
+                low_pass_state: [0.0; 2],
+            },
+        );
+    }
+
+    pub fn stop_stream(&mut self, id: c_int) {
+        self.streams.remove(&id);
+    }
+
+    pub fn get_channels(&self, id: c_int) -> Option<usize> {
+        self.streams.get(&id).map(|stream| stream.channels)
+    }
+
+    pub fn stream_ids(&self) -> impl Iterator<Item = c_int> + '_ {
+        self.streams.keys().copied()
+    }
+
+    /// planar samples, `planes[channel][frame]`
+    pub fn push(&mut self, id: c_int, planes: &[&[f32]]) {
+        let max_frames = self.max_buffered_frames();
+        let Some(stream) = self.streams.get_mut(&id) else {
+            return;
+        };
+        let Some(first) = planes.first() else {
+            return;
+        };
+
+        // mono goes to both sides, anything past stereo is dropped
+        let second = planes.get(1).unwrap_or(first);
+        stream
+            .buffer
+            .extend(first.iter().zip(second.iter()).map(|(&l, &r)| [l, r]));
+
+        if stream.buffer.len() > max_frames {
+            let excess = stream.buffer.len() - max_frames;
+            stream.buffer.drain(..excess);
+        }
+    }
+
+    pub fn set_gain(&mut self, id: c_int, gain: StereoGain) {
+        if let Some(stream) = self.streams.get_mut(&id) {
+            stream.target_gain = gain;
+        }
+    }
+
+    /// cutoff in hertz, `None` removes the filter
+    pub fn set_low_pass(&mut self, id: c_int, frequency: Option<f32>) {
+        let alpha = frequency.map_or(1.0, |frequency| {
+            let nyquist = self.sample_rate as f32 / 2.0;
+            if frequency >= nyquist {
+                1.0
+            } else {
+                1.0 - (-2.0 * PI * frequency.max(1.0) / self.sample_rate as f32).exp()
+            }
+        });
+
+        if let Some(stream) = self.streams.get_mut(&id) {
+            stream.low_pass_alpha = alpha;
+        }
+    }
+
+    /// fills interleaved `out` with every stream's next frames, streams that
+    /// haven't caught up yet are silent for the missing part
+    pub fn mix(&mut self, out: &mut [f32], channels: usize) {
+        out.fill(0.0);
+        if channels == 0 {
+            return;
+        }
+
+        let smoothing = 1.0 - (-1.0 / (GAIN_SMOOTHING_SECONDS * self.sample_rate as f32)).exp();
+
+        for stream in self.streams.values_mut() {
+            for frame in out.chunks_exact_mut(channels) {
+                let Some([left, right]) = stream.buffer.pop_front() else {
+                    break;
+                };
+
+                stream.gain.left += (stream.target_gain.left - stream.gain.left) * smoothing;
+                stream.gain.right += (stream.target_gain.right - stream.gain.right) * smoothing;
+
+                let state = &mut stream.low_pass_state;
+                state[0] += (left - state[0]) * stream.low_pass_alpha;
+                state[1] += (right - state[1]) * stream.low_pass_alpha;
+
+                let left = state[0] * stream.gain.left;
+                let right = state[1] * stream.gain.right;
+
+                if let [out_left, out_right, ..] = frame {
+                    *out_left += left;
+                    *out_right += right;
+                } else {
+                    frame[0] += (left + right) / 2.0;
+                }
+            }
+        }
+
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn max_buffered_frames(&self) -> usize {
+        (self.sample_rate as f32 * MAX_LATENCY_SECONDS) as usize
+    }
+}
+
+#[cfg(test)]
+fn assert_near(a: f32, b: f32) {
+    assert!((a - b).abs() < 0.001, "{a} != {b}");
+}
+
+#[cfg(test)]
+fn settled(mixer: &mut Mixer, id: c_int, gain: StereoGain) {
+    mixer.set_gain(id, gain);
+    if let Some(stream) = mixer.streams.get_mut(&id) {
+        stream.gain = gain;
+    }
+}
+
+#[test]
+fn test_stereo_gain() {
+    let center = StereoGain::new(0.5, 0.0);
+    assert_near(center.left, 0.5);
+    assert_near(center.right, 0.5);
+
+    let right = StereoGain::new(1.0, 1.0);
+    assert_near(right.left, 0.0);
+    assert_near(right.right, 1.0);
+
+    let left = StereoGain::new(1.0, -0.5);
+    assert_near(left.left, 1.0);
+    assert_near(left.right, 0.5);
+
+    // clamped
+    assert_eq!(StereoGain::new(1.0, 5.0), right);
+}
+
+#[test]
+fn test_mix() {
+    let mut mixer = Mixer::new(48000);
+    mixer.start_stream(1, 2);
+    mixer.start_stream(2, 1);
+    settled(&mut mixer, 1, StereoGain::new(1.0, 0.0));
+    settled(&mut mixer, 2, StereoGain::new(0.5, 1.0));
+
+    mixer.push(1, &[&[0.1, 0.2], &[0.3, 0.4]]);
+    // mono is copied to both sides
+    mixer.push(2, &[&[0.2, 0.2, 0.2]]);
+    // unknown streams are ignored
+    mixer.push(3, &[&[1.0]]);
+
+    let mut out = [0.0; 6];
+    mixer.mix(&mut out, 2);
+    assert_near(out[0], 0.1);
+    assert_near(out[1], 0.3 + 0.1);
+    assert_near(out[2], 0.2);
+    assert_near(out[3], 0.4 + 0.1);
+    // stream 1 ran out
+    assert_near(out[4], 0.0);
+    assert_near(out[5], 0.1);
+
+    // everything was used up
+    mixer.mix(&mut out, 2);
+    assert!(out.iter().all(|sample| sample.abs() < f32::EPSILON));
+
+    // mono output, and clipping
+    mixer.push(1, &[&[0.9], &[0.9]]);
+    mixer.push(2, &[&[1.0]]);
+    let mut out = [0.0; 1];
+    mixer.mix(&mut out, 1);
+    assert_near(out[0], 1.0);
+
+    mixer.stop_stream(1);
+    assert_eq!(mixer.get_channels(1), None);
+    assert_eq!(mixer.get_channels(2), Some(1));
+}
+
+#[test]
+fn test_mix_gain_ramp() {
+    let mut mixer = Mixer::new(1000);
+    mixer.start_stream(1, 2);
+    mixer.set_gain(1, StereoGain::new(1.0, 0.0));
+
+    let frames = 200;
+    let ones = vec![1.0; frames];
+    mixer.push(1, &[&ones, &ones]);
+
+    let mut out = vec![0.0; frames * 2];
+    mixer.mix(&mut out, 2);
+
+    // starts silent and fades in without jumping
+    assert!(out[0] < 0.1);
+    for pair in out.chunks_exact(2).collect::<Vec<_>>().windows(2) {
+        assert!(pair[1][0] >= pair[0][0]);
+    }
+    assert_near(out[out.len() - 1], 1.0);
+}
+
+#[test]
+fn test_mix_latency_cap() {
+    let mut mixer = Mixer::new(100);
+    mixer.start_stream(1, 1);
+    settled(&mut mixer, 1, StereoGain::new(1.0, 0.0));
+
+    let old = vec![0.5; 100];
+    mixer.push(1, &[&old]);
+    mixer.push(1, &[&[1.0; 10]]);
+
+    // only the newest 0.2 seconds are kept
+    let mut out = vec![0.0; 100];
+    mixer.mix(&mut out, 1);
+    assert_near(out[9], 0.5);
+    assert_near(out[10], 1.0);
+    assert_near(out[19], 1.0);
+    assert_near(out[20], 0.0);
+}
+
+#[test]
+fn test_mix_low_pass() {
+    // loudness of a signal alternating every sample, the highest frequency
+    fn loudness(frequency: Option<f32>) -> f32 {
+        let mut mixer = Mixer::new(44100);
+        mixer.start_stream(1, 1);
+        settled(&mut mixer, 1, StereoGain::new(1.0, 0.0));
+        mixer.set_low_pass(1, frequency);
+
+        let samples = (0..1000)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect::<Vec<f32>>();
+        mixer.push(1, &[&samples]);
+
+        let mut out = vec![0.0; 2000];
+        mixer.mix(&mut out, 2);
+        out[1000..].iter().map(|sample| sample.abs()).sum::<f32>()
+    }
+
+    let unfiltered = loudness(None);
+    assert_near(unfiltered, loudness(Some(30000.0)));
+    assert!(loudness(Some(400.0)) < unfiltered * 0.1);
+}
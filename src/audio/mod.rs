@@ -0,0 +1,282 @@
+//! captures browser audio from CEF and plays it ourselves, so every screen
+//! gets the same volume, distance and panning regardless of what's on it
+
+mod mixer;
+mod output;
+mod wav;
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    os::raw::c_int,
+    slice,
+    sync::Mutex,
+    time::Duration,
+};
+
+use classicube_helpers::async_manager;
+use futures::{future::RemoteHandle, prelude::*};
+use lazy_static::lazy_static;
+use ncollide3d::na::Vector3;
+use tracing::{debug, warn};
+
+pub use self::output::AudioOutput;
+use self::{
+    mixer::{Mixer, StereoGain},
+    output::Sink,
+};
+use crate::{
+    cef::{RustRefBrowser, RustRefClient},
+    entity_manager::{CefEntity, EntityManager},
+    error::{Result, ResultExt},
+    options,
+    player::{OcclusionTracker, Player, VolumeMode, compute_real_volume, mixer},
+};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(32);
+
+lazy_static! {
+    // shared with the CEF audio thread and the output device's thread
+    static ref MIXER: Mutex<Mixer> = Mutex::new(Mixer::new(48000));
+}
+
+thread_local!(
+    static SINK: RefCell<Option<Sink>> = RefCell::default();
+);
+
+thread_local!(
+    static UPDATE_LOOP: RefCell<Option<RemoteHandle<()>>> = RefCell::default();
+);
+
+thread_local!(
+    static IS_ENABLED: Cell<bool> = const { Cell::new(false) };
+);
+
+// browsers created while capture was enabled
+thread_local!(
+    static CAPTURED_BROWSERS: RefCell<HashSet<c_int>> = RefCell::default();
+);
+
+/// opens the output and has CEF send us the audio of browsers created after this
+pub fn enable(client: &RustRefClient) -> Result<()> {
+    if IS_ENABLED.get() {
+        return Ok(());
+    }
+
+    // still open if we were disabled while browsers were capturing
+    let sample_rate = if SINK.with_borrow(Option::is_some) {
+        MIXER.lock().unwrap().sample_rate()
+    } else {
+        let output = options::AUDIO_OUTPUT.get()?;
+        let (sink, sample_rate) = Sink::open(output)?;
+        debug!("audio capture to {} at {}hz", output, sample_rate);
+
+        MIXER.lock().unwrap().reset(sample_rate);
+        SINK.with_borrow_mut(|cell| *cell = Some(sink));
+
+        let (f, remote_handle) = update_loop().remote_handle();
+        UPDATE_LOOP.with_borrow_mut(|cell| *cell = Some(remote_handle));
+        async_manager::spawn_local_on_main_thread(f);
+
+        sample_rate
+    };
+
+    client.set_audio_capture(
+        true,
+        c_int::try_from(sample_rate).chain_err(|| "bad sample rate")?,
+    )?;
+    IS_ENABLED.set(true);
+
+    Ok(())
+}
+
+/// browsers already capturing keep going until they're closed
+pub fn disable(client: &RustRefClient) -> Result<()> {
+    client.set_audio_capture(false, 0)?;
+    IS_ENABLED.set(false);
+
+    Ok(())
+}
+
+pub fn shutdown() {
+    IS_ENABLED.set(false);
+    UPDATE_LOOP.with_borrow_mut(|cell| cell.take());
+    SINK.with_borrow_mut(|cell| cell.take());
+    CAPTURED_BROWSERS.with_borrow_mut(HashSet::clear);
+
+    if let Ok(mut mixer) = MIXER.lock() {
+        let sample_rate = mixer.sample_rate();
+        mixer.reset(sample_rate);
+    }
+}
+
+/// whether the update loop setting gains is running
+pub fn is_running() -> bool {
+    UPDATE_LOOP.with(|cell| cell.try_borrow().is_ok_and(|handle| handle.is_some()))
+}
+
+pub fn on_browser_created(browser_id: c_int) {
+    if IS_ENABLED.get() {
+        CAPTURED_BROWSERS.with_borrow_mut(|browsers| browsers.insert(browser_id));
+    }
+}
+
+pub fn on_browser_closed(browser_id: c_int) {
+    CAPTURED_BROWSERS.with_borrow_mut(|browsers| browsers.remove(&browser_id));
+}
+
+/// whether this browser's audio comes through us instead of the page
+pub fn is_capturing(browser_id: c_int) -> bool {
+    CAPTURED_BROWSERS.with_borrow(|browsers| browsers.contains(&browser_id))
+}
+
+// the stream callbacks are called on CEF's audio thread, only touch MIXER!
+
+pub extern "C" fn on_audio_stream_started(
+    browser: RustRefBrowser,
+    sample_rate: c_int,
+    channels: c_int,
+) {
+    let Ok(mut mixer) = MIXER.lock() else {
+        return;
+    };
+
+    let browser_id = browser.get_identifier();
+    if u32::try_from(sample_rate).ok() != Some(mixer.sample_rate()) {
+        warn!(
+            "browser {} audio at {}hz instead of {}hz",
+            browser_id,
+            sample_rate,
+            mixer.sample_rate()
+        );
+    }
+
+    let channels = usize::try_from(channels).unwrap_or_default();
+    mixer.start_stream(browser_id, channels);
+}
+
+pub unsafe extern "C" fn on_audio_stream_packet(
+    browser: RustRefBrowser,
+    data: *mut *const f32,
+    frames: c_int,
+) {
+    let Ok(mut mixer) = MIXER.lock() else {
+        return;
+    };
+
+    let browser_id = browser.get_identifier();
+    let (Some(channels), Ok(frames)) = (mixer.get_channels(browser_id), usize::try_from(frames))
+    else {
+        return;
+    };
+    if data.is_null() || channels == 0 {
+        return;
+    }
+
+    let planes = (0..channels)
+        .map(|channel| unsafe { slice::from_raw_parts(*data.add(channel), frames) })
+        .collect::<Vec<_>>();
+    mixer.push(browser_id, &planes);
+}
+
+pub extern "C" fn on_audio_stream_stopped(browser: RustRefBrowser) {
+    if let Ok(mut mixer) = MIXER.lock() {
+        mixer.stop_stream(browser.get_identifier());
+    }
+}
+
+async fn update_loop() {
+    // entity_id, tracker
+    let mut occlusions: HashMap<usize, OcclusionTracker> = HashMap::new();
+
+    loop {
+        update(&mut occlusions);
+        async_manager::sleep(UPDATE_INTERVAL).await;
+    }
+}
+
+fn update(occlusions: &mut HashMap<usize, OcclusionTracker>) {
+    let updates = EntityManager::with_all_entities(|entities| {
+        occlusions.retain(|entity_id, _| entities.contains_key(entity_id));
+
+        entities
+            .iter()
+            .filter_map(|(&entity_id, entity)| {
+                let browser_id = entity.browser.as_ref()?.get_identifier();
+                if !is_capturing(browser_id) {
+                    return None;
+                }
+
+                let occlusion = occlusions.entry(entity_id).or_default();
+                let (gain, low_pass) = get_stream_params(entity, occlusion)?;
+                Some((browser_id, gain, low_pass))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // don't hold the lock while ray-marching above
+    if let Ok(mut mixer) = MIXER.lock() {
+        for (browser_id, gain, low_pass) in updates {
+            mixer.set_gain(browser_id, gain);
+            mixer.set_low_pass(browser_id, low_pass);
+        }
+    }
+}
+
+/// gain and low-pass cutoff for a screen's stream
+fn get_stream_params(
+    entity: &CefEntity,
+    occlusion: &mut OcclusionTracker,
+) -> Option<(StereoGain, Option<f32>)> {
+    // these pages already apply their own volume and the global modifier,
+    // see `start_loop`
+    let page_has_volume = matches!(
+        entity.player,
+        Player::YouTube(_) | Player::Dash(_) | Player::Hls(_) | Player::Media(_)
+    );
+    let volume_modifier = if page_has_volume {
+        1.0
+    } else {
+        options::VOLUME.get().unwrap_or(1.0)
+    };
+
+    let (volume, volume_mode) = compute_real_volume(entity, occlusion)?;
+    mixer::report_volume(entity.id, volume);
+
+    let (volume, pan) = match volume_mode {
+        VolumeMode::Global => {
+            let volume = if page_has_volume { 1.0 } else { volume };
+            return Some((StereoGain::new(volume * volume_modifier, 0.0), None));
+        }
+
+        VolumeMode::Distance { .. } => (volume, 0.0),
+
+        VolumeMode::Panning { pan, .. } => (volume, pan),
+
+        VolumeMode::Spatial {
+            distance, position, ..
+        } => {
+            // no PannerNode here, so do its linear rolloff and just pan
+            let position = Vector3::from(position);
+            let magnitude = position.magnitude();
+            let rolloff = (1.0 - magnitude / distance).clamp(0.0, 1.0);
+            let pan = if magnitude > 0.0001 {
+                position.x / magnitude * 0.8
+            } else {
+                0.0
+            };
+
+            (volume * rolloff, pan)
+        }
+    };
+
+    let low_pass = if options::OCCLUSION_LOW_PASS.get().unwrap_or(false) {
+        Some(occlusion.get().get_low_pass_frequency())
+    } else {
+        None
+    };
+
+    let volume = volume * mixer::get_gain(entity.id);
+
+    Some((StereoGain::new(volume * volume_modifier, pan), low_pass))
+}
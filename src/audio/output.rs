@@ -0,0 +1,221 @@
+//! where the mixed audio ends up
+
+use std::{
+    env,
+    fmt::{self, Display},
+    fs::{self, File},
+    io::BufWriter,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use cpal::{
+    FromSample, SampleFormat, SizedSample,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+use tracing::{debug, warn};
+
+use super::{MIXER, wav::WavWriter};
+use crate::error::{Error, Result, ResultExt, bail};
+
+/// rate used when there's no device to ask
+const THREAD_SAMPLE_RATE: u32 = 48000;
+
+/// how much the null/file sinks mix at a time
+const THREAD_CHUNK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioOutput {
+    /// the system's default output device
+    Device,
+    /// cef/audio-<timestamp>.wav
+    File,
+    /// mixed and thrown away
+    Null,
+}
+
+impl FromStr for AudioOutput {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "device" => Ok(Self::Device),
+            "file" => Ok(Self::File),
+            "null" | "none" => Ok(Self::Null),
+            _ => bail!("unknown audio output {:?}, use device, file or null", s),
+        }
+    }
+}
+
+impl Display for AudioOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Device => "device",
+            Self::File => "file",
+            Self::Null => "null",
+        })
+    }
+}
+
+pub enum Sink {
+    Device(cpal::Stream),
+
+    Thread {
+        stop: Arc<AtomicBool>,
+        handle: Option<JoinHandle<()>>,
+    },
+}
+
+impl Sink {
+    /// starts pulling from the mixer, returns the sample rate it wants
+    pub fn open(output: AudioOutput) -> Result<(Self, u32)> {
+        match output {
+            AudioOutput::Device => Self::open_device(),
+
+            AudioOutput::File => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .chain_err(|| "system time before epoch")?
+                    .as_millis();
+
+                let dir = env::current_dir()
+                    .chain_err(|| "current_dir() None")?
+                    .join("cef");
+                fs::create_dir_all(&dir)?;
+                let path = dir.join(format!("audio-{timestamp}.wav"));
+                debug!("writing audio to {:?}", path);
+
+                let writer =
+                    WavWriter::new(BufWriter::new(File::create(path)?), 2, THREAD_SAMPLE_RATE)?;
+                Ok((Self::open_thread(Some(writer)), THREAD_SAMPLE_RATE))
+            }
+
+            AudioOutput::Null => Ok((Self::open_thread(None), THREAD_SAMPLE_RATE)),
+        }
+    }
+
+    fn open_device() -> Result<(Self, u32)> {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .chain_err(|| "no audio output device")?;
+        let config = device
+            .default_output_config()
+            .chain_err(|| "couldn't get audio output config")?;
+
+        let sample_format = config.sample_format();
+        let config: cpal::StreamConfig = config.into();
+        let sample_rate = config.sample_rate.0;
+
+        let stream = match sample_format {
+            SampleFormat::F32 => build_device_stream::<f32>(&device, &config)?,
+            SampleFormat::I16 => build_device_stream::<i16>(&device, &config)?,
+            SampleFormat::U16 => build_device_stream::<u16>(&device, &config)?,
+            SampleFormat::I32 => build_device_stream::<i32>(&device, &config)?,
+            other => bail!("unsupported audio output format {}", other),
+        };
+        stream.play().chain_err(|| "couldn't start audio output")?;
+
+        Ok((Self::Device(stream), sample_rate))
+    }
+
+    /// mixes in real time on its own thread, like a device would
+    fn open_thread(mut writer: Option<WavWriter<BufWriter<File>>>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let stop = stop.clone();
+
+            thread::spawn(move || {
+                let frames =
+                    (u128::from(THREAD_SAMPLE_RATE) * THREAD_CHUNK.as_millis() / 1000) as usize;
+                let mut buffer = vec![0.0; frames * 2];
+
+                let start = Instant::now();
+                let mut chunks = 0u32;
+                while !stop.load(Ordering::SeqCst) {
+                    if let Ok(mut mixer) = MIXER.lock() {
+                        mixer.mix(&mut buffer, 2);
+                    }
+
+                    if let Some(w) = writer.as_mut()
+                        && let Err(e) = w.write_samples(&buffer)
+                    {
+                        warn!("audio file: {}", e);
+                        writer = None;
+                    }
+
+                    chunks += 1;
+                    let next = start + THREAD_CHUNK * chunks;
+                    thread::sleep(next.saturating_duration_since(Instant::now()));
+                }
+
+                if let Some(writer) = writer
+                    && let Err(e) = writer.finish()
+                {
+                    warn!("audio file: {}", e);
+                }
+            })
+        };
+
+        Self::Thread {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Sink {
+    fn drop(&mut self) {
+        if let Self::Thread { stop, handle } = self {
+            stop.store(true, Ordering::SeqCst);
+            if let Some(handle) = handle.take() {
+                let _ignore = handle.join();
+            }
+        }
+    }
+}
+
+fn build_device_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels);
+    let mut mixed = Vec::new();
+
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                mixed.resize(data.len(), 0.0);
+                match MIXER.lock() {
+                    Ok(mut mixer) => mixer.mix(&mut mixed, channels),
+                    Err(_) => mixed.fill(0.0),
+                }
+
+                for (out, &sample) in data.iter_mut().zip(&mixed) {
+                    *out = T::from_sample(sample);
+                }
+            },
+            |e| warn!("audio output: {}", e),
+            None,
+        )
+        .chain_err(|| "couldn't open audio output")
+}
+
+#[test]
+fn test_audio_output_parse() {
+    for output in [AudioOutput::Device, AudioOutput::File, AudioOutput::Null] {
+        assert_eq!(output.to_string().parse::<AudioOutput>().unwrap(), output);
+    }
+    assert_eq!("NONE".parse::<AudioOutput>().unwrap(), AudioOutput::Null);
+    assert!("speakers".parse::<AudioOutput>().is_err());
+}
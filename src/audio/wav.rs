@@ -0,0 +1,98 @@
+//! minimal 32-bit float WAV writer for the file output
+
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::error::{Result, ResultExt};
+
+const HEADER_SIZE: u32 = 44;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    data_size: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// writes a header with placeholder sizes, fixed up in `finish`
+    pub fn new(mut inner: W, channels: u16, sample_rate: u32) -> Result<Self> {
+        let block_align = channels * 4;
+
+        inner.write_all(b"RIFF")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        inner.write_all(b"WAVE")?;
+
+        inner.write_all(b"fmt ")?;
+        inner.write_all(&16u32.to_le_bytes())?;
+        inner.write_all(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes())?;
+        inner.write_all(&channels.to_le_bytes())?;
+        inner.write_all(&sample_rate.to_le_bytes())?;
+        inner.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        inner.write_all(&block_align.to_le_bytes())?;
+        inner.write_all(&32u16.to_le_bytes())?;
+
+        inner.write_all(b"data")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            inner,
+            data_size: 0,
+        })
+    }
+
+    /// interleaved samples
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let bytes = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+
+        let size = u32::try_from(bytes.len())
+            .ok()
+            .and_then(|size| self.data_size.checked_add(size))
+            .filter(|&size| size <= u32::MAX - HEADER_SIZE)
+            .chain_err(|| "wav file too big")?;
+
+        self.inner.write_all(&bytes)?;
+        self.data_size = size;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+
+        self.inner.seek(SeekFrom::Start(40))?;
+        self.inner.write_all(&self.data_size.to_le_bytes())?;
+
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+}
+
+#[test]
+fn test_wav_writer() {
+    use std::io::Cursor;
+
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), 2, 48000).unwrap();
+    writer.write_samples(&[0.5, -0.5]).unwrap();
+    writer.write_samples(&[1.0, 0.0]).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    assert_eq!(bytes.len(), 44 + 16);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[4..8], &(36u32 + 16).to_le_bytes());
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    // float, stereo
+    assert_eq!(&bytes[20..24], &[3, 0, 2, 0]);
+    assert_eq!(&bytes[24..28], &48000u32.to_le_bytes());
+    assert_eq!(&bytes[28..32], &(48000u32 * 8).to_le_bytes());
+    assert_eq!(&bytes[32..36], &[8, 0, 32, 0]);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(&bytes[40..44], &16u32.to_le_bytes());
+    assert_eq!(&bytes[44..48], &0.5f32.to_le_bytes());
+    assert_eq!(&bytes[56..60], &0.0f32.to_le_bytes());
+}
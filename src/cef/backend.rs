@@ -0,0 +1,182 @@
+//! the browser that screens and players talk to
+//!
+//! everything outside of `cef` goes through `Browser` instead of
+//! `RustRefBrowser` so it can run in tests with a `FakeBrowser`
+
+use std::os::raw::c_int;
+
+#[cfg(test)]
+use super::FakeBrowser;
+use super::{NavigationEntry, RustRefBrowser, RustV8Value};
+use crate::error::Result;
+
+#[derive(Debug, Clone)]
+pub enum Browser {
+    Cef(RustRefBrowser),
+    #[cfg(test)]
+    Fake(FakeBrowser),
+}
+
+impl From<RustRefBrowser> for Browser {
+    fn from(browser: RustRefBrowser) -> Self {
+        Browser::Cef(browser)
+    }
+}
+
+impl Browser {
+    pub fn get_identifier(&self) -> c_int {
+        match self {
+            Browser::Cef(browser) => browser.get_identifier(),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.get_identifier(),
+        }
+    }
+
+    pub fn load_url<T: Into<Vec<u8>>>(&self, url: T) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.load_url(url),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.load_url(url),
+        }
+    }
+
+    pub fn execute_javascript<T: Into<Vec<u8>>>(&self, code: T) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.execute_javascript(code),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.execute_javascript(code),
+        }
+    }
+
+    pub fn execute_javascript_on_frame<T: Into<Vec<u8>>, U: Into<Vec<u8>>>(
+        &self,
+        frame_name: T,
+        code: U,
+    ) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.execute_javascript_on_frame(frame_name, code),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.execute_javascript_on_frame(frame_name, code),
+        }
+    }
+
+    pub async fn eval_javascript<T: Into<Vec<u8>>>(&self, code: T) -> Result<RustV8Value> {
+        match self {
+            Browser::Cef(browser) => browser.eval_javascript(code).await,
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.eval_javascript(code),
+        }
+    }
+
+    pub fn send_click(&self, x: c_int, y: c_int) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.send_click(x, y),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn send_text<T: Into<Vec<u8>>>(&self, text: T) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.send_text(text),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.reload(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        match self {
+            Browser::Cef(browser) => browser.can_go_back(),
+            #[cfg(test)]
+            Browser::Fake(_) => false,
+        }
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        match self {
+            Browser::Cef(browser) => browser.can_go_forward(),
+            #[cfg(test)]
+            Browser::Fake(_) => false,
+        }
+    }
+
+    pub fn go_back(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.go_back(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn go_forward(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.go_forward(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn get_url(&self) -> String {
+        match self {
+            Browser::Cef(browser) => browser.get_url(),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.get_url(),
+        }
+    }
+
+    pub fn get_navigation_entries(&self) -> Result<Vec<NavigationEntry>> {
+        match self {
+            Browser::Cef(browser) => browser.get_navigation_entries(),
+            #[cfg(test)]
+            Browser::Fake(browser) => Ok(browser.get_navigation_entries()),
+        }
+    }
+
+    pub fn was_resized(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.was_resized(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn invalidate(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.invalidate(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn open_dev_tools(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.open_dev_tools(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+
+    pub fn set_audio_muted(&self, mute: bool) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.set_audio_muted(mute),
+            #[cfg(test)]
+            Browser::Fake(browser) => browser.set_audio_muted(mute),
+        }
+    }
+
+    pub fn close(&self) -> Result<()> {
+        match self {
+            Browser::Cef(browser) => browser.close(),
+            #[cfg(test)]
+            Browser::Fake(_) => Ok(()),
+        }
+    }
+}
@@ -4,18 +4,16 @@ use std::{
     env,
     ffi::{CStr, CString},
     fmt::Display,
-    fs, io,
-    iter::Iterator,
-    os::raw::c_int,
-    process, ptr, slice,
-    time::{SystemTime, UNIX_EPOCH},
+    os::raw::{c_char, c_int, c_void},
+    path::Path,
+    ptr, slice,
 };
 
 use tracing::{debug, warn};
 use url::Url;
 
 pub use self::generated::*;
-use super::{javascript, javascript::RustV8Value};
+use super::{javascript, javascript::RustV8Value, local_files};
 use crate::error::{ErrorKind, Result, ResultExt, bail};
 
 #[unsafe(no_mangle)]
@@ -39,23 +37,36 @@ pub unsafe extern "C" fn rust_warn(c_str: *const ::std::os::raw::c_char) {
 const YOUTUBE_HTML: &[u8] = include_bytes!("../../player/youtube/page.html");
 const MEDIA_HTML: &[u8] = include_bytes!("../../player/media/page.html");
 
+enum SchemeResponse {
+    Page(&'static [u8]),
+    File(local_files::FileResponse),
+}
+
 fn handle_scheme_create(
     _browser: RustRefBrowser,
     _scheme_name: *const ::std::os::raw::c_char,
     url: *const ::std::os::raw::c_char,
-) -> Result<&'static [u8]> {
+    range: *const ::std::os::raw::c_char,
+) -> Result<SchemeResponse> {
     let url = unsafe { CStr::from_ptr(url) }.to_str()?;
     let url = Url::parse(url)?;
+    let range = unsafe { CStr::from_ptr(range) }.to_str()?;
+    let range = if range.is_empty() { None } else { Some(range) };
 
-    debug!("rust_handle_scheme_create {}", url);
+    debug!("rust_handle_scheme_create {} {:?}", url, range);
 
     // YouTube goes through the synthetic https host so its embedder
     // identity check sees a valid https origin via ancestorOrigins;
     // everything else stays on `local://` to keep mixed-content support
     // (the media player loads plain http streams).
     match (url.scheme(), url.host_str(), url.path()) {
-        ("https", Some("classicube-cef.invalid"), "/youtube") => Ok(YOUTUBE_HTML),
-        ("local", Some("media"), _) => Ok(MEDIA_HTML),
+        ("https", Some("classicube-cef.invalid"), "/youtube") => {
+            Ok(SchemeResponse::Page(YOUTUBE_HTML))
+        }
+        ("local", Some("media"), _) => Ok(SchemeResponse::Page(MEDIA_HTML)),
+        ("local", Some(local_files::HOST), _) => Ok(SchemeResponse::File(
+            local_files::handle_request(&url, range)?,
+        )),
         _ => bail!("no page registered for {}", url),
     }
 }
@@ -65,26 +76,61 @@ pub extern "C" fn rust_handle_scheme_create(
     browser: RustRefBrowser,
     scheme_name: *const ::std::os::raw::c_char,
     url: *const ::std::os::raw::c_char,
+    range: *const ::std::os::raw::c_char,
 ) -> RustSchemeReturn {
-    match handle_scheme_create(browser, scheme_name, url) {
-        Ok(data) => RustSchemeReturn {
+    match handle_scheme_create(browser, scheme_name, url, range) {
+        Ok(SchemeResponse::Page(data)) => RustSchemeReturn {
             data: data.as_ptr() as *mut std::os::raw::c_void,
             data_size: data.len() as _,
+            file_path: ptr::null_mut(),
             mime_type: c"text/html".as_ptr().cast_mut(),
+            status_code: 200,
+            content_range: ptr::null_mut(),
+            response_length: data.len() as _,
         },
 
+        Ok(SchemeResponse::File(file)) => {
+            let path = CString::new(file.path.to_string_lossy().into_owned()).unwrap();
+            let content_range = file.get_content_range().map_or(ptr::null_mut(), |range| {
+                CString::new(range).unwrap().into_raw()
+            });
+
+            RustSchemeReturn {
+                data: ptr::null_mut(),
+                data_size: 0,
+                file_path: path.into_raw(),
+                mime_type: file.mime_type.as_ptr().cast_mut(),
+                status_code: if file.range.is_some() { 206 } else { 200 },
+                content_range,
+                response_length: file.get_length() as _,
+            }
+        }
+
         Err(e) => {
             warn!("{}", e);
 
             RustSchemeReturn {
                 data: ptr::null_mut(),
                 data_size: 0,
+                file_path: ptr::null_mut(),
                 mime_type: ptr::null_mut(),
+                status_code: 0,
+                content_range: ptr::null_mut(),
+                response_length: 0,
             }
         }
     }
 }
 
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rust_free_scheme_return(ret: RustSchemeReturn) {
+    for c_str in [ret.file_path, ret.content_range] {
+        if !c_str.is_null() {
+            drop(unsafe { CString::from_raw(c_str) });
+        }
+    }
+}
+
 // #[unsafe(no_mangle)]
 // pub unsafe extern "C" fn rust_wprint(c_str: *const u16) {
 //     use widestring::WideCStr;
@@ -123,38 +169,8 @@ impl RustRefApp {
         #[cfg(target_os = "linux")]
         let browser_subprocess_path = cef_dir_path.join("cef");
 
-        let root_cache_path = {
-            let cache_dir = cef_dir_path.join("cache");
-            fs::create_dir_all(&cache_dir).chain_err(|| "create cache dir")?;
-
-            let mut dirs = fs::read_dir(&cache_dir)
-                .and_then(Iterator::collect::<io::Result<Vec<_>>>)
-                .map(|dirs| {
-                    dirs.into_iter()
-                        .filter(|dir| dir.path().is_dir())
-                        .map(|dir| dir.path())
-                        .collect::<Vec<_>>()
-                })
-                .chain_err(|| "read_dir cache_dir")?;
-
-            // sort by oldest first
-            dirs.sort();
-            debug!(?dirs);
-
-            while dirs.len() >= 10 {
-                let dir = dirs.remove(0);
-                debug!("removing old cache dir: {}", dir.display());
-                fs::remove_dir_all(&dir)
-                    .chain_err(|| format!("remove_dir_all: {}", dir.display()))?;
-            }
-
-            let current_time = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .chain_err(|| "get current time")?;
-            let epoch = current_time.as_millis();
-            let pid = process::id();
-            cache_dir.join(format!("{epoch}-{pid}"))
-        };
+        // the shared cache if no other game has it, profiles need it
+        let root_cache_path = super::profile::prepare_root_cache_path()?;
 
         // ClassiCube doesn't need to run as an app bundle, but
         // this has to be set or else we get errors, although it seems to work with any non-empty string
@@ -230,8 +246,12 @@ impl RustRefClient {
         fps: c_int,
         insecure: bool,
         background_color: u32,
+        cache_path: Option<&Path>,
     ) -> Result<()> {
         let startup_url = CString::new(startup_url)?;
+        let cache_path = cache_path
+            .map(|cache_path| CString::new(format!("{}", cache_path.display())))
+            .transpose()?;
 
         to_result(unsafe {
             cef_interface_create_browser(
@@ -240,9 +260,16 @@ impl RustRefClient {
                 fps,
                 insecure,
                 background_color,
+                cache_path
+                    .as_ref()
+                    .map_or(ptr::null(), |cache_path| cache_path.as_ptr()),
             )
         })
     }
+
+    pub fn set_audio_capture(&self, enabled: bool, sample_rate: c_int) -> Result<()> {
+        to_result(unsafe { cef_interface_client_set_audio_capture(self.ptr, enabled, sample_rate) })
+    }
 }
 impl Drop for RustRefClient {
     fn drop(&mut self) {
@@ -355,10 +382,65 @@ impl RustRefBrowser {
         to_result(unsafe { cef_interface_browser_reload(self.ptr) })
     }
 
+    pub fn can_go_back(&self) -> bool {
+        unsafe { cef_interface_browser_can_go_back(self.ptr) }
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        unsafe { cef_interface_browser_can_go_forward(self.ptr) }
+    }
+
+    pub fn go_back(&self) -> Result<()> {
+        to_result(unsafe { cef_interface_browser_go_back(self.ptr) })
+    }
+
+    pub fn go_forward(&self) -> Result<()> {
+        to_result(unsafe { cef_interface_browser_go_forward(self.ptr) })
+    }
+
+    /// the main frame's url, even if the page changed it
+    pub fn get_url(&self) -> String {
+        unsafe { cef_interface_browser_get_url(self.ptr) }.to_string()
+    }
+
+    /// oldest first
+    pub fn get_navigation_entries(&self) -> Result<Vec<NavigationEntry>> {
+        extern "C" fn on_navigation_entry(
+            data: *mut c_void,
+            url: *const c_char,
+            title: *const c_char,
+            current: bool,
+        ) {
+            let entries = unsafe { &mut *data.cast::<Vec<NavigationEntry>>() };
+            entries.push(NavigationEntry {
+                url: unsafe { CStr::from_ptr(url) }.to_string_lossy().to_string(),
+                title: unsafe { CStr::from_ptr(title) }
+                    .to_string_lossy()
+                    .to_string(),
+                current,
+            });
+        }
+
+        let mut entries: Vec<NavigationEntry> = Vec::new();
+        to_result(unsafe {
+            cef_interface_browser_get_navigation_entries(
+                self.ptr,
+                Some(on_navigation_entry),
+                (&raw mut entries).cast(),
+            )
+        })?;
+
+        Ok(entries)
+    }
+
     pub fn was_resized(&self) -> Result<()> {
         to_result(unsafe { cef_interface_browser_was_resized(self.ptr) })
     }
 
+    pub fn invalidate(&self) -> Result<()> {
+        to_result(unsafe { cef_interface_browser_invalidate(self.ptr) })
+    }
+
     pub fn open_dev_tools(&self) -> Result<()> {
         to_result(unsafe { cef_interface_browser_open_dev_tools(self.ptr) })
     }
@@ -382,6 +464,14 @@ impl Clone for RustRefBrowser {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationEntry {
+    pub url: String,
+    pub title: String,
+    /// the page the browser is showing
+    pub current: bool,
+}
+
 impl Display for RustRefString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = unsafe { slice::from_raw_parts(self.ptr.cast::<u8>(), self.len) };
@@ -8,12 +8,17 @@ use std::{
 use classicube_helpers::WithInner;
 use tracing::{debug, warn};
 
-use super::{CEF_DEFAULT_HEIGHT, CEF_DEFAULT_WIDTH, CefEvent, EVENT_QUEUE, bindings::RustRect};
-use crate::cef::RustRefBrowser;
+use super::{
+    CEF_DEFAULT_HEIGHT, CEF_DEFAULT_WIDTH, CefEvent, EVENT_QUEUE, Profile, bindings::RustRect,
+};
+use crate::{
+    adblock, audio,
+    cef::{Browser, RustRefBrowser},
+};
 
 // identifier, browser
 thread_local!(
-    pub static BROWSERS: RefCell<HashMap<c_int, RustRefBrowser>> = RefCell::default();
+    pub static BROWSERS: RefCell<HashMap<c_int, Browser>> = RefCell::default();
 );
 
 thread_local!(
@@ -24,6 +29,11 @@ thread_local!(
     pub static ALLOW_INSECURE: RefCell<HashMap<c_int, bool>> = RefCell::default();
 );
 
+// identifier, profile it was created with
+thread_local!(
+    pub static BROWSER_PROFILES: RefCell<HashMap<c_int, Profile>> = RefCell::default();
+);
+
 // OnAfterCreated
 #[tracing::instrument(fields(browser = browser.get_identifier()))]
 pub extern "C" fn on_after_created(browser: RustRefBrowser) {
@@ -34,14 +44,17 @@ pub extern "C" fn on_after_created(browser: RustRefBrowser) {
         let browser = browser.clone();
         EVENT_QUEUE
             .with_inner_mut(move |(sender, _receiver)| {
-                let _ignore_error = sender.send(CefEvent::BrowserCreated(browser));
+                let _ignore_error = sender.send(CefEvent::BrowserCreated(browser.into()));
             })
             .unwrap();
     }
 
+    audio::on_browser_created(id);
+    adblock::on_browser_created(id);
+
     BROWSERS.with(move |cell| {
         let browsers = &mut *cell.borrow_mut();
-        browsers.insert(id, browser);
+        browsers.insert(id, browser.into());
     });
 }
 
@@ -53,14 +66,22 @@ pub extern "C" fn on_before_close(browser: RustRefBrowser) {
 
     EVENT_QUEUE
         .with_inner_mut(move |(sender, _receiver)| {
-            let _ignore_error = sender.send(CefEvent::BrowserClosed(browser));
+            let _ignore_error = sender.send(CefEvent::BrowserClosed(browser.into()));
         })
         .unwrap();
 
+    audio::on_browser_closed(id);
+    adblock::on_browser_closed(id);
+
     BROWSERS.with(move |cell| {
         let browsers = &mut *cell.borrow_mut();
         browsers.remove(&id);
     });
+
+    BROWSER_PROFILES.with(move |cell| {
+        let profiles = &mut *cell.borrow_mut();
+        profiles.remove(&id);
+    });
 }
 
 // OnPageLoaded
@@ -71,7 +92,7 @@ pub extern "C" fn on_page_loaded(browser: RustRefBrowser) {
 
     EVENT_QUEUE
         .with_inner_mut(move |(sender, _receiver)| {
-            let _ignore_error = sender.send(CefEvent::BrowserPageLoaded(browser));
+            let _ignore_error = sender.send(CefEvent::BrowserPageLoaded(browser.into()));
         })
         .unwrap();
 }
@@ -87,7 +108,23 @@ pub extern "C" fn on_title_change(browser: RustRefBrowser, title_c_str: *const c
 
     EVENT_QUEUE
         .with_inner_mut(move |(sender, _receiver)| {
-            let _ignore_error = sender.send(CefEvent::BrowserTitleChange(browser, title));
+            let _ignore_error = sender.send(CefEvent::BrowserTitleChange(browser.into(), title));
+        })
+        .unwrap();
+}
+
+// OnAddressChange
+#[tracing::instrument(fields(browser = browser.get_identifier(), url_c_str))]
+pub extern "C" fn on_address_change(browser: RustRefBrowser, url_c_str: *const c_char) {
+    let id = browser.get_identifier();
+    let url = unsafe { CStr::from_ptr(url_c_str) }
+        .to_string_lossy()
+        .to_string();
+    debug!("on_address_change {} {}", id, url);
+
+    EVENT_QUEUE
+        .with_inner_mut(move |(sender, _receiver)| {
+            let _ignore_error = sender.send(CefEvent::BrowserAddressChange(browser.into(), url));
         })
         .unwrap();
 }
@@ -131,4 +168,5 @@ pub fn shutdown() {
     // subsequent Init starts from a clean slate.
     BROWSER_SIZES.with(|cell| cell.borrow_mut().clear());
     ALLOW_INSECURE.with(|cell| cell.borrow_mut().clear());
+    BROWSER_PROFILES.with(|cell| cell.borrow_mut().clear());
 }
@@ -0,0 +1,125 @@
+//! an in-memory `Browser` for tests, remembers what it was told to do and
+//! answers evals with results set up by the test
+
+use std::{cell::RefCell, os::raw::c_int, rc::Rc};
+
+use super::{NavigationEntry, RustV8Value};
+use crate::error::{Result, bail};
+
+/// clones share the same state, so a test can keep one and give the other
+/// to a screen
+#[derive(Debug, Clone)]
+pub struct FakeBrowser {
+    id: c_int,
+    state: Rc<RefCell<FakeState>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    loaded_urls: Vec<String>,
+    executed_javascript: Vec<String>,
+    /// part of the code, value
+    eval_results: Vec<(String, RustV8Value)>,
+    audio_muted: bool,
+}
+
+impl FakeBrowser {
+    pub fn new(id: c_int) -> Self {
+        Self {
+            id,
+            state: Rc::default(),
+        }
+    }
+
+    /// evals of code containing `code_part` return `value`, later calls
+    /// replace earlier ones
+    pub fn set_eval_result(&self, code_part: &str, value: RustV8Value) {
+        let eval_results = &mut self.state.borrow_mut().eval_results;
+        eval_results.retain(|(part, _)| part != code_part);
+        eval_results.push((code_part.to_string(), value));
+    }
+
+    pub fn loaded_urls(&self) -> Vec<String> {
+        self.state.borrow().loaded_urls.clone()
+    }
+
+    pub fn executed_javascript(&self) -> Vec<String> {
+        self.state.borrow().executed_javascript.clone()
+    }
+
+    pub fn is_audio_muted(&self) -> bool {
+        self.state.borrow().audio_muted
+    }
+
+    pub fn get_identifier(&self) -> c_int {
+        self.id
+    }
+
+    pub fn load_url<T: Into<Vec<u8>>>(&self, url: T) -> Result<()> {
+        let url = to_string(url);
+        self.state.borrow_mut().loaded_urls.push(url);
+        Ok(())
+    }
+
+    pub fn execute_javascript<T: Into<Vec<u8>>>(&self, code: T) -> Result<()> {
+        let code = to_string(code);
+        self.state.borrow_mut().executed_javascript.push(code);
+        Ok(())
+    }
+
+    pub fn execute_javascript_on_frame<T: Into<Vec<u8>>, U: Into<Vec<u8>>>(
+        &self,
+        _frame_name: T,
+        code: U,
+    ) -> Result<()> {
+        self.execute_javascript(code)
+    }
+
+    pub fn eval_javascript<T: Into<Vec<u8>>>(&self, code: T) -> Result<RustV8Value> {
+        let code = to_string(code);
+
+        let state = self.state.borrow();
+        if let Some((_, value)) = state
+            .eval_results
+            .iter()
+            .find(|(part, _)| code.contains(part.as_str()))
+        {
+            Ok(value.clone())
+        } else {
+            bail!("no eval result for {:?}", code);
+        }
+    }
+
+    /// the last loaded url
+    pub fn get_url(&self) -> String {
+        self.state
+            .borrow()
+            .loaded_urls
+            .last()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// every loaded url, the last is current
+    pub fn get_navigation_entries(&self) -> Vec<NavigationEntry> {
+        let loaded_urls = &self.state.borrow().loaded_urls;
+        loaded_urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| NavigationEntry {
+                url: url.clone(),
+                title: String::new(),
+                current: i + 1 == loaded_urls.len(),
+            })
+            .collect()
+    }
+
+    pub fn set_audio_muted(&self, mute: bool) -> Result<()> {
+        self.state.borrow_mut().audio_muted = mute;
+        Ok(())
+    }
+}
+
+fn to_string<T: Into<Vec<u8>>>(bytes: T) -> String {
+    String::from_utf8_lossy(&bytes.into()).to_string()
+}
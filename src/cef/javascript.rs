@@ -18,8 +18,13 @@ thread_local!(
         RefCell::default();
 );
 
+/// evals still waiting for a response, None if borrowed
+pub fn get_waiting_task_count() -> Option<usize> {
+    WAITING_TASKS.with(|cell| Some(cell.try_borrow().ok()?.len()))
+}
+
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RustV8Value {
     Unknown,
     Array,
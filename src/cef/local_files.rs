@@ -0,0 +1,372 @@
+//! `local://files/<path>` serves files from `cef/www`, so screens can play
+//! videos and images everyone has without hosting them anywhere
+
+use std::{
+    env,
+    ffi::CStr,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use url::Url;
+
+use crate::error::{Result, ResultExt, bail, ensure};
+
+pub const HOST: &str = "files";
+
+/// what to answer with, the file itself is streamed from disk by CEF
+pub struct FileResponse {
+    pub path: PathBuf,
+    pub mime_type: &'static CStr,
+    pub size: u64,
+    /// first and last byte, for a `206 Partial Content`
+    pub range: Option<(u64, u64)>,
+}
+
+impl FileResponse {
+    /// bytes to send
+    pub fn get_length(&self) -> u64 {
+        self.range.map_or(self.size, |(start, end)| end - start + 1)
+    }
+
+    pub fn get_content_range(&self) -> Option<String> {
+        self.range
+            .map(|(start, end)| format!("bytes {start}-{end}/{}", self.size))
+    }
+}
+
+pub fn is_local_file(url: &Url) -> bool {
+    url.scheme() == "local" && url.host_str() == Some(HOST)
+}
+
+fn get_root() -> Result<PathBuf> {
+    Ok(env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("www"))
+}
+
+/// `range` is the request's `Range` header
+pub fn handle_request(url: &Url, range: Option<&str>) -> Result<FileResponse> {
+    let root = get_root()?;
+    let path = resolve_path(&root, url)?;
+
+    // a symlink could still point outside
+    let canonical_root = root.canonicalize().chain_err(|| "no cef/www directory")?;
+    let canonical_path = path
+        .canonicalize()
+        .chain_err(|| format!("no file {}", url.path()))?;
+    ensure!(
+        canonical_path.starts_with(&canonical_root),
+        "{} is outside of cef/www",
+        url
+    );
+
+    let file = File::open(&path).chain_err(|| format!("couldn't open {}", url.path()))?;
+    let metadata = file.metadata()?;
+    ensure!(metadata.is_file(), "{} isn't a file", url.path());
+    let size = metadata.len();
+
+    let mime_type = if let Some(mime_type) = get_mime_type_from_ext(&path) {
+        mime_type
+    } else {
+        let mut head = Vec::new();
+        file.take(512).read_to_end(&mut head)?;
+        sniff_mime_type(&head)
+    };
+
+    let range = range.map(|range| parse_range(range, size)).transpose()?;
+
+    Ok(FileResponse {
+        path,
+        mime_type,
+        size,
+        range,
+    })
+}
+
+/// the file a url points to inside `root`, anything that could leave it is
+/// an error
+fn resolve_path(root: &Path, url: &Url) -> Result<PathBuf> {
+    ensure!(is_local_file(url), "not a local://{} url", HOST);
+
+    let mut path = root.to_path_buf();
+    let mut depth = 0;
+    for segment in url.path_segments().chain_err(|| "no path segments")? {
+        let segment = percent_decode(segment)?;
+        if segment.is_empty() {
+            continue;
+        }
+
+        ensure!(
+            !segment.contains(['/', '\\', ':', '\0']),
+            "bad path segment {:?}",
+            segment
+        );
+
+        let mut components = Path::new(&segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) => path.push(name),
+            _ => bail!("bad path segment {:?}", segment),
+        }
+        depth += 1;
+    }
+    ensure!(depth > 0, "no file name");
+
+    Ok(path)
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|c| c.is_ascii_hexdigit()))
+                .chain_err(|| "bad percent encoding")?;
+            decoded.push(u8::from_str_radix(hex, 16)?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).chain_err(|| "path isn't utf-8")
+}
+
+/// first and last byte of a `Range: bytes=` header, only the first range of
+/// a list is used
+fn parse_range(header: &str, size: u64) -> Result<(u64, u64)> {
+    ensure!(size > 0, "range {:?} of an empty file", header);
+
+    let range = header
+        .trim()
+        .strip_prefix("bytes=")
+        .chain_err(|| format!("unsupported range {header:?}"))?;
+    let range = range.split(',').next().unwrap_or_default().trim();
+    let (start, end) = range
+        .split_once('-')
+        .chain_err(|| format!("bad range {header:?}"))?;
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // the last n bytes
+        ("", suffix) => {
+            let suffix = suffix.parse::<u64>()?;
+            ensure!(suffix > 0, "empty range {:?}", header);
+            (size.saturating_sub(suffix), size - 1)
+        }
+        (start, "") => (start.parse::<u64>()?, size - 1),
+        (start, end) => (start.parse::<u64>()?, end.parse::<u64>()?.min(size - 1)),
+    };
+    ensure!(
+        start <= end && start < size,
+        "range {:?} not in a file of {} bytes",
+        header,
+        size
+    );
+
+    Ok((start, end))
+}
+
+fn get_mime_type_from_ext(path: &Path) -> Option<&'static CStr> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    Some(match ext.as_str() {
+        "html" | "htm" => c"text/html",
+        "css" => c"text/css",
+        "js" | "mjs" => c"text/javascript",
+        "json" => c"application/json",
+        "txt" | "srt" => c"text/plain",
+        "vtt" => c"text/vtt",
+        "xml" => c"application/xml",
+        "wasm" => c"application/wasm",
+
+        "png" | "apng" => c"image/png",
+        "jpg" | "jpeg" | "jfif" => c"image/jpeg",
+        "gif" => c"image/gif",
+        "webp" => c"image/webp",
+        "avif" => c"image/avif",
+        "svg" => c"image/svg+xml",
+        "bmp" => c"image/bmp",
+        "ico" => c"image/x-icon",
+
+        "mp4" | "m4v" => c"video/mp4",
+        "webm" | "mkv" => c"video/webm",
+        "ogv" => c"video/ogg",
+        "mov" => c"video/quicktime",
+        "mp3" => c"audio/mpeg",
+        "m4a" | "aac" => c"audio/mp4",
+        "ogg" | "oga" | "opus" => c"audio/ogg",
+        "wav" => c"audio/wav",
+        "flac" => c"audio/flac",
+        "weba" => c"audio/webm",
+
+        "m3u8" => c"application/vnd.apple.mpegurl",
+        "mpd" => c"application/dash+xml",
+
+        "woff" => c"font/woff",
+        "woff2" => c"font/woff2",
+        "ttf" => c"font/ttf",
+        "otf" => c"font/otf",
+
+        _ => return None,
+    })
+}
+
+/// from the first bytes of a file without a known extension
+fn sniff_mime_type(head: &[u8]) -> &'static CStr {
+    let starts_with_ignore_case = |prefix: &[u8]| {
+        let start = head.iter().position(|c| !c.is_ascii_whitespace());
+        start.is_some_and(|start| {
+            head[start..]
+                .get(..prefix.len())
+                .is_some_and(|bytes| bytes.eq_ignore_ascii_case(prefix))
+        })
+    };
+
+    match head {
+        [0x89, b'P', b'N', b'G', ..] => c"image/png",
+        [0xFF, 0xD8, 0xFF, ..] => c"image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => c"image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => c"image/webp",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'A',
+            b'V',
+            b'E',
+            ..,
+        ] => c"audio/wav",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => c"video/webm",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => c"video/mp4",
+        [b'O', b'g', b'g', b'S', ..] => c"audio/ogg",
+        [b'f', b'L', b'a', b'C', ..] => c"audio/flac",
+        [b'I', b'D', b'3', ..] => c"audio/mpeg",
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => c"audio/mpeg",
+        [b'%', b'P', b'D', b'F', ..] => c"application/pdf",
+        _ if starts_with_ignore_case(b"<!doctype html") || starts_with_ignore_case(b"<html") => {
+            c"text/html"
+        }
+        _ if starts_with_ignore_case(b"<svg") => c"image/svg+xml",
+        _ if is_text(head) => c"text/plain",
+        _ => c"application/octet-stream",
+    }
+}
+
+fn is_text(head: &[u8]) -> bool {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        // the last character was cut in half
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+
+    !text.chars().any(|c| c.is_control() && !c.is_whitespace())
+}
+
+#[test]
+fn test_resolve_path() {
+    let root = Path::new("www");
+    let resolve = |url: &str| resolve_path(root, &Url::parse(url).unwrap());
+
+    assert_eq!(
+        resolve("local://files/videos/intro.mp4").unwrap(),
+        root.join("videos").join("intro.mp4")
+    );
+    assert_eq!(
+        resolve("local://files/my%20file.png?v=2#frag").unwrap(),
+        root.join("my file.png")
+    );
+
+    // the url parser already drops `..`, so these can't climb out either
+    assert_eq!(
+        resolve("local://files/a/../b.png").unwrap(),
+        root.join("b.png")
+    );
+    assert_eq!(
+        resolve("local://files/../../secret.txt").unwrap(),
+        root.join("secret.txt")
+    );
+    assert_eq!(
+        resolve("local://files/%2e%2e/secret.txt").unwrap(),
+        root.join("secret.txt")
+    );
+
+    for url in [
+        "local://files/..%2fsecret.txt",
+        "local://files/..%2Fsecret.txt",
+        "local://files/..%5csecret.txt",
+        "local://files/%2e%2e%2f%2e%2e%2fsecret.txt",
+        "local://files/%2fetc%2fpasswd",
+        "local://files/C%3a%5cWindows%5cwin.ini",
+        "local://files/a%00.png",
+        "local://files/%zz",
+        "local://files/%+f",
+        "local://files/",
+        "local://media/video.mp4",
+    ] {
+        assert!(resolve(url).is_err(), "{url} should be rejected");
+    }
+}
+
+#[test]
+fn test_parse_range() {
+    assert_eq!(parse_range("bytes=0-", 100).unwrap(), (0, 99));
+    assert_eq!(parse_range("bytes=10-19", 100).unwrap(), (10, 19));
+    assert_eq!(parse_range("bytes=90-200", 100).unwrap(), (90, 99));
+    assert_eq!(parse_range("bytes=-10", 100).unwrap(), (90, 99));
+    assert_eq!(parse_range("bytes=-200", 100).unwrap(), (0, 99));
+    assert_eq!(parse_range("bytes=0-0, 5-9", 100).unwrap(), (0, 0));
+
+    assert!(parse_range("bytes=100-", 100).is_err());
+    assert!(parse_range("bytes=20-10", 100).is_err());
+    assert!(parse_range("bytes=-0", 100).is_err());
+    assert!(parse_range("items=0-1", 100).is_err());
+    assert!(parse_range("bytes=a-b", 100).is_err());
+    assert!(parse_range("bytes=0-", 0).is_err());
+}
+
+#[test]
+fn test_mime_type() {
+    assert_eq!(
+        get_mime_type_from_ext(Path::new("a/Video.MP4")),
+        Some(c"video/mp4")
+    );
+    assert_eq!(get_mime_type_from_ext(Path::new("noext")), None);
+
+    assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n"), c"image/png");
+    assert_eq!(sniff_mime_type(b"\0\0\0\x20ftypisom"), c"video/mp4");
+    assert_eq!(sniff_mime_type(b"\x1a\x45\xdf\xa3"), c"video/webm");
+    assert_eq!(sniff_mime_type(b"ID3\x04"), c"audio/mpeg");
+    assert_eq!(sniff_mime_type(b"  <!DOCTYPE html><p>"), c"text/html");
+    assert_eq!(sniff_mime_type("hello ✓".as_bytes()), c"text/plain");
+    assert_eq!(sniff_mime_type(b"\0\x01\x02"), c"application/octet-stream");
+}
@@ -1,7 +1,12 @@
+mod backend;
 mod bindings;
 mod browser;
+#[cfg(test)]
+mod fake_browser;
 mod javascript;
+mod local_files;
 mod mute_lose_focus;
+pub mod profile;
 
 use std::{
     cell::{Cell, RefCell},
@@ -15,20 +20,28 @@ use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::sync::broadcast;
 use tracing::{Instrument, debug, debug_span, error, warn};
 
+#[cfg(test)]
+pub use self::fake_browser::FakeBrowser;
 pub use self::{
+    backend::Browser,
     bindings::{
-        Callbacks, RustRefApp, RustRefBrowser, RustRefClient, cef_interface_execute_process,
+        Callbacks, FFIResourceType, NavigationEntry, RustRefApp, RustRefBrowser, RustRefClient,
+        cef_interface_execute_process,
     },
     javascript::RustV8Value,
+    local_files::is_local_file,
+    profile::{Profile, ProfileName},
 };
 use self::{
-    browser::{BROWSER_SIZES, BROWSERS},
+    browser::{BROWSER_PROFILES, BROWSER_SIZES, BROWSERS},
     mute_lose_focus::IS_FOCUSED,
 };
 use crate::{
-    entity_manager::{TEXTURE_HEIGHT, TEXTURE_WIDTH, cef_paint_callback},
+    adblock, audio,
+    diagnostics::DebugState,
+    entity_manager::{EntityManager, TEXTURE_HEIGHT, TEXTURE_WIDTH, cef_paint_callback, popup},
     error::{Result, ResultExt, bail},
-    options::MUTE_LOSE_FOCUS,
+    options::{MUTE_LOSE_FOCUS, NATIVE_AUDIO},
 };
 
 pub const CEF_DEFAULT_WIDTH: u16 = 1920;
@@ -37,10 +50,11 @@ pub const CEF_DEFAULT_HEIGHT: u16 = 1080;
 #[derive(Debug, Clone)]
 pub enum CefEvent {
     ContextInitialized(RustRefClient),
-    BrowserCreated(RustRefBrowser),
-    BrowserPageLoaded(RustRefBrowser),
-    BrowserTitleChange(RustRefBrowser, String),
-    BrowserClosed(RustRefBrowser),
+    BrowserCreated(Browser),
+    BrowserPageLoaded(Browser),
+    BrowserTitleChange(Browser, String),
+    BrowserAddressChange(Browser, String),
+    BrowserClosed(Browser),
 }
 
 thread_local!(
@@ -85,14 +99,21 @@ impl Cef {
             on_before_close: Some(browser::on_before_close),
             on_load_end: Some(browser::on_page_loaded),
             on_title_change: Some(browser::on_title_change),
+            on_address_change: Some(browser::on_address_change),
             on_paint: Some(cef_paint_callback),
             get_view_rect: Some(browser::get_view_rect),
             on_javascript: Some(javascript::on_javascript_callback),
             on_certificate_error: Some(browser::on_certificate_error_callback),
+            on_before_popup: Some(popup::on_before_popup_callback),
+            on_before_resource_load: Some(adblock::on_before_resource_load),
+            on_audio_stream_started: Some(audio::on_audio_stream_started),
+            on_audio_stream_packet: Some(audio::on_audio_stream_packet),
+            on_audio_stream_stopped: Some(audio::on_audio_stream_stopped),
         });
 
         let mut event_receiver = Self::create_event_listener();
 
+        adblock::initialize();
         app.initialize()?;
 
         let client = loop {
@@ -103,6 +124,12 @@ impl Cef {
 
         IS_INITIALIZED.set(true);
 
+        if NATIVE_AUDIO.get()?
+            && let Err(e) = audio::enable(&client)
+        {
+            warn!("couldn't enable native audio: {}", e);
+        }
+
         async_manager::spawn_local_on_main_thread(async move {
             while crate::time_silent!("Cef::step()", 100, { Cef::step() }) {
                 async_manager::yield_now().await;
@@ -130,9 +157,15 @@ impl Cef {
         // load a blank browser so that the next load is quicker
         async_manager::spawn_local_on_main_thread(
             async {
-                let browser = Self::create_browser("data:text/html,", 30, false, 0x00FF_FFFF)
-                    .await
-                    .unwrap();
+                let browser = Self::create_browser(
+                    "data:text/html,",
+                    30,
+                    false,
+                    0x00FF_FFFF,
+                    Profile::Incognito,
+                )
+                .await
+                .unwrap();
                 Self::close_browser(&browser).await.unwrap();
             }
             .instrument(debug_span!("warm_up")),
@@ -175,6 +208,8 @@ impl Cef {
 
         browser::shutdown();
         javascript::shutdown();
+        audio::shutdown();
+        adblock::shutdown();
 
         IS_INITIALIZED.set(false);
     }
@@ -201,7 +236,8 @@ impl Cef {
         fps: u16,
         insecure: bool,
         background_color: u32,
-    ) -> Result<RustRefBrowser> {
+        profile: Profile,
+    ) -> Result<Browser> {
         let mut create_browser_mutex = {
             let mut mutex = CEF.with(Clone::clone);
             let maybe_cef = mutex.lock().await;
@@ -226,7 +262,14 @@ impl Cef {
             (client, event_receiver)
         };
 
-        client.create_browser(url, fps as _, insecure, background_color)?;
+        let cache_path = profile.get_cache_path()?;
+        client.create_browser(
+            url,
+            fps as _,
+            insecure,
+            background_color,
+            cache_path.as_deref(),
+        )?;
 
         let browser = loop {
             if let CefEvent::BrowserCreated(browser) = event_receiver.recv().await.unwrap() {
@@ -236,7 +279,12 @@ impl Cef {
 
         let browser_id = browser.get_identifier();
 
-        debug!("Cef::create_browser => {}", browser_id);
+        debug!("Cef::create_browser => {} ({})", browser_id, profile);
+
+        BROWSER_PROFILES.with(|cell| {
+            let profiles = &mut *cell.borrow_mut();
+            profiles.insert(browser_id, profile);
+        });
 
         if Self::should_mute_for_focus() {
             browser.set_audio_muted(true)?;
@@ -258,7 +306,7 @@ impl Cef {
         MUTE_LOSE_FOCUS.get().unwrap_or(false) && !IS_FOCUSED.get()
     }
 
-    pub async fn close_browser(browser: &RustRefBrowser) -> Result<()> {
+    pub async fn close_browser(browser: &Browser) -> Result<()> {
         let mut event_receiver = Self::create_event_listener();
 
         let id = browser.get_identifier();
@@ -278,7 +326,7 @@ impl Cef {
 
     pub async fn close_all_browsers() {
         // must clone here or we will recurse into `close` and borrow multiple times
-        let browsers: HashMap<c_int, RustRefBrowser> = BROWSERS.with(|cell| {
+        let browsers: HashMap<c_int, Browser> = BROWSERS.with(|cell| {
             let browsers = &mut *cell.borrow_mut();
             mem::take(browsers)
         });
@@ -297,15 +345,53 @@ impl Cef {
         }
     }
 
+    /// only affects browsers created after this
+    pub async fn set_native_audio(enabled: bool) -> Result<()> {
+        let mut mutex = CEF.with(Clone::clone);
+        let maybe_cef = mutex.lock().await;
+        let cef = maybe_cef.as_ref().chain_err(|| "no cef")?;
+
+        if enabled {
+            audio::enable(&cef.client)
+        } else {
+            audio::disable(&cef.client)
+        }
+    }
+
     pub fn set_audio_muted_all(mute: bool) {
         BROWSERS.with(|cell| {
             let browsers = &mut *cell.borrow_mut();
-            for browser in browsers.values() {
-                browser.set_audio_muted(mute).unwrap();
+            for (&browser_id, browser) in browsers.iter() {
+                // screens we muted ourselves stay muted when we regain focus
+                let locally_muted = EntityManager::with_by_browser_id(browser_id, |entity| {
+                    Ok(entity.local.is_muted())
+                })
+                .unwrap_or(false);
+
+                browser.set_audio_muted(mute || locally_muted).unwrap();
             }
         });
     }
 
+    /// copy our browser maps for `cef debug state` and crash reports,
+    /// leaving out any that were borrowed so this never panics
+    pub fn fill_debug_state(state: &mut DebugState) {
+        state.browsers =
+            BROWSERS.with(|cell| Some(cell.try_borrow().ok()?.keys().copied().collect()));
+
+        state.browser_sizes = BROWSER_SIZES.with(|cell| {
+            Some(
+                cell.try_borrow()
+                    .ok()?
+                    .iter()
+                    .map(|(&browser_id, &size)| (browser_id, size))
+                    .collect(),
+            )
+        });
+
+        state.waiting_javascript = javascript::get_waiting_task_count();
+    }
+
     fn set_browser_size(browser_id: c_int, width: u16, height: u16) -> Result<()> {
         // 0 size crashes
         if width < 1 || height < 1 || width > TEXTURE_WIDTH || height > TEXTURE_HEIGHT {
@@ -321,7 +407,7 @@ impl Cef {
         Ok(())
     }
 
-    pub fn resize_browser(browser: &RustRefBrowser, width: u16, height: u16) -> Result<()> {
+    pub fn resize_browser(browser: &Browser, width: u16, height: u16) -> Result<()> {
         let browser_id = browser.get_identifier();
 
         Self::set_browser_size(browser_id, width, height)?;
@@ -330,7 +416,7 @@ impl Cef {
         Ok(())
     }
 
-    pub fn get_browser_size(browser: &RustRefBrowser) -> (u16, u16) {
+    pub fn get_browser_size(browser: &Browser) -> (u16, u16) {
         let browser_id = browser.get_identifier();
         BROWSER_SIZES.with(move |cell| {
             let sizes = &*cell.borrow();
@@ -341,4 +427,12 @@ impl Cef {
                 .unwrap_or((CEF_DEFAULT_WIDTH, CEF_DEFAULT_HEIGHT))
         })
     }
+
+    pub fn get_browser_profile(browser: &Browser) -> Option<Profile> {
+        let browser_id = browser.get_identifier();
+        BROWSER_PROFILES.with(move |cell| {
+            let profiles = &*cell.borrow();
+            profiles.get(&browser_id).copied()
+        })
+    }
 }
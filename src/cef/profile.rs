@@ -0,0 +1,311 @@
+//! named browser profiles, each with its own cookies and storage in
+//! `cef/cache/profiles/<name>`
+//!
+//! only one game can use `cef/cache` at a time; any others fall back to a
+//! throwaway cache where every screen is incognito
+
+use std::{
+    cell::Cell,
+    env,
+    fmt::{self, Debug, Display},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{debug, warn};
+
+use super::browser::BROWSER_PROFILES;
+use crate::{
+    error::{Error, Result, ResultExt, bail, ensure},
+    options::PROFILE,
+};
+
+const MAX_NAME_LEN: usize = 32;
+
+/// how many throwaway caches we keep when another game has `cef/cache`
+const MAX_INSTANCE_CACHES: usize = 10;
+
+/// held for the life of the process, CEF can't change caches after it starts
+static CACHE_LOCK: OnceLock<File> = OnceLock::new();
+
+thread_local!(
+    static WARNED_NO_PROFILES: Cell<bool> = const { Cell::new(false) };
+);
+
+/// lowercase letters, numbers, `-` and `_`
+///
+/// fixed size so it can be a `RustOption`
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProfileName {
+    bytes: [u8; MAX_NAME_LEN],
+    len: u8,
+}
+
+impl ProfileName {
+    pub const DEFAULT: Self = Self::from_static("default");
+
+    const fn from_static(name: &str) -> Self {
+        let name = name.as_bytes();
+        let mut bytes = [0; MAX_NAME_LEN];
+        let mut i = 0;
+        while i < name.len() {
+            bytes[i] = name[i];
+            i += 1;
+        }
+
+        Self {
+            bytes,
+            len: name.len() as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl FromStr for ProfileName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_ascii_lowercase();
+        ensure!(!name.is_empty(), "profile name is empty");
+        ensure!(
+            name.len() <= MAX_NAME_LEN,
+            "profile name is longer than {} characters",
+            MAX_NAME_LEN
+        );
+        ensure!(
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "profile name can only have letters, numbers, - and _"
+        );
+
+        Ok(Self::from_static(&name))
+    }
+}
+
+impl Display for ProfileName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for ProfileName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Profile {
+    Named(ProfileName),
+    /// nothing is written to disk, and nothing is shared with other screens
+    Incognito,
+}
+
+impl Profile {
+    /// from `cef config profile`
+    pub fn get_default() -> Result<Self> {
+        Ok(Profile::Named(PROFILE.get()?))
+    }
+
+    /// None keeps everything in memory
+    pub fn get_cache_path(&self) -> Result<Option<PathBuf>> {
+        match self {
+            Profile::Named(name) => {
+                if CACHE_LOCK.get().is_none() {
+                    if !WARNED_NO_PROFILES.replace(true) {
+                        warn!("another game is using cef/cache, all screens are incognito");
+                    }
+                    return Ok(None);
+                }
+
+                Ok(Some(get_profiles_path()?.join(name.as_str())))
+            }
+
+            Profile::Incognito => Ok(None),
+        }
+    }
+
+    fn is_in_use(&self) -> bool {
+        BROWSER_PROFILES.with_borrow(|profiles| profiles.values().any(|profile| profile == self))
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Profile::Named(name) => Display::fmt(name, f),
+            Profile::Incognito => f.write_str("incognito"),
+        }
+    }
+}
+
+fn get_cache_path() -> Result<PathBuf> {
+    Ok(env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("cache"))
+}
+
+fn get_profiles_path() -> Result<PathBuf> {
+    Ok(get_cache_path()?.join("profiles"))
+}
+
+/// `cef/cache` if we're the only game using it, else a new throwaway
+/// folder inside it
+pub fn prepare_root_cache_path() -> Result<PathBuf> {
+    let cache_path = get_cache_path()?;
+    fs::create_dir_all(&cache_path).chain_err(|| "create cache dir")?;
+
+    if CACHE_LOCK.get().is_some() {
+        return Ok(cache_path);
+    }
+
+    let lock_file = File::options()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(cache_path.join("plugin.lock"))
+        .chain_err(|| "open cache lock")?;
+    if lock_file.try_lock().is_ok() {
+        let _ignore = CACHE_LOCK.set(lock_file);
+        remove_instance_caches(&cache_path, 0)?;
+        return Ok(cache_path);
+    }
+
+    warn!("cef/cache is in use by another game, using a throwaway cache");
+    remove_instance_caches(&cache_path, MAX_INSTANCE_CACHES - 1)?;
+
+    let epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .chain_err(|| "get current time")?
+        .as_millis();
+    Ok(cache_path
+        .join("instances")
+        .join(format!("{epoch}-{}", process::id())))
+}
+
+/// keeps the newest `keep` throwaway caches, also removes the per-launch
+/// caches older versions made
+fn remove_instance_caches(cache_path: &Path, keep: usize) -> Result<()> {
+    let mut dirs = Vec::new();
+    for parent in [cache_path.to_path_buf(), cache_path.join("instances")] {
+        let Ok(entries) = fs::read_dir(&parent) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(is_instance_dir_name)
+            {
+                dirs.push(path);
+            }
+        }
+    }
+
+    // oldest first
+    dirs.sort_by_key(|dir| dir.file_name().map(ToOwned::to_owned));
+    let remove_count = dirs.len().saturating_sub(keep);
+    for dir in dirs.drain(..remove_count) {
+        debug!("removing old cache dir: {}", dir.display());
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            // probably the other game's
+            warn!("remove_dir_all {}: {}", dir.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+/// `<epoch>-<pid>`
+fn is_instance_dir_name(name: &str) -> bool {
+    name.split_once('-').is_some_and(|(epoch, pid)| {
+        !epoch.is_empty()
+            && !pid.is_empty()
+            && epoch.chars().all(|c| c.is_ascii_digit())
+            && pid.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+/// name and bytes on disk of every saved profile
+pub fn list() -> Result<Vec<(String, u64)>> {
+    let profiles_path = get_profiles_path()?;
+    if !profiles_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(&profiles_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            profiles.push((name, get_dir_size(&entry.path())?));
+        }
+    }
+    profiles.sort();
+
+    Ok(profiles)
+}
+
+/// deletes a profile's cookies, logins and cache
+pub fn clear(name: ProfileName) -> Result<()> {
+    if Profile::Named(name).is_in_use() {
+        bail!("profile {} is in use, close its screens first", name);
+    }
+
+    let path = get_profiles_path()?.join(name.as_str());
+    if !path.exists() {
+        bail!("profile {} has nothing saved", name);
+    }
+    fs::remove_dir_all(&path).chain_err(|| format!("couldn't delete profile {name}"))?;
+
+    Ok(())
+}
+
+fn get_dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += get_dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[test]
+fn test_profile_name() {
+    let name: ProfileName = " Work_2 ".parse().unwrap();
+    assert_eq!(name.as_str(), "work_2");
+    assert_eq!(name.to_string(), "work_2");
+    assert_eq!(ProfileName::DEFAULT.as_str(), "default");
+    assert_eq!(
+        "DEFAULT".parse::<ProfileName>().unwrap(),
+        ProfileName::DEFAULT
+    );
+
+    assert!("".parse::<ProfileName>().is_err());
+    assert!("../escape".parse::<ProfileName>().is_err());
+    assert!("has space".parse::<ProfileName>().is_err());
+    assert!("a".repeat(MAX_NAME_LEN).parse::<ProfileName>().is_ok());
+    assert!("a".repeat(MAX_NAME_LEN + 1).parse::<ProfileName>().is_err());
+
+    assert!(is_instance_dir_name("1700000000000-1234"));
+    assert!(!is_instance_dir_name("profiles"));
+    assert!(!is_instance_dir_name("-1234"));
+    assert!(!is_instance_dir_name("abc-1234"));
+}
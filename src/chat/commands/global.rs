@@ -1,11 +1,12 @@
 //! global commands not targetted at a specific entity
 
 use clap::Subcommand;
-use classicube_helpers::async_manager;
+use classicube_helpers::{async_manager, entities::ENTITY_SELF_ID};
 use classicube_sys::{Chat_Send, OwnedString};
 
 use super::helpers::move_entity;
 use crate::{
+    cef::{Profile, ProfileName},
     chat::{Chat, PlayerSnapshot},
     entity_manager::{EntityBuilder, EntityManager, TargetEntity},
     error::{Result, bail},
@@ -86,6 +87,16 @@ pub enum Commands {
         #[arg(long, short)]
         transparent: bool,
 
+        /// Browser profile to keep cookies and logins in
+        ///
+        /// Defaults to "cef config profile", only used on your own screens
+        #[arg(long, conflicts_with("incognito"))]
+        profile: Option<ProfileName>,
+
+        /// Don't save or share cookies and logins
+        #[arg(long)]
+        incognito: bool,
+
         // url has to be multiple because urls can be chopped in half by
         // line continuations, so we join the parts together as a hack
         #[arg(allow_hyphen_values(true))]
@@ -135,12 +146,14 @@ pub async fn run(player_snapshot: PlayerSnapshot, commands: Commands) -> Result<
 
         Commands::Create {
             global,
+            incognito,
             insecure,
             r#loop,
             name,
             no_autoplay,
             no_send,
             no_wait,
+            profile,
             silent,
             transparent,
             url,
@@ -192,6 +205,17 @@ pub async fn run(player_snapshot: PlayerSnapshot, commands: Commands) -> Result<
                 entity_builder = entity_builder.name(name);
             }
 
+            if player_snapshot.id != ENTITY_SELF_ID {
+                entity_builder = entity_builder.creator(player_snapshot.real_name.clone());
+            }
+
+            // other players' pages never see our cookies or logins
+            if incognito || player_snapshot.id != ENTITY_SELF_ID {
+                entity_builder = entity_builder.profile(Profile::Incognito);
+            } else if let Some(profile) = profile {
+                entity_builder = entity_builder.profile(Profile::Named(profile));
+            }
+
             let entity_id = entity_builder.create().await?;
 
             if !global {
@@ -250,7 +274,7 @@ pub async fn run(player_snapshot: PlayerSnapshot, commands: Commands) -> Result<
 fn test_commands_global() {
     let run = |cmd: &'static str| async {
         crate::chat::commands::run(
-            unsafe { std::mem::zeroed() },
+            crate::chat::PlayerSnapshot::zeroed(),
             cmd.split(' ').map(str::to_string).collect(),
             true,
             true,
@@ -1,9 +1,15 @@
 //! commands that should only run on the person who said them
 
-use std::time::Duration;
+use std::{
+    env, fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use clap::Subcommand;
-use classicube_helpers::{async_manager, color::SILVER};
+use classicube_helpers::{
+    async_manager,
+    color::{GOLD, RED, SILVER, TEAL},
+};
 use classicube_sys::{
     ENTITIES_SELF_ID, Entities, FACE_CONSTS, FACE_CONSTS_FACE_XMAX, FACE_CONSTS_FACE_XMIN,
     FACE_CONSTS_FACE_YMAX, FACE_CONSTS_FACE_YMIN, FACE_CONSTS_FACE_ZMAX, FACE_CONSTS_FACE_ZMIN,
@@ -12,11 +18,24 @@ use classicube_sys::{
 
 use super::{Chat, helpers::get_camera_trace};
 use crate::{
-    api,
+    adblock, api,
+    cef::{ProfileName, profile},
     chat::{PlayerSnapshot, hidden_communication::whispers},
-    entity_manager::{EntityManager, TargetEntity},
-    error::{Result, ResultExt},
-    helpers::format_duration,
+    diagnostics::DebugState,
+    entity_manager::{
+        CefEntity, EntityManager, TargetEntity,
+        animation::{self, Ease, Keyframe, Transform},
+        capture,
+        hud::HudAnchor,
+        local_overrides::{self, LocalOverrides},
+    },
+    error::{Result, ResultExt, bail, ensure},
+    helpers::{format_duration, format_size, parse_duration},
+    logger,
+    options::PROFILE,
+    player::{PlayerTrait, VolumeMode, mixer},
+    scheduler::{self, Trigger},
+    userscripts,
 };
 
 #[derive(Debug, Subcommand)]
@@ -42,15 +61,296 @@ pub enum Commands {
         name: Option<String>,
     },
 
+    /// Show the page a screen is really on
+    Url {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Show the pages a screen has been to, see "cef back" and "cef forward"
+    History {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Show a screen on top of your game, like picture-in-picture
+    ///
+    /// Without options, toggles the overlay
+    #[command(alias("pip"))]
+    Hud {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// Where on your window to show it
+        #[arg(long, short, value_enum)]
+        anchor: Option<HudAnchor>,
+
+        /// Fraction of your window's width, 0 to 1
+        #[arg(long, short)]
+        size: Option<f32>,
+
+        /// 0 is invisible, 1 is solid
+        #[arg(long, short)]
+        opacity: Option<f32>,
+
+        /// Hide the overlay
+        #[arg(long, conflicts_with_all(["anchor", "size", "opacity"]))]
+        off: bool,
+    },
+
     /// Re-sync all screens from someone else
     Sync { player_name: String },
 
+    /// Show how loud every screen is after ducking and focus
+    Mixer,
+
+    /// Save what the screen is showing to screenshots/
+    ///
+    /// With --every, also keep saving frames for a timelapse until --stop
+    #[command(alias("capture"))]
+    Screenshot {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// Save a frame every interval, like 10s or 1m
+        #[arg(long, short)]
+        every: Option<String>,
+
+        /// Stop saving timelapse frames
+        #[arg(long, conflicts_with("every"))]
+        stop: bool,
+    },
+
+    /// Mute, hide or change the volume of screens only for yourself
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Local(LocalCommands),
+
+    /// Look inside the plugin when screens misbehave
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Debug(DebugCommands),
+
+    /// Change what gets written to cef.log
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Log(LogCommands),
+
+    /// Record a flamegraph of what the plugin is doing
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Profile(ProfileCommands),
+
+    /// Scripts and styles from cef/userscripts that run on pages
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Userscript(UserscriptCommands),
+
+    /// See or delete what browser profiles have saved
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Cache(CacheCommands),
+
+    /// Block ads and trackers with the filter lists in cef/filters
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Adblock(AdblockCommands),
+
+    /// Keyframe paths in cef/paths for screens to move along
+    ///
+    /// cef here
+    /// cef path add intro
+    /// cef at 10 5 10
+    /// cef path add intro --seconds 3
+    /// cef path play intro --loop
+    #[command(subcommand, subcommand_required(true), arg_required_else_help(true))]
+    Path(PathCommands),
+
+    /// Run a cef command later
+    ///
+    /// cef schedule 20:00 play -n lobby https://...
+    /// cef schedule +30m stop -n arena
+    /// cef schedule @3:10 skip
+    #[command(
+        subcommand,
+        alias("at-time"),
+        subcommand_required(true),
+        arg_required_else_help(true)
+    )]
+    Schedule(ScheduleCommands),
+
     /// Fake a crash via panic!()
     #[cfg(debug_assertions)]
     #[command(alias("panic"), hide(true))]
     Crash,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum LocalCommands {
+    /// Toggle muting a screen
+    Mute {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Toggle hiding a screen
+    Hide {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Make a screen quieter or louder, -1 to 1, not for global screens
+    VolumeOffset {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        #[arg(allow_negative_numbers(true))]
+        offset: f32,
+    },
+
+    /// Mute and hide every screen a player creates
+    Ignore {
+        /// Their username, not a nickname
+        player_name: String,
+    },
+
+    /// Stop ignoring a player's screens
+    Unignore { player_name: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DebugCommands {
+    /// Save screens, browsers and background tasks to a file and check
+    /// that they agree with each other
+    State,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LogCommands {
+    /// Show or set the log filter
+    ///
+    /// cef log level debug
+    /// cef log level info,classicube_cef_plugin::player=trace
+    Level {
+        /// Same syntax as RUST_LOG
+        filter: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileCommands {
+    /// Start recording to cef/flame-<timestamp>.folded
+    Start,
+
+    /// Stop recording and save the file
+    Stop,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserscriptCommands {
+    /// List userscripts and what they match
+    List,
+
+    /// Run a userscript again from the next page load
+    Enable {
+        /// File name inside cef/userscripts
+        name: String,
+    },
+
+    /// Stop running a userscript, pages already open keep it until reloaded
+    Disable {
+        /// File name inside cef/userscripts
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AdblockCommands {
+    /// Block ads on a screen
+    On {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Stop blocking ads on a screen, reload it to get what was blocked
+    Off {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Read the filter lists in cef/filters again
+    Reload,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    /// Show how much each profile has saved
+    Size,
+
+    /// Delete a profile's cookies, logins and cache
+    ///
+    /// cef cache clear
+    /// cef cache clear work
+    /// cef cache clear all
+    Clear {
+        /// Defaults to "cef config profile"
+        profile: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PathCommands {
+    /// List saved paths, or the keyframes of one
+    List { path: Option<String> },
+
+    /// Add where a screen is now as the next keyframe of a path
+    Add {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        path: String,
+
+        /// Time to get here from the last keyframe
+        #[arg(long, short, default_value_t = 1.0)]
+        seconds: f32,
+
+        #[arg(long, short, value_enum, default_value_t = Ease::InOut)]
+        ease: Ease,
+    },
+
+    /// Delete a saved path
+    Remove { path: String },
+
+    /// Move a screen along a saved path, for everyone
+    Play {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        path: String,
+
+        /// Start again from the first keyframe after the last
+        #[arg(long, short)]
+        r#loop: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ScheduleCommands {
+    /// List scheduled commands
+    List,
+
+    /// Cancel a scheduled command
+    Cancel { id: usize },
+
+    /// <when> <command...> where when is 20:00, +30m or @3:10
+    #[command(external_subcommand)]
+    Add(Vec<String>),
+}
+
 pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
     match commands {
         Commands::Search { search } => {
@@ -105,6 +405,148 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             Chat::send(format!("cef at{maybe_name} {x} {y} {z} {yaw} {pitch}"));
         }
 
+        Commands::Screenshot { name, every, stop } => {
+            let entity_id = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| Ok(entity.id),
+            )?;
+
+            if stop {
+                if !capture::stop_timelapse(entity_id) {
+                    bail!("no timelapse running");
+                }
+                Chat::print(format!("{SILVER}timelapse stopped"));
+                return Ok(());
+            }
+
+            let path = capture::screenshot(entity_id).await?;
+            Chat::print(format!("{SILVER}saved {}", path.display()));
+
+            if let Some(every) = every {
+                let interval = parse_duration(&every)
+                    .filter(|interval| !interval.is_zero())
+                    .chain_err(|| format!("invalid interval {every:?}"))?;
+                capture::start_timelapse(entity_id, interval);
+                Chat::print(format!(
+                    "{SILVER}saving a frame every {}",
+                    format_duration(interval)
+                ));
+            }
+        }
+
+        Commands::Mixer => {
+            let entries = mixer::get_entries();
+            if entries.is_empty() {
+                Chat::print("No screens");
+            }
+
+            let focused = mixer::get_focused();
+            for entry in entries {
+                let name = entry
+                    .name
+                    .map(|name| format!(" {name}"))
+                    .unwrap_or_default();
+
+                let mut tags = String::new();
+                if entry.is_map_theme {
+                    tags.push_str(" map theme");
+                }
+                if focused == Some(entry.entity_id) {
+                    tags.push_str(" focused");
+                }
+
+                Chat::print(format!(
+                    "{GOLD}#{}{name} {SILVER}{}: {:.2} x {:.2} = {TEAL}{:.2}{SILVER}{tags}",
+                    entry.entity_id, entry.type_name, entry.level, entry.gain, entry.effective
+                ));
+            }
+        }
+
+        Commands::Local(LocalCommands::Mute { name }) => {
+            with_local_overrides(&player, name, |local| local.muted = !local.muted)?;
+        }
+
+        Commands::Local(LocalCommands::Hide { name }) => {
+            with_local_overrides(&player, name, |local| local.hidden = !local.hidden)?;
+        }
+
+        Commands::Local(LocalCommands::VolumeOffset { name, offset }) => {
+            ensure!(
+                (-1.0..=1.0).contains(&offset),
+                "offset must be between -1 and 1"
+            );
+
+            // global screens play at the volume you set, so the mixer never
+            // touches them
+            let is_global = EntityManager::with_entity(
+                name.as_ref().map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    TargetEntity::get_entity_id,
+                )?,
+                |entity| Ok(entity.player.get_volume_mode() == VolumeMode::Global),
+            )?;
+            ensure!(
+                offset.abs() < 0.0001 || !is_global,
+                "volume offset doesn't work on global screens, use cef volume instead"
+            );
+
+            with_local_overrides(&player, name, |local| local.volume_offset = offset)?;
+        }
+
+        Commands::Local(LocalCommands::Ignore { player_name }) => {
+            set_player_ignored(&player_name, true);
+            Chat::print(format!("{SILVER}ignoring screens from {player_name}"));
+        }
+
+        Commands::Local(LocalCommands::Unignore { player_name }) => {
+            set_player_ignored(&player_name, false);
+            Chat::print(format!("{SILVER}no longer ignoring {player_name}"));
+        }
+
+        Commands::Url { name } => {
+            let (id, url) = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    let browser = entity.browser.as_ref().chain_err(|| "no browser")?;
+                    Ok((entity.id, browser.get_url()))
+                },
+            )?;
+            Chat::print(format!("{GOLD}#{id} {TEAL}{url}"));
+        }
+
+        Commands::History { name } => {
+            let (id, entries) = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    let browser = entity.browser.as_ref().chain_err(|| "no browser")?;
+                    Ok((entity.id, browser.get_navigation_entries()?))
+                },
+            )?;
+
+            Chat::print(format!("{GOLD}#{id} {SILVER}history:"));
+            for (i, entry) in entries.iter().enumerate() {
+                let title = if entry.title.is_empty() {
+                    &entry.url
+                } else {
+                    &entry.title
+                };
+                if entry.current {
+                    Chat::print(format!("{TEAL}{}. {title} (current)", i + 1));
+                } else {
+                    Chat::print(format!("{SILVER}{}. {title}", i + 1));
+                }
+            }
+        }
+
         Commands::Devtools { name } => {
             EntityManager::with_entity(
                 name.map_or_else(
@@ -121,6 +563,55 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             )?;
         }
 
+        Commands::Hud {
+            name,
+            anchor,
+            size,
+            opacity,
+            off,
+        } => {
+            if let Some(size) = size {
+                ensure!((0.0..=1.0).contains(&size), "size must be between 0 and 1");
+            }
+            if let Some(opacity) = opacity {
+                ensure!(
+                    (0.0..=1.0).contains(&opacity),
+                    "opacity must be between 0 and 1"
+                );
+            }
+
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    let toggle_off = anchor.is_none()
+                        && size.is_none()
+                        && opacity.is_none()
+                        && entity.hud.is_some();
+
+                    if off || toggle_off {
+                        entity.hud = None;
+                    } else {
+                        let mut hud = entity.hud.unwrap_or_default();
+                        if let Some(anchor) = anchor {
+                            hud.anchor = anchor;
+                        }
+                        if let Some(size) = size {
+                            hud.size = size;
+                        }
+                        if let Some(opacity) = opacity {
+                            hud.opacity = opacity;
+                        }
+                        entity.hud = Some(hud);
+                    }
+
+                    Ok(())
+                },
+            )?;
+        }
+
         Commands::Sync { player_name } => {
             // TODO realname search
             let had_data = whispers::outgoing::query_whisper(&player_name).await?;
@@ -133,6 +624,253 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             // TODO 0 args, randomly chosen? maybe everyone like map join?
         }
 
+        Commands::Debug(DebugCommands::State) => {
+            let state = DebugState::capture();
+            let path = state.save()?;
+
+            Chat::print(format!(
+                "{SILVER}{} screens, {} browsers, {} waiting javascript",
+                state.entities.as_ref().map_or(0, Vec::len),
+                state.browsers.as_ref().map_or(0, Vec::len),
+                state.waiting_javascript.unwrap_or_default()
+            ));
+
+            let problems = state.find_problems();
+            if problems.is_empty() {
+                Chat::print(format!("{TEAL}no problems found"));
+            }
+            for problem in problems.iter().take(5) {
+                Chat::print(format!("{RED}{problem}"));
+            }
+            if problems.len() > 5 {
+                Chat::print(format!("{RED}and {} more", problems.len() - 5));
+            }
+
+            Chat::print(format!("{SILVER}saved to {}", path.display()));
+        }
+
+        Commands::Log(LogCommands::Level { filter: None }) => {
+            Chat::print(format!(
+                "{SILVER}log level: {TEAL}{}",
+                logger::get_filter()?
+            ));
+        }
+
+        Commands::Log(LogCommands::Level {
+            filter: Some(filter),
+        }) => {
+            let old_filter = logger::get_filter()?;
+            logger::set_filter(&filter)?;
+            Chat::print(format!(
+                "{SILVER}log level: {old_filter} -> {TEAL}{}",
+                logger::get_filter()?
+            ));
+        }
+
+        Commands::Profile(ProfileCommands::Start) => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .chain_err(|| "system time before epoch")?
+                .as_secs();
+
+            let dir = env::current_dir()
+                .chain_err(|| "current_dir() None")?
+                .join("cef");
+            fs::create_dir_all(&dir)?;
+            logger::start_profile(dir.join(format!("flame-{timestamp}.folded")))?;
+
+            Chat::print(format!(
+                "{SILVER}profiling, {TEAL}cef profile stop{SILVER} to save"
+            ));
+        }
+
+        Commands::Profile(ProfileCommands::Stop) => {
+            let path = logger::stop_profile()?;
+            Chat::print(format!(
+                "{SILVER}saved to {} (folded stacks for inferno or flamegraph.pl)",
+                path.display()
+            ));
+        }
+
+        Commands::Userscript(UserscriptCommands::List) => {
+            userscripts::reload();
+            let scripts = userscripts::load_all()?;
+            if scripts.is_empty() {
+                Chat::print(format!("{SILVER}no userscripts in cef/userscripts"));
+            }
+
+            for script in scripts {
+                let status = if userscripts::is_enabled(&script.name) {
+                    format!("{TEAL}enabled")
+                } else {
+                    format!("{RED}disabled")
+                };
+                let matches = if script.matches.is_empty() {
+                    "every url".to_string()
+                } else {
+                    script.matches.join(" ")
+                };
+                let screens = if script.screens.is_empty() {
+                    String::new()
+                } else {
+                    format!(" on {}", script.screens.join(" "))
+                };
+                Chat::print(format!(
+                    "{SILVER}{}: {status}{SILVER} {matches}{screens}",
+                    script.name
+                ));
+            }
+        }
+
+        Commands::Userscript(UserscriptCommands::Enable { name }) => {
+            userscripts::set_enabled(&name, true)?;
+            Chat::print(format!("{SILVER}enabled {TEAL}{name}"));
+        }
+
+        Commands::Userscript(UserscriptCommands::Disable { name }) => {
+            userscripts::set_enabled(&name, false)?;
+            Chat::print(format!("{SILVER}disabled {TEAL}{name}"));
+        }
+
+        Commands::Adblock(AdblockCommands::On { name }) => {
+            set_adblock(&player, name, true)?;
+        }
+
+        Commands::Adblock(AdblockCommands::Off { name }) => {
+            set_adblock(&player, name, false)?;
+        }
+
+        Commands::Adblock(AdblockCommands::Reload) => {
+            let count = adblock::reload().await?;
+            Chat::print(format!("{SILVER}loaded {GOLD}{count} {SILVER}filter rules"));
+        }
+
+        Commands::Path(PathCommands::List { path: None }) => {
+            let paths = animation::list_paths()?;
+            if paths.is_empty() {
+                Chat::print(format!("{SILVER}no paths in cef/paths"));
+            }
+            for path in paths {
+                let count = animation::load_path(&path).map_or(0, |keyframes| keyframes.len());
+                Chat::print(format!("{SILVER}{path}: {GOLD}{count} {SILVER}keyframes"));
+            }
+        }
+
+        Commands::Path(PathCommands::List { path: Some(path) }) => {
+            for (i, keyframe) in animation::load_path(&path)?.iter().enumerate() {
+                let index = i + 1;
+                Chat::print(format!("{GOLD}{index} {SILVER}{keyframe}"));
+            }
+        }
+
+        Commands::Path(PathCommands::Add {
+            name,
+            path,
+            seconds,
+            ease,
+        }) => {
+            ensure!(
+                seconds.is_finite() && seconds >= 0.0,
+                "seconds can't be negative"
+            );
+
+            let transform = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| Ok(Transform::from_entity(entity)),
+            )?;
+
+            let mut keyframes = if animation::list_paths()?.contains(&path) {
+                animation::load_path(&path)?
+            } else {
+                Vec::new()
+            };
+            keyframes.push(Keyframe {
+                transform,
+                seconds,
+                ease,
+            });
+            animation::save_path(&path, &keyframes)?;
+
+            let count = keyframes.len();
+            Chat::print(format!("{SILVER}{path}: {GOLD}{count} {SILVER}keyframes"));
+        }
+
+        Commands::Path(PathCommands::Remove { path }) => {
+            animation::remove_path(&path)?;
+            Chat::print(format!("{SILVER}removed path {TEAL}{path}"));
+        }
+
+        Commands::Path(PathCommands::Play { name, path, r#loop }) => {
+            let keyframes = animation::load_path(&path)?;
+            ensure!(!keyframes.is_empty(), "path {:?} has no keyframes", path);
+
+            let maybe_name = name.map(|name| format!(" -n {name}")).unwrap_or_default();
+            let maybe_loop = if r#loop { " --loop" } else { "" };
+            let keyframes = keyframes
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(";");
+            // no spaces so chat wrapping this over lines can be undone
+            Chat::send(format!("cef animate{maybe_name}{maybe_loop} {keyframes}"));
+        }
+
+        Commands::Cache(CacheCommands::Size) => {
+            let profiles = profile::list()?;
+            if profiles.is_empty() {
+                Chat::print(format!("{SILVER}no profiles saved"));
+            }
+
+            let mut total = 0;
+            for (name, size) in profiles {
+                Chat::print(format!("{SILVER}{name}: {TEAL}{}", format_size(size)));
+                total += size;
+            }
+            Chat::print(format!("{SILVER}total: {TEAL}{}", format_size(total)));
+        }
+
+        Commands::Cache(CacheCommands::Clear { profile: name }) => {
+            let names = match name.as_deref() {
+                Some("all") => profile::list()?
+                    .into_iter()
+                    .map(|(name, _size)| name.parse())
+                    .collect::<Result<Vec<ProfileName>>>()?,
+                Some(name) => vec![name.parse()?],
+                None => vec![PROFILE.get()?],
+            };
+
+            for name in names {
+                profile::clear(name)?;
+                Chat::print(format!("{SILVER}cleared profile {TEAL}{name}"));
+            }
+        }
+
+        Commands::Schedule(ScheduleCommands::List) => {
+            let tasks = scheduler::list();
+            if tasks.is_empty() {
+                Chat::print(format!("{SILVER}nothing scheduled"));
+            }
+            for (id, trigger, command) in tasks {
+                Chat::print(format!("{SILVER}{id}: {trigger}: cef {command}"));
+            }
+        }
+
+        Commands::Schedule(ScheduleCommands::Cancel { id }) => {
+            scheduler::cancel(id)?;
+            Chat::print(format!("{SILVER}cancelled {id}"));
+        }
+
+        Commands::Schedule(ScheduleCommands::Add(mut args)) => {
+            let when = args.remove(0);
+            let eye_position = player.eye_position;
+            let trigger = Trigger::parse(&when, || eye_position.get_entity_id())?;
+            let id = scheduler::schedule(player, trigger, args)?;
+            Chat::print(format!("{SILVER}scheduled {id} {trigger}"));
+        }
+
         #[cfg(debug_assertions)]
         Commands::Crash => {
             panic!("here's your crash!");
@@ -141,3 +879,78 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
 
     Ok(())
 }
+
+/// change a screen's local overrides, then save and apply them
+fn with_local_overrides<F>(player: &PlayerSnapshot, name: Option<String>, f: F) -> Result<()>
+where
+    F: FnOnce(&mut LocalOverrides),
+{
+    EntityManager::with_entity(
+        name.map_or_else(
+            || player.eye_position.get_entity_id(),
+            |name| name.get_entity_id(),
+        )?,
+        |entity| {
+            f(&mut entity.local);
+            entity.local.save(entity.name.as_deref());
+            apply_local_overrides(entity);
+
+            let status = entity.local.to_string();
+            let status = if status.is_empty() { "none" } else { &status };
+            let saved = if entity.name.is_some() {
+                ""
+            } else {
+                " (unnamed, not saved)"
+            };
+            Chat::print(format!(
+                "{GOLD}#{} {SILVER}local: {TEAL}{status}{SILVER}{saved}",
+                entity.id
+            ));
+
+            Ok(())
+        },
+    )
+}
+
+fn set_player_ignored(player_name: &str, ignored: bool) {
+    local_overrides::set_player_ignored(player_name, ignored);
+
+    EntityManager::with_all_entities(|entities| {
+        for entity in entities.values_mut() {
+            if entity
+                .creator
+                .as_ref()
+                .is_some_and(|creator| creator.eq_ignore_ascii_case(player_name))
+            {
+                entity.local.ignored = ignored;
+                apply_local_overrides(entity);
+            }
+        }
+    });
+}
+
+/// volume is picked up by the mixer, mute needs telling the browser
+fn apply_local_overrides(entity: &CefEntity) {
+    if let Some(browser) = &entity.browser {
+        let _ignore = entity.update_audio_muted(browser);
+    }
+}
+
+fn set_adblock(player: &PlayerSnapshot, name: Option<String>, enabled: bool) -> Result<()> {
+    let (id, browser_id) = EntityManager::with_entity(
+        name.map_or_else(
+            || player.eye_position.get_entity_id(),
+            |name| name.get_entity_id(),
+        )?,
+        |entity| {
+            let browser = entity.browser.as_ref().chain_err(|| "no browser")?;
+            Ok((entity.id, browser.get_identifier()))
+        },
+    )?;
+
+    adblock::set_enabled(browser_id, enabled);
+    let status = if enabled { "on" } else { "off" };
+    Chat::print(format!("{GOLD}#{id} {SILVER}ad blocking {TEAL}{status}"));
+
+    Ok(())
+}
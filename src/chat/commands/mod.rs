@@ -3,6 +3,7 @@ mod helpers;
 mod local;
 mod options;
 mod screen;
+mod script;
 
 use clap::{Parser, Subcommand};
 use classicube_helpers::async_manager;
@@ -42,55 +43,20 @@ pub enum CefArgsSub {
 
     #[command(flatten)]
     Screen(screen::Commands),
+
+    #[command(flatten)]
+    Script(script::Commands),
 }
 
 #[tracing::instrument(name = "commands::run", fields(player, is_self, show_errors, args = args.join(" ").as_str()))]
 pub async fn run(
     player: PlayerSnapshot,
-    mut args: Vec<String>,
+    args: Vec<String>,
     is_self: bool,
     show_errors: bool,
 ) -> Result<()> {
-    args.insert(0, "cef".to_string());
-
-    debug!("command {:?}", args);
-
-    match CefArgs::try_parse_from(args) {
-        Ok(args) => {
-            debug!(?args, "CefArgs::try_parse_from");
-            let fut = async move {
-                match args.sub {
-                    CefArgsSub::Global(args) => {
-                        global::run(player, args).await?;
-                    }
-                    CefArgsSub::Local(args) => {
-                        if is_self {
-                            local::run(player, args).await?;
-                        }
-                    }
-                    CefArgsSub::Options(args) => {
-                        if is_self {
-                            options::run(args).await?;
-                        }
-                    }
-                    CefArgsSub::Screen(args) => {
-                        screen::run(player, args).await?;
-                    }
-                }
-
-                Ok::<_, Error>(())
-            };
-
-            if args.background {
-                async_manager::spawn_local_on_main_thread(async move {
-                    if let Err(e) = fut.await {
-                        warn!("backgrounded command: {}", e);
-                    }
-                });
-            } else {
-                fut.await?;
-            }
-        }
+    match parse(args) {
+        Ok(args) => run_parsed(player, args, is_self).await?,
 
         Err(e) => {
             warn!("{:#?}", e);
@@ -106,6 +72,77 @@ pub async fn run(
     Ok(())
 }
 
+/// like [`run`] for ourselves, but a command that doesn't parse is an
+/// error instead of only printed, so scripts can stop on it
+pub async fn run_checked(player: PlayerSnapshot, args: Vec<String>) -> Result<()> {
+    match parse(args) {
+        Ok(args) => run_parsed(player, args, true).await,
+
+        // asking for help isn't a mistake
+        Err(e) if !e.use_stderr() => {
+            chat_print_lines(&format!("{e}"));
+            Ok(())
+        }
+
+        Err(e) => {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            Err(message.trim_start_matches("error: ").into())
+        }
+    }
+}
+
+fn parse(mut args: Vec<String>) -> std::result::Result<CefArgs, clap::Error> {
+    args.insert(0, "cef".to_string());
+
+    debug!("command {:?}", args);
+
+    CefArgs::try_parse_from(args)
+}
+
+async fn run_parsed(player: PlayerSnapshot, args: CefArgs, is_self: bool) -> Result<()> {
+    debug!(?args, "CefArgs::try_parse_from");
+    let fut = async move {
+        match args.sub {
+            CefArgsSub::Global(args) => {
+                global::run(player, args).await?;
+            }
+            CefArgsSub::Local(args) => {
+                if is_self {
+                    local::run(player, args).await?;
+                }
+            }
+            CefArgsSub::Options(args) => {
+                if is_self {
+                    options::run(args).await?;
+                }
+            }
+            CefArgsSub::Screen(args) => {
+                screen::run(player, args).await?;
+            }
+            CefArgsSub::Script(args) => {
+                if is_self {
+                    script::run(player, args).await?;
+                }
+            }
+        }
+
+        Ok::<_, Error>(())
+    };
+
+    if args.background {
+        async_manager::spawn_local_on_main_thread(async move {
+            if let Err(e) = fut.await {
+                warn!("backgrounded command: {}", e);
+            }
+        });
+    } else {
+        fut.await?;
+    }
+
+    Ok(())
+}
+
 /// needs to keep same color code from last line
 fn chat_print_lines(s: &str) {
     let s = s.trim();
@@ -149,7 +186,7 @@ fn test_commands() {
 
     async_manager::spawn_local_on_main_thread(async {
         run(
-            unsafe { std::mem::zeroed() },
+            PlayerSnapshot::zeroed(),
             "-- help".split(' ').map(str::to_string).collect(),
             true,
             true,
@@ -158,7 +195,7 @@ fn test_commands() {
         .unwrap();
 
         run(
-            unsafe { std::mem::zeroed() },
+            PlayerSnapshot::zeroed(),
             "-b".split(' ').map(str::to_string).collect(),
             true,
             true,
@@ -167,11 +204,8 @@ fn test_commands() {
         .unwrap();
 
         run(
-            unsafe { std::mem::zeroed() },
-            "help config mute-lose-focus"
-                .split(' ')
-                .map(str::to_string)
-                .collect(),
+            PlayerSnapshot::zeroed(),
+            "help config reset".split(' ').map(str::to_string).collect(),
             true,
             true,
         )
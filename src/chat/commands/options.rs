@@ -1,14 +1,12 @@
 //! commands for setting local config options
 
 use clap::Subcommand;
+use classicube_helpers::color::{GOLD, SILVER, TEAL};
 
 use super::Chat;
 use crate::{
-    chat::hidden_communication::CURRENT_MAP_THEME,
-    entity_manager::EntityManager,
-    error::{Error, Result},
-    options,
-    player::PlayerTrait,
+    error::{Result, bail},
+    options::{self, OptionInfo},
 };
 
 #[derive(Debug, Subcommand)]
@@ -25,174 +23,89 @@ pub enum Commands {
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommands {
-    /// Mute cef when you alt-tab out of the game
-    MuteLoseFocus {
-        #[arg(help(format!("[default: {}]", options::MUTE_LOSE_FOCUS.default())))]
-        enabled: Option<bool>,
-    },
-
-    /// Auto-play map themes
-    AutoplayMapThemes {
-        #[arg(help(format!("[default: {}]", options::AUTOPLAY_MAP_THEMES.default())))]
-        enabled: Option<bool>,
-    },
-
-    /// Show subtitles/closed captions on YouTube videos
-    Subtitles {
-        #[arg(help(format!("[default: {}]", options::SUBTITLES.default())))]
-        enabled: Option<bool>,
-    },
-
-    /// Global volume modifier
-    Volume {
-        #[arg(help(format!("[default: {}]", options::VOLUME.default())))]
-        percent: Option<f32>,
-    },
+    /// Show every option with its value
+    List,
 
-    /// Map theme volume
-    MapThemeVolume {
-        #[arg(help(format!("[default: {}]", options::MAP_THEME_VOLUME.default())))]
-        percent: Option<f32>,
+    /// Set an option back to its default
+    Reset {
+        /// Option name, or "all"
+        name: String,
     },
 
-    /// Changes default frame rate of newly created browsers
-    FrameRate {
-        #[arg(help(format!("[default: {}]", options::FRAME_RATE.default())))]
-        fps: Option<u16>,
-    },
+    /// <option> [value], leave out the value to see it
+    #[command(external_subcommand)]
+    Set(Vec<String>),
 }
 
 pub async fn run(commands: Commands) -> Result<()> {
     let Commands::Config(commands) = commands;
 
     match commands {
-        ConfigCommands::MuteLoseFocus { enabled } => {
-            let value = options::MUTE_LOSE_FOCUS.get()?;
-            if let Some(enabled) = enabled {
-                options::MUTE_LOSE_FOCUS.set(enabled);
-                Chat::print(format!(
-                    "mute-lose-focus: {} -> {}",
-                    value,
-                    options::MUTE_LOSE_FOCUS.get()?
-                ));
-            } else {
-                Chat::print(format!("mute-lose-focus: {value}"));
-            }
-        }
-
-        ConfigCommands::AutoplayMapThemes { enabled } => {
-            let value = options::AUTOPLAY_MAP_THEMES.get()?;
-            if let Some(enabled) = enabled {
-                options::AUTOPLAY_MAP_THEMES.set(enabled);
-                Chat::print(format!(
-                    "autoplay-map-themes: {} -> {}",
-                    value,
-                    options::AUTOPLAY_MAP_THEMES.get()?
-                ));
-
-                if let Some(entity_id) = CURRENT_MAP_THEME.get() {
-                    CURRENT_MAP_THEME.set(None);
-                    let _ignore = EntityManager::remove_entity(entity_id).await;
+        ConfigCommands::List => {
+            for info in options::REGISTRY {
+                let value = info.get()?;
+                let mut details = format!("default {}", info.format_value(&info.default()));
+                if let Some((min, max)) = info.range {
+                    details.push_str(&format!(", {min} to {max}"));
                 }
-            } else {
-                Chat::print(format!("autoplay-map-themes: {value}"));
-            }
-        }
 
-        ConfigCommands::Subtitles { enabled } => {
-            let value = options::SUBTITLES.get()?;
-            if let Some(enabled) = enabled {
-                options::SUBTITLES.set(enabled);
                 Chat::print(format!(
-                    "subtitles: {} -> {}",
-                    value,
-                    options::SUBTITLES.get()?
+                    "{GOLD}{}: {TEAL}{} {SILVER}({details}) {}",
+                    info.name(),
+                    info.format_value(&value),
+                    info.description
                 ));
-            } else {
-                Chat::print(format!("subtitles: {value}"));
             }
         }
 
-        ConfigCommands::Volume { percent } => {
-            let value = options::VOLUME.get()?;
-            if let Some(volume) = percent {
-                options::VOLUME.set(volume);
-                Chat::print(format!("volume: {value} -> {}", options::VOLUME.get()?));
-
-                if let Some(entity_id) = CURRENT_MAP_THEME.get() {
-                    EntityManager::with_entity(entity_id, |entity| {
-                        entity.player.set_volume(entity.browser.as_ref(), volume)?;
-
-                        Ok(())
-                    })?;
-                }
-
-                EntityManager::with_all_entities(|entities| {
-                    for entity in entities.values_mut() {
-                        let volume = entity.player.get_volume();
-
-                        // bad hacks because we only run javascript setVolume
-                        // when screen volume has changed
-                        let _ignore = entity.player.set_volume(entity.browser.as_ref(), 0.0);
-                        let _ignore = entity.player.set_volume(entity.browser.as_ref(), volume);
-                    }
-
-                    Ok::<_, Error>(())
-                })?;
+        ConfigCommands::Reset { name } => {
+            let infos: Vec<&OptionInfo> = if name.eq_ignore_ascii_case("all") {
+                options::REGISTRY.iter().collect()
             } else {
-                Chat::print(format!("volume: {value}"));
+                vec![options::find(&name)?]
+            };
+
+            for info in infos {
+                let old_value = info.reset().await?;
+                print_change(info, &old_value)?;
             }
         }
 
-        ConfigCommands::MapThemeVolume { percent } => {
-            let value = options::MAP_THEME_VOLUME.get()?;
-            if let Some(volume) = percent {
-                options::MAP_THEME_VOLUME.set(volume);
-                Chat::print(format!(
-                    "map-theme-volume: {} -> {}",
-                    value,
-                    options::MAP_THEME_VOLUME.get()?
-                ));
-
-                if let Some(entity_id) = CURRENT_MAP_THEME.get() {
-                    EntityManager::with_entity(entity_id, |entity| {
-                        entity.player.set_volume(entity.browser.as_ref(), volume)?;
-
-                        Ok(())
-                    })?;
+        ConfigCommands::Set(args) => {
+            let [name, value @ ..] = args.as_slice() else {
+                bail!("missing option name, see cef config list");
+            };
+            let info = options::find(name)?;
+
+            match value {
+                [] => {
+                    Chat::print(format!(
+                        "{}: {}",
+                        info.name(),
+                        info.format_value(&info.get()?)
+                    ));
                 }
 
-                EntityManager::with_all_entities(|entities| {
-                    for entity in entities.values_mut() {
-                        let volume = entity.player.get_volume();
-
-                        // bad hacks because we only run javascript setVolume
-                        // when screen volume has changed
-                        let _ignore = entity.player.set_volume(entity.browser.as_ref(), 0.0);
-                        let _ignore = entity.player.set_volume(entity.browser.as_ref(), volume);
-                    }
-
-                    Ok::<_, Error>(())
-                })?;
-            } else {
-                Chat::print(format!("map-theme-volume: {value}"));
-            }
-        }
+                [value] => {
+                    let old_value = info.set(value).await?;
+                    print_change(info, &old_value)?;
+                }
 
-        ConfigCommands::FrameRate { fps } => {
-            let value = options::FRAME_RATE.get()?;
-            if let Some(fps) = fps {
-                options::FRAME_RATE.set(fps);
-                Chat::print(format!(
-                    "frame-rate: {} -> {}",
-                    value,
-                    options::FRAME_RATE.get()?
-                ));
-            } else {
-                Chat::print(format!("frame-rate: {value}"));
+                _ => bail!("too many values for {}", info.name()),
             }
         }
     }
 
     Ok(())
 }
+
+fn print_change(info: &OptionInfo, old_value: &str) -> Result<()> {
+    Chat::print(format!(
+        "{}: {} -> {}",
+        info.name(),
+        info.format_value(old_value),
+        info.format_value(&info.get()?)
+    ));
+
+    Ok(())
+}
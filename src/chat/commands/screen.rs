@@ -11,15 +11,21 @@ use classicube_helpers::{
     async_manager,
     color::{GOLD, SILVER, TEAL},
 };
+use url::Url;
 
 use super::helpers::{get_click_coords, move_entity};
 use crate::{
+    adblock,
     cef::Cef,
     chat::{Chat, PlayerSnapshot},
-    entity_manager::{CefEntity, EntityManager, TargetEntity},
+    entity_manager::{
+        CefEntity, EntityManager, TEXTURE_HEIGHT, TEXTURE_WIDTH, TargetEntity, TextureRect,
+        animation::{self, Animation, Ease, Keyframe, Transform},
+        attachment::{Attachment, DEFAULT_OFFSET},
+    },
     error::{Error, Result, ResultExt, bail, ensure},
-    helpers::format_duration,
-    player::{PlayerBuilder, PlayerTrait, VolumeMode},
+    helpers::{format_duration, get_screen_axes, vec3_to_vector3},
+    player::{Fade, PlayerBuilder, PlayerTrait, VolumeMode},
 };
 
 #[derive(Debug, Subcommand)]
@@ -32,6 +38,40 @@ pub enum Commands {
         name: Option<String>,
     },
 
+    /// Make the screen follow a player
+    ///
+    /// The offset is right, up and forward from their feet and turns with
+    /// them, so a negative forward puts the screen on their back
+    #[command(alias("follow"))]
+    Attach {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// Player to follow
+        player: String,
+
+        #[arg(
+            long,
+            num_args(3),
+            value_names(["X", "Y", "Z"]),
+            allow_hyphen_values(true)
+        )]
+        offset: Option<Vec<f32>>,
+
+        /// Always face the camera, like a name tag
+        #[arg(long)]
+        billboard: bool,
+    },
+
+    /// Stop following a player after "cef attach"
+    #[command(alias("unfollow"))]
+    Detach {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
     /// Play or queue something
     #[command(aliases(["play", "load"]))]
     Queue {
@@ -79,6 +119,8 @@ pub enum Commands {
     },
 
     /// Remove screen
+    ///
+    /// Closing any tile of a wall removes the whole wall.
     #[command(aliases(["remove", "clear"]))]
     Close {
         /// Name of screen
@@ -116,6 +158,20 @@ pub enum Commands {
         name: Option<String>,
     },
 
+    /// Go back a page, like a browser's back button
+    Back {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Go forward a page after "cef back"
+    Forward {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
     /// Change angles of screen
     #[command(alias("angle"))]
     Angles {
@@ -166,6 +222,33 @@ pub enum Commands {
         height: u16,
     },
 
+    /// Show another screen's video here too
+    ///
+    /// Mirrors have no sound of their own and are removed with the source
+    /// screen. Control playback on the source screen.
+    Mirror {
+        /// Name of the screen to mirror
+        source: String,
+
+        /// Name of the new mirror
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+
+    /// Tile a screen's browser across a grid of screens
+    ///
+    /// The screen becomes the top-left tile and the wall grows right and up
+    /// from where it stands. Use 1 1 to go back to a single screen.
+    Wall {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        columns: u8,
+
+        rows: u8,
+    },
+
     /// Set audio volume of a screen
     ///
     /// If --global is specified, distance acts as volume
@@ -182,6 +265,11 @@ pub enum Commands {
         #[arg(long, short)]
         panning: bool,
 
+        /// Use HRTF spatial volume, which includes height and which way
+        /// the screen faces
+        #[arg(long, short, conflicts_with_all(["global", "panning"]))]
+        spatial: bool,
+
         distance: f32,
 
         #[arg(conflicts_with("global"))]
@@ -230,6 +318,60 @@ pub enum Commands {
         scale: Option<f32>,
     },
 
+    /// Move smoothly to coords x,y,z and optional yaw,pitch,scale
+    #[command(alias("glide"))]
+    MoveTo {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        #[arg(allow_hyphen_values(true))]
+        x: c_float,
+
+        #[arg(allow_hyphen_values(true))]
+        y: c_float,
+
+        #[arg(allow_hyphen_values(true))]
+        z: c_float,
+
+        #[arg(allow_hyphen_values(true))]
+        yaw: Option<f32>,
+
+        #[arg(requires("yaw"), allow_hyphen_values(true))]
+        pitch: Option<f32>,
+
+        #[arg(requires("pitch"), allow_hyphen_values(true))]
+        scale: Option<f32>,
+
+        /// How long the move takes
+        #[arg(long, short, default_value_t = 1.0)]
+        seconds: f32,
+
+        #[arg(long, short, value_enum, default_value_t = Ease::InOut)]
+        ease: Ease,
+    },
+
+    /// Move along keyframes, see "cef path play"
+    ///
+    /// Each keyframe is x,y,z,yaw,pitch,scale,seconds,ease, separated by
+    /// spaces or ;
+    Animate {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// Start again from the first keyframe after the last
+        #[arg(long, short)]
+        r#loop: bool,
+
+        /// Stop moving where it is now
+        #[arg(long, conflicts_with_all(["keyframes", "loop"]))]
+        stop: bool,
+
+        #[arg(required_unless_present("stop"), allow_hyphen_values(true))]
+        keyframes: Vec<String>,
+    },
+
     /// Show what's playing
     Info {
         /// Name of screen
@@ -261,6 +403,17 @@ pub enum Commands {
         speed: f32,
     },
 
+    /// Show SRT or WebVTT subtitles on a video screen
+    #[command(alias("subs"))]
+    Subtitles {
+        /// Name of screen
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// Subtitle file url, or "off"
+        url: String,
+    },
+
     /// Fade volume of screen
     #[command(override_usage("cef fade [OPTIONS] [FROM] <TO> <SECONDS>"))]
     Fade {
@@ -296,6 +449,48 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             )?;
         }
 
+        Commands::Attach {
+            name,
+            player: target,
+            offset,
+            billboard,
+        } => {
+            let offset = offset.map_or(DEFAULT_OFFSET, |offset| (offset[0], offset[1], offset[2]));
+            let mut attachment = Attachment::new(target, offset, billboard);
+            ensure!(
+                attachment.find_target().is_some(),
+                "no player named {:?}",
+                attachment.target
+            );
+
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                move |entity| {
+                    entity.animation = None;
+                    entity.attachment = Some(attachment);
+
+                    Ok(())
+                },
+            )?;
+        }
+
+        Commands::Detach { name } => {
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    ensure!(entity.attachment.take().is_some(), "screen isn't attached");
+
+                    Ok(())
+                },
+            )?;
+        }
+
         Commands::Queue {
             name,
             skip,
@@ -402,7 +597,15 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                     || player.eye_position.get_entity_id(),
                     |name| name.get_entity_id(),
                 )?,
-                |entity| Ok(entity.id),
+                |entity| {
+                    // closing one tile would leave a hole in the wall, so
+                    // close the wall's screen along with all of its tiles
+                    if entity.texture_rect == TextureRect::FULL {
+                        Ok(entity.id)
+                    } else {
+                        Ok(entity.texture_source.unwrap_or(entity.id))
+                    }
+                },
             )?;
 
             EntityManager::remove_entity(entity_id).await?;
@@ -452,6 +655,32 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             browser.reload()?;
         }
 
+        Commands::Back { name } => {
+            let entity_id = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| Ok(entity.id),
+            )?;
+            let browser = EntityManager::get_browser_by_entity_id(entity_id)?;
+            ensure!(browser.can_go_back(), "nothing to go back to");
+            browser.go_back()?;
+        }
+
+        Commands::Forward { name } => {
+            let entity_id = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| Ok(entity.id),
+            )?;
+            let browser = EntityManager::get_browser_by_entity_id(entity_id)?;
+            ensure!(browser.can_go_forward(), "nothing to go forward to");
+            browser.go_forward()?;
+        }
+
         Commands::Angles { name, yaw, pitch } => {
             EntityManager::with_entity(
                 name.map_or_else(
@@ -485,22 +714,30 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                     browser.send_click(x, y)?;
                 }
             } else {
-                let (entity_id, entity_pos, [entity_pitch, entity_yaw], entity_scale, entity_size) =
-                    EntityManager::with_entity(
-                        name.map_or_else(
-                            || player.eye_position.get_entity_id(),
-                            |name| name.get_entity_id(),
-                        )?,
-                        |entity| {
-                            Ok((
-                                entity.id,
-                                entity.entity.Position,
-                                [entity.entity.RotX, entity.entity.RotY],
-                                entity.entity.ModelScale,
-                                entity.get_size(),
-                            ))
-                        },
-                    )?;
+                let (
+                    entity_id,
+                    entity_pos,
+                    [entity_pitch, entity_yaw],
+                    entity_scale,
+                    entity_size,
+                    texture_rect,
+                ) = EntityManager::with_entity(
+                    name.map_or_else(
+                        // the tile or mirror we're looking at, not its source
+                        || EntityManager::with_closest(player.eye_position, |entity| Ok(entity.id)),
+                        |name| name.get_entity_id(),
+                    )?,
+                    |entity| {
+                        Ok((
+                            entity.id,
+                            entity.entity.Position,
+                            [entity.entity.RotX, entity.entity.RotY],
+                            entity.entity.ModelScale,
+                            entity.get_size(),
+                            entity.texture_rect,
+                        ))
+                    },
+                )?;
 
                 let browser = EntityManager::get_browser_by_entity_id(entity_id)?;
                 let (browser_width, browser_height) = Cef::get_browser_size(&browser);
@@ -517,6 +754,13 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                     browser_width as u32,
                     browser_height as u32,
                 )? {
+                    // wall tiles only show part of the browser
+                    let (browser_width, browser_height) =
+                        (f32::from(browser_width), f32::from(browser_height));
+                    let x =
+                        (texture_rect.x + x / browser_width * texture_rect.width) * browser_width;
+                    let y = (texture_rect.y + y / browser_height * texture_rect.height)
+                        * browser_height;
                     browser.send_click(x as _, y as _)?;
                 }
             }
@@ -554,10 +798,146 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             Cef::resize_browser(&browser, width, height)?;
         }
 
+        Commands::Mirror { source, name } => {
+            // mirroring a mirror or wall tile means mirroring its source
+            let source_id = EntityManager::with_entity(source, |entity| {
+                Ok(entity.texture_source.unwrap_or(entity.id))
+            })?;
+
+            let entity_id = EntityManager::create_linked(source_id, name)?;
+            EntityManager::with_entity(entity_id, |entity| {
+                move_entity(entity, &player);
+                Ok(())
+            })?;
+        }
+
+        Commands::Wall {
+            name,
+            columns,
+            rows,
+        } => {
+            ensure!(
+                columns > 0 && rows > 0,
+                "columns and rows must be at least 1"
+            );
+
+            // looking at a tile means the wall's screen
+            let source_id = EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| Ok(entity.texture_source.unwrap_or(entity.id)),
+            )?;
+
+            // mirrors show the whole browser and stay
+            let old_tile_ids = EntityManager::with_all_entities(|entities| {
+                entities
+                    .values()
+                    .filter(|entity| {
+                        entity.texture_source == Some(source_id)
+                            && entity.texture_rect != TextureRect::FULL
+                    })
+                    .map(|entity| entity.id)
+                    .collect::<Vec<_>>()
+            });
+            for id in old_tile_ids {
+                EntityManager::remove_entity(id).await?;
+            }
+
+            let browser = EntityManager::get_browser_by_entity_id(source_id)?;
+            let (browser_width, browser_height) = Cef::get_browser_size(&browser);
+
+            let (base_name, origin, right, up, tile_width, tile_height, tile_resolution) =
+                EntityManager::with_entity(source_id, |entity| {
+                    let (right, up) = get_screen_axes(entity.entity.RotX, entity.entity.RotY);
+                    let (width, height) = entity.get_size();
+                    let tile_width = entity.entity.ModelScale.x * width as f32;
+                    let tile_height = entity.entity.ModelScale.y * height as f32;
+
+                    // undo the lift from a previous wall
+                    let old_rect = entity.texture_rect;
+                    let old_rows = (1.0 / old_rect.height).round();
+                    let origin = vec3_to_vector3(&entity.entity.Position)
+                        - up * tile_height * (old_rows - 1.0);
+
+                    let tile_resolution = (
+                        browser_width as f32 * old_rect.width,
+                        browser_height as f32 * old_rect.height,
+                    );
+
+                    Ok((
+                        entity
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| format!("wall{source_id}")),
+                        origin,
+                        right,
+                        up,
+                        tile_width,
+                        tile_height,
+                        tile_resolution,
+                    ))
+                })?;
+
+            let resolution_width = u16::try_from((tile_resolution.0 * f32::from(columns)) as i32)
+                .map_or(TEXTURE_WIDTH, |width| width.min(TEXTURE_WIDTH));
+            let resolution_height = u16::try_from((tile_resolution.1 * f32::from(rows)) as i32)
+                .map_or(TEXTURE_HEIGHT, |height| height.min(TEXTURE_HEIGHT));
+
+            let mut tile_ids = Vec::new();
+            for row in 0..rows {
+                for column in 0..columns {
+                    let tile_id = if row == 0 && column == 0 {
+                        source_id
+                    } else {
+                        EntityManager::create_linked(
+                            source_id,
+                            Some(format!("{base_name}-{column}-{row}")),
+                        )?
+                    };
+
+                    EntityManager::with_entity(tile_id, |entity| {
+                        entity.texture_rect = TextureRect {
+                            x: f32::from(column) / f32::from(columns),
+                            y: f32::from(row) / f32::from(rows),
+                            width: 1.0 / f32::from(columns),
+                            height: 1.0 / f32::from(rows),
+                        };
+
+                        let position = origin
+                            + right * tile_width * f32::from(column)
+                            + up * tile_height * f32::from(rows - 1 - row);
+                        entity
+                            .entity
+                            .Position
+                            .set(position.x, position.y, position.z);
+
+                        // the next paint would also do this, but only if the
+                        // resolution actually changed
+                        entity.update_uv(resolution_width as _, resolution_height as _);
+
+                        Ok(())
+                    })?;
+
+                    tile_ids.push(tile_id);
+                }
+            }
+
+            Cef::resize_browser(&browser, resolution_width, resolution_height)?;
+
+            if tile_ids.len() > 1 {
+                Chat::print(format!(
+                    "{SILVER}wall {columns}x{rows}, tiles are named {base_name}-<column>-<row>"
+                ));
+            }
+        }
+
         Commands::Volume {
             name,
             global,
             panning,
+            spatial,
             distance,
             multiplier,
         } => {
@@ -577,7 +957,17 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                     } else {
                         let multiplier = multiplier.unwrap_or(1.0);
 
-                        if panning {
+                        if spatial {
+                            entity.player.set_volume_mode(
+                                entity.browser.as_ref(),
+                                VolumeMode::Spatial {
+                                    multiplier,
+                                    distance,
+                                    position: [0.0, 0.0, 0.0],
+                                    orientation: [0.0, 0.0, 0.0],
+                                },
+                            )?;
+                        } else if panning {
                             entity.player.set_volume_mode(
                                 entity.browser.as_ref(),
                                 VolumeMode::Panning {
@@ -714,10 +1104,83 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             )?;
         }
 
+        Commands::MoveTo {
+            name,
+            x,
+            y,
+            z,
+            yaw,
+            pitch,
+            scale,
+            seconds,
+            ease,
+        } => {
+            ensure!(
+                seconds.is_finite() && seconds >= 0.0,
+                "seconds can't be negative"
+            );
+
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    let start = Transform::from_entity(entity);
+                    let keyframe = Keyframe {
+                        transform: Transform {
+                            position: (x, y, z),
+                            yaw: yaw.unwrap_or(start.yaw),
+                            pitch: pitch.unwrap_or(start.pitch),
+                            scale: scale.unwrap_or(start.scale),
+                        },
+                        seconds,
+                        ease,
+                    };
+
+                    entity.attachment = None;
+                    entity.animation = Some(Animation::new(start, vec![keyframe], false));
+
+                    Ok(())
+                },
+            )?;
+        }
+
+        Commands::Animate {
+            name,
+            r#loop,
+            stop,
+            keyframes,
+        } => {
+            let keyframes = animation::parse_keyframes(&keyframes)?;
+
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                move |entity| {
+                    if stop {
+                        ensure!(entity.animation.take().is_some(), "screen isn't moving");
+                    } else {
+                        let start = Transform::from_entity(entity);
+                        entity.attachment = None;
+                        entity.animation = Some(Animation::new(start, keyframes, r#loop));
+                    }
+
+                    Ok(())
+                },
+            )?;
+        }
+
         Commands::Info { name: _ } => {
             // let's have it print for everyone
             EntityManager::with_all_entities(|entities| {
-                for entity in entities.values() {
+                // wall tiles and mirrors show their source's player
+                for entity in entities
+                    .values()
+                    .filter(|entity| entity.texture_source.is_none())
+                {
                     let url = entity.player.get_url();
                     let title = entity.player.get_title();
 
@@ -732,6 +1195,22 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
 
                     Chat::print(url);
 
+                    if let Some(attachment) = &entity.attachment {
+                        Chat::print(format!("{TEAL}Following {SILVER}{}", attachment.target));
+                    }
+
+                    if let Some(subtitles) = entity.player.get_subtitles() {
+                        Chat::print(format!("{TEAL}Subtitles {SILVER}{subtitles}"));
+                    }
+
+                    // only what this client blocked
+                    let blocked = entity.browser.as_ref().map_or(0, |browser| {
+                        adblock::get_blocked_count(browser.get_identifier())
+                    });
+                    if blocked > 0 {
+                        Chat::print(format!("{TEAL}Blocked {GOLD}{blocked} {SILVER}ad requests"));
+                    }
+
                     if !entity.queue.is_empty() {
                         let len = entity.queue.len();
                         Chat::print(format!("{GOLD}{len} {TEAL}items in queue:"));
@@ -796,6 +1275,25 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
             )?;
         }
 
+        Commands::Subtitles { name, url } => {
+            let url = if url == "off" {
+                None
+            } else {
+                Some(Url::parse(&url)?.to_string())
+            };
+
+            EntityManager::with_entity(
+                name.map_or_else(
+                    || player.eye_position.get_entity_id(),
+                    |name| name.get_entity_id(),
+                )?,
+                |entity| {
+                    entity.player.set_subtitles(entity.browser.as_ref(), url)?;
+                    Ok(())
+                },
+            )?;
+        }
+
         Commands::Fade {
             name,
             from_or_to,
@@ -837,7 +1335,8 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                     Ok(match entity.player.get_volume_mode() {
                         VolumeMode::Global => entity.player.get_volume(),
                         VolumeMode::Distance { multiplier, .. }
-                        | VolumeMode::Panning { multiplier, .. } => multiplier,
+                        | VolumeMode::Panning { multiplier, .. }
+                        | VolumeMode::Spatial { multiplier, .. } => multiplier,
                     })
                 })?
             };
@@ -870,24 +1369,34 @@ pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
                                 pan,
                             },
                         ),
+                        VolumeMode::Spatial {
+                            multiplier: _,
+                            distance,
+                            position,
+                            orientation,
+                        } => entity.player.set_volume_mode(
+                            entity.browser.as_ref(),
+                            VolumeMode::Spatial {
+                                multiplier: volume,
+                                distance,
+                                position,
+                                orientation,
+                            },
+                        ),
                     }
                 })
             };
 
             set_volume(from)?;
 
-            let start_time = Instant::now();
+            let fade = Fade::new(from, to, seconds);
             loop {
                 let now = Instant::now();
-                let secs_from_start = (now - start_time).as_secs_f32();
-
-                let percent = secs_from_start / seconds;
-                if percent > 1.0 {
+                if fade.is_finished_at(now) {
                     break;
                 }
 
-                let volume = from + (to - from) * percent;
-                set_volume(volume)?;
+                set_volume(fade.get_at(now))?;
 
                 async_manager::sleep(Duration::from_millis(32)).await;
             }
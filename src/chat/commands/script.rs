@@ -0,0 +1,517 @@
+//! local scripts of cef commands, read from `cef/scripts`
+//!
+//! ```text
+//! # comments start with #
+//! set NAME intro
+//! create -n $NAME https://www.youtube.com/watch?v=dQw4w9WgXcQ
+//! wait load $NAME
+//! at -n $NAME 10 64 10 90 0
+//! wait 5s
+//! on-error continue
+//! volume -n $NAME 0.5
+//!
+//! macro lights
+//!   angles -n $1 0 0
+//!   wait 1s
+//!   angles -n $1 90 0
+//! end
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    env, fs,
+    time::Duration,
+};
+
+use async_recursion::async_recursion;
+use clap::Subcommand;
+use classicube_helpers::{async_manager, color::SILVER};
+use tracing::debug;
+
+use super::Chat;
+use crate::{
+    chat::PlayerSnapshot,
+    entity_manager::{EntityManager, TargetEntity},
+    error::{Error, Result, ResultExt, bail},
+    helpers::parse_duration,
+};
+
+/// stops `macro a` calling `macro a` forever
+const MAX_DEPTH: usize = 8;
+
+thread_local!(
+    static MACROS: RefCell<HashMap<String, Vec<Statement>>> = RefCell::default();
+);
+
+thread_local!(
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+);
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Run a script of cef commands from cef/scripts
+    Run {
+        /// File name inside cef/scripts
+        file: String,
+
+        /// Values for $1, $2, ...
+        #[arg(allow_hyphen_values(true))]
+        args: Vec<String>,
+    },
+
+    /// Run a macro defined by a script, or list macros
+    #[command(alias("macros"))]
+    Macro {
+        name: Option<String>,
+
+        /// Values for $1, $2, ...
+        #[arg(allow_hyphen_values(true))]
+        args: Vec<String>,
+    },
+}
+
+#[async_recursion(?Send)]
+pub async fn run(player: PlayerSnapshot, commands: Commands) -> Result<()> {
+    match commands {
+        Commands::Run { file, args } => {
+            let script = load(&file)?;
+
+            MACROS.with(|cell| {
+                let macros = &mut *cell.borrow_mut();
+                macros.extend(script.macros);
+            });
+
+            with_depth(execute(&player, &script.statements, &args)).await?;
+        }
+
+        Commands::Macro {
+            name: Some(name),
+            args,
+        } => {
+            let statements = MACROS
+                .with(|cell| cell.borrow().get(&name).cloned())
+                .chain_err(|| format!("no macro named {name:?}"))?;
+
+            with_depth(execute(&player, &statements, &args)).await?;
+        }
+
+        Commands::Macro { name: None, .. } => {
+            let mut names = MACROS.with(|cell| cell.borrow().keys().cloned().collect::<Vec<_>>());
+            names.sort();
+
+            if names.is_empty() {
+                Chat::print(format!("{SILVER}no macros loaded, use cef run <file>"));
+            } else {
+                Chat::print(format!("{SILVER}macros: {}", names.join(", ")));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn with_depth<F: Future<Output = Result<()>>>(f: F) -> Result<()> {
+    struct DepthGuard(usize);
+    impl Drop for DepthGuard {
+        fn drop(&mut self) {
+            DEPTH.set(self.0);
+        }
+    }
+
+    let depth = DEPTH.get();
+    if depth >= MAX_DEPTH {
+        bail!("scripts nested too deeply");
+    }
+
+    DEPTH.set(depth + 1);
+    let _guard = DepthGuard(depth);
+    f.await
+}
+
+fn load(file: &str) -> Result<Script> {
+    if file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.') {
+        bail!("invalid script name {:?}", file);
+    }
+
+    let path = env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("scripts")
+        .join(file);
+
+    let source =
+        fs::read_to_string(&path).chain_err(|| format!("couldn't read {}", path.display()))?;
+
+    Script::parse(&source)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorMode {
+    Stop,
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Set {
+        line: usize,
+        name: String,
+        value: String,
+    },
+    Wait {
+        line: usize,
+        duration: Duration,
+    },
+    WaitLoad {
+        line: usize,
+        name: Option<String>,
+    },
+    OnError {
+        line: usize,
+        mode: ErrorMode,
+    },
+    Command {
+        line: usize,
+        text: String,
+    },
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Script {
+    statements: Vec<Statement>,
+    macros: HashMap<String, Vec<Statement>>,
+}
+
+impl Script {
+    fn parse(source: &str) -> Result<Self> {
+        let mut script = Self::default();
+        let mut current_macro: Option<(String, Vec<Statement>)> = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(keyword, rest)| (keyword, rest.trim()));
+
+            match keyword {
+                "macro" => {
+                    if current_macro.is_some() {
+                        bail!("line {}: macros can't be nested", line_number);
+                    }
+                    if rest.is_empty() || rest.contains(char::is_whitespace) {
+                        bail!("line {}: expected macro <name>", line_number);
+                    }
+                    current_macro = Some((rest.to_string(), Vec::new()));
+                    continue;
+                }
+
+                "end" => {
+                    let (name, statements) = current_macro
+                        .take()
+                        .chain_err(|| format!("line {line_number}: end without macro"))?;
+                    script.macros.insert(name, statements);
+                    continue;
+                }
+
+                _ => {}
+            }
+
+            let statement = Statement::parse(line_number, keyword, rest, line)?;
+            if let Some((_, statements)) = current_macro.as_mut() {
+                statements.push(statement);
+            } else {
+                script.statements.push(statement);
+            }
+        }
+
+        if let Some((name, _)) = current_macro {
+            bail!("macro {:?} is missing end", name);
+        }
+
+        Ok(script)
+    }
+}
+
+impl Statement {
+    fn parse(line: usize, keyword: &str, rest: &str, whole: &str) -> Result<Self> {
+        Ok(match keyword {
+            "set" => {
+                let (name, value) = rest
+                    .split_once(char::is_whitespace)
+                    .map_or((rest, ""), |(name, value)| (name, value.trim()));
+                if name.is_empty() {
+                    bail!("line {}: expected set <name> <value>", line);
+                }
+
+                Self::Set {
+                    line,
+                    name: name.to_string(),
+                    value: value.to_string(),
+                }
+            }
+
+            "wait" => {
+                let (what, name) = rest
+                    .split_once(char::is_whitespace)
+                    .map_or((rest, ""), |(what, name)| (what, name.trim()));
+
+                if what == "load" {
+                    Self::WaitLoad {
+                        line,
+                        name: (!name.is_empty()).then(|| name.to_string()),
+                    }
+                } else {
+                    let duration = parse_duration(rest)
+                        .chain_err(|| format!("line {line}: invalid duration {rest:?}"))?;
+                    Self::Wait { line, duration }
+                }
+            }
+
+            "on-error" => {
+                let mode = match rest {
+                    "stop" => ErrorMode::Stop,
+                    "continue" => ErrorMode::Continue,
+                    _ => bail!("line {}: expected on-error stop|continue", line),
+                };
+                Self::OnError { line, mode }
+            }
+
+            _ => {
+                // a leading "cef" is allowed so lines can be pasted from chat
+                let text = whole.strip_prefix("cef ").unwrap_or(whole).trim();
+                Self::Command {
+                    line,
+                    text: text.to_string(),
+                }
+            }
+        })
+    }
+
+    fn line(&self) -> usize {
+        match self {
+            Self::Set { line, .. }
+            | Self::Wait { line, .. }
+            | Self::WaitLoad { line, .. }
+            | Self::OnError { line, .. }
+            | Self::Command { line, .. } => *line,
+        }
+    }
+}
+
+async fn execute(player: &PlayerSnapshot, statements: &[Statement], args: &[String]) -> Result<()> {
+    let mut variables = HashMap::new();
+    let mut error_mode = ErrorMode::Stop;
+
+    for statement in statements {
+        debug!(?statement, "script");
+
+        let result = execute_statement(player, statement, &mut variables, &mut error_mode, args)
+            .await
+            .map_err(|e| Error::from(format!("line {}: {}", statement.line(), e)));
+
+        if let Err(e) = result {
+            match error_mode {
+                ErrorMode::Stop => return Err(e),
+                ErrorMode::Continue => {
+                    Chat::print(format!("{SILVER}{e}"));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn execute_statement(
+    player: &PlayerSnapshot,
+    statement: &Statement,
+    variables: &mut HashMap<String, String>,
+    error_mode: &mut ErrorMode,
+    args: &[String],
+) -> Result<()> {
+    match statement {
+        Statement::Set { name, value, .. } => {
+            let value = substitute(value, variables, args);
+            variables.insert(name.clone(), value);
+        }
+
+        Statement::Wait { duration, .. } => {
+            async_manager::sleep(*duration).await;
+        }
+
+        Statement::WaitLoad { name, .. } => {
+            let page_load = match name {
+                Some(name) => {
+                    let name = substitute(name, variables, args);
+                    EntityManager::with_entity(name.get_entity_id()?, |entity| {
+                        Ok(entity.wait_for_page_load())
+                    })?
+                }
+                None => {
+                    EntityManager::with_entity(player.eye_position.get_entity_id()?, |entity| {
+                        Ok(entity.wait_for_page_load())
+                    })?
+                }
+            };
+
+            if page_load.await.is_err() {
+                bail!("wait_for_page_load cancelled");
+            }
+        }
+
+        Statement::OnError { mode, .. } => {
+            *error_mode = *mode;
+        }
+
+        Statement::Command { text, .. } => {
+            let text = substitute(text, variables, args);
+            let args = text.split_whitespace().map(str::to_string).collect();
+            super::run_checked(player.clone(), args).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// replaces `$NAME`, `${NAME}` and `$1` with their values
+fn substitute(text: &str, variables: &HashMap<String, String>, args: &[String]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+
+        let (name, end) = if let Some(inner) = text[i + 1..].strip_prefix('{') {
+            match inner.find('}') {
+                Some(len) => (&inner[..len], i + 1 + 1 + len + 1),
+                None => {
+                    output.push(c);
+                    continue;
+                }
+            }
+        } else {
+            let len = text[i + 1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(text.len() - i - 1);
+            (&text[i + 1..i + 1 + len], i + 1 + len)
+        };
+
+        if name.is_empty() {
+            output.push(c);
+            continue;
+        }
+
+        let value = if let Ok(n) = name.parse::<usize>() {
+            n.checked_sub(1).and_then(|n| args.get(n)).cloned()
+        } else {
+            variables.get(name).cloned()
+        };
+        output.push_str(&value.unwrap_or_default());
+
+        while chars.peek().is_some_and(|(j, _)| *j < end) {
+            chars.next();
+        }
+    }
+
+    output
+}
+
+#[test]
+fn test_parse() {
+    let script = Script::parse(
+        "# intro\n\
+         set NAME intro\n\
+         cef create -n $NAME https://example.com\n\
+         wait load $NAME\n\
+         \n\
+         wait 1m30s\n\
+         on-error continue\n\
+         macro spin\n\
+         \x20 angles -n $1 90 0\n\
+         \x20 wait 500ms\n\
+         end\n\
+         volume 0.5\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        script.statements,
+        vec![
+            Statement::Set {
+                line: 2,
+                name: "NAME".to_string(),
+                value: "intro".to_string()
+            },
+            Statement::Command {
+                line: 3,
+                text: "create -n $NAME https://example.com".to_string()
+            },
+            Statement::WaitLoad {
+                line: 4,
+                name: Some("$NAME".to_string())
+            },
+            Statement::Wait {
+                line: 6,
+                duration: Duration::from_secs(90)
+            },
+            Statement::OnError {
+                line: 7,
+                mode: ErrorMode::Continue
+            },
+            Statement::Command {
+                line: 12,
+                text: "volume 0.5".to_string()
+            },
+        ]
+    );
+
+    assert_eq!(
+        script.macros.get("spin"),
+        Some(&vec![
+            Statement::Command {
+                line: 9,
+                text: "angles -n $1 90 0".to_string()
+            },
+            Statement::Wait {
+                line: 10,
+                duration: Duration::from_millis(500)
+            },
+        ])
+    );
+
+    assert!(Script::parse("wait forever").is_err());
+    assert!(Script::parse("on-error maybe").is_err());
+    assert!(Script::parse("macro a\nmacro b\nend\nend").is_err());
+    assert!(Script::parse("macro a\nplay x").is_err());
+    assert!(Script::parse("end").is_err());
+}
+
+#[test]
+fn test_substitute() {
+    let variables = HashMap::from([
+        ("NAME".to_string(), "intro".to_string()),
+        ("x".to_string(), "1".to_string()),
+    ]);
+    let args = vec!["first".to_string(), "second".to_string()];
+
+    for (a, b) in &[
+        ("at -n $NAME 1 2 3", "at -n intro 1 2 3"),
+        ("${NAME}2", "intro2"),
+        ("$x.5", "1.5"),
+        ("$1 $2 $3", "first second "),
+        ("$MISSING!", "!"),
+        ("cost $ 5", "cost $ 5"),
+        ("${unclosed", "${unclosed"),
+    ] {
+        assert_eq!(&substitute(a, &variables, &args), b, "{a:?}");
+    }
+}
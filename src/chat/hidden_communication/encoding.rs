@@ -2,12 +2,15 @@ use std::{collections::VecDeque, io::Cursor, time::Duration};
 
 use base64::{Engine, prelude::BASE64_STANDARD};
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::{
-    cef::Cef,
-    entity_manager::{EntityBuilder, EntityManager},
-    error::Result,
+    cef::{Cef, Profile},
+    entity_manager::{
+        CefEntity, EntityBuilder, EntityManager, TextureRect, animation::Animation,
+        attachment::Attachment,
+    },
+    error::{Result, ResultExt, ensure},
     player::{Player, PlayerTrait},
 };
 
@@ -23,6 +26,55 @@ pub struct LightEntity {
     rotation: (f32, f32),
     position: (f32, f32, f32),
     background_color: u32,
+    attachment: Option<Attachment>,
+    animation: Option<Animation>,
+    /// real name of whoever made it, None if it was the sender
+    creator: Option<String>,
+    /// which part of the browser we show, changed by `cef wall`
+    texture_rect: TextureRect,
+    /// wall tiles and mirrors showing this screen
+    links: Vec<LightLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LightLink {
+    name: Option<String>,
+    size: (u16, u16),
+    scale: f32,
+    rotation: (f32, f32),
+    position: (f32, f32, f32),
+    texture_rect: TextureRect,
+}
+
+impl LightLink {
+    fn from_entity(entity: &CefEntity) -> Self {
+        let e = &entity.entity;
+
+        Self {
+            name: entity.name.clone(),
+            size: entity.get_size(),
+            scale: entity.get_scale(),
+            rotation: (e.RotX, e.RotY),
+            position: (e.Position.x, e.Position.y, e.Position.z),
+            texture_rect: entity.texture_rect,
+        }
+    }
+
+    fn create(self, source_id: usize) -> Result<()> {
+        let entity_id = EntityManager::create_linked(source_id, self.name)?;
+
+        EntityManager::with_entity(entity_id, |entity| {
+            let (x, y, z) = self.position;
+            entity.entity.Position.set(x, y, z);
+            entity.entity.RotX = self.rotation.0;
+            entity.entity.RotY = self.rotation.1;
+            entity.set_scale(self.scale);
+            entity.set_size(self.size.0, self.size.1);
+            // uv's follow on the source's next paint
+            entity.texture_rect = self.texture_rect;
+            Ok(())
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,9 +88,20 @@ const BINCODE_CONFIG: bincode::config::Configuration<
     bincode::config::NoLimit,
 > = bincode::config::legacy();
 
+/// before the bincode data, older plugins sent none so their messages start
+/// with the entity count instead
+const MAGIC: &[u8] = b"cef";
+
+/// bincode has no field names or defaults, so bump this whenever
+/// `LightEntity` or anything inside it changes shape, like a new field or
+/// enum variant
+const VERSION: u8 = 7;
+
 /// to base64
 pub fn encode(message: &Message) -> Result<String> {
-    let data = bincode::serde::encode_to_vec(message, BINCODE_CONFIG)?;
+    let mut data = MAGIC.to_vec();
+    data.push(VERSION);
+    bincode::serde::encode_into_std_write(message, &mut data, BINCODE_CONFIG)?;
     let compressed_data = zstd::encode_all(Cursor::new(&data), 0)?;
 
     Ok(BASE64_STANDARD.encode(compressed_data))
@@ -48,12 +111,25 @@ pub fn encode(message: &Message) -> Result<String> {
 pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Message> {
     let compressed_data = BASE64_STANDARD.decode(input)?;
     let data = zstd::decode_all(Cursor::new(&compressed_data))?;
-    let (message, _) = bincode::serde::decode_from_slice(&data, BINCODE_CONFIG)?;
+
+    let data = data
+        .strip_prefix(MAGIC)
+        .chain_err(|| "sync is from an older cef plugin, one of you needs to update")?;
+    let (&version, data) = data.split_first().chain_err(|| "sync message is empty")?;
+    ensure!(
+        version == VERSION,
+        "sync is from cef plugin sync version {}, we have {}, one of you needs to update",
+        version,
+        VERSION
+    );
+
+    let (message, _) = bincode::serde::decode_from_slice(data, BINCODE_CONFIG)?;
     Ok(message)
 }
 
 pub fn create_message() -> Message {
     let light_entities: Vec<_> = EntityManager::with_all_entities(|entities| {
+        let entities = &*entities;
         entities
             .iter()
             .filter(|(_id, entity)| entity.should_send)
@@ -75,6 +151,15 @@ pub fn create_message() -> Message {
                 let rotation = (e.RotX, e.RotY);
                 let position = (e.Position.x, e.Position.y, e.Position.z);
                 let background_color = entity.background_color;
+                let attachment = entity.attachment.clone();
+                let animation = entity.animation.clone();
+                let creator = entity.creator.clone();
+                let texture_rect = entity.texture_rect;
+                let links = entities
+                    .values()
+                    .filter(|link| link.texture_source == Some(entity.id))
+                    .map(LightLink::from_entity)
+                    .collect();
 
                 LightEntity {
                     player,
@@ -86,6 +171,11 @@ pub fn create_message() -> Message {
                     rotation,
                     position,
                     background_color,
+                    attachment,
+                    animation,
+                    creator,
+                    texture_rect,
+                    links,
                 }
             })
             .collect()
@@ -96,7 +186,8 @@ pub fn create_message() -> Message {
     }
 }
 
-pub async fn received_message(mut message: Message) -> Result<bool> {
+/// `sender` is the real name of who sent it
+pub async fn received_message(mut message: Message, sender: &str) -> Result<bool> {
     let mut had_data = false;
 
     // only remove synced browsers
@@ -133,20 +224,63 @@ pub async fn received_message(mut message: Message) -> Result<bool> {
             .scale(info.scale)
             .rotation(info.rotation.0, info.rotation.1)
             .position(info.position.0, info.position.1, info.position.2)
-            .background_color(info.background_color);
+            .background_color(info.background_color)
+            // someone else made these, keep our cookies and logins away
+            .profile(Profile::Incognito);
 
         if let Some(name) = info.name {
             builder = builder.name(name);
         }
 
+        // so ignoring someone also hides screens we only got from a sync
+        builder = builder.creator(info.creator.unwrap_or_else(|| sender.to_string()));
+
+        if let Some(attachment) = info.attachment {
+            builder = builder.attachment(attachment);
+        }
+
+        if let Some(animation) = info.animation {
+            builder = builder.animation(animation);
+        }
+
         if let Some(res) = info.resolution {
             builder = builder.resolution(res.0, res.1);
         }
 
-        builder.create().await?;
+        let entity_id = builder.create().await?;
+
+        EntityManager::with_entity(entity_id, |entity| {
+            entity.texture_rect = info.texture_rect;
+            Ok(())
+        })?;
+        for link in info.links {
+            if let Err(e) = link.create(entity_id) {
+                warn!("couldn't sync wall tile or mirror: {}", e);
+            }
+        }
 
         had_data = true;
     }
 
     Ok(had_data)
 }
+
+#[test]
+fn test_versioned_encoding() {
+    let encoded = encode(&Message {
+        entities: Vec::new(),
+    })
+    .unwrap();
+    assert!(decode(&encoded).unwrap().entities.is_empty());
+
+    // what plugins before the version byte sent
+    let old_data = bincode::serde::encode_to_vec(
+        Message {
+            entities: Vec::new(),
+        },
+        BINCODE_CONFIG,
+    )
+    .unwrap();
+    let old_encoded = BASE64_STANDARD.encode(zstd::encode_all(Cursor::new(&old_data), 0).unwrap());
+    assert!(decode(old_encoded).is_err());
+}
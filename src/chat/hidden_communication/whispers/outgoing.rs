@@ -82,5 +82,5 @@ pub async fn query_whisper(real_name: &str) -> Result<bool> {
     debug!("got encoded message length {}", full_message_encoded.len());
     let message = encoding::decode(full_message_encoded)?;
     debug!("decoded {:#?}", message);
-    encoding::received_message(message).await
+    encoding::received_message(message, real_name).await
 }
@@ -299,7 +299,12 @@ fn handle_chat_received(message: String, message_type: MsgType) {
                 opt = opt2;
             }
 
-            if let Some(player_snapshot) = opt {
+            if let Some(mut player_snapshot) = opt {
+                // empty in singleplayer
+                if !real_name.is_empty() {
+                    player_snapshot.real_name = real_name;
+                }
+
                 FUTURE_HANDLE.with(|cell| {
                     let (remote, remote_handle) = async move {
                         if unsafe { Server.IsSinglePlayer } == 0 {
@@ -343,6 +348,10 @@ fn handle_chat_received(message: String, message_type: MsgType) {
 #[derive(Debug, Clone)]
 pub struct PlayerSnapshot {
     pub id: u8,
+    /// display name without colors
+    pub name: String,
+    /// username from the tab list, nicks and ranks can't change it
+    pub real_name: String,
     pub eye_position: Vec3,
     pub Position: Vec3,
     pub Pitch: f32,
@@ -353,6 +362,23 @@ pub struct PlayerSnapshot {
 }
 
 impl PlayerSnapshot {
+    /// nobody at the origin, for running commands in tests
+    #[cfg(test)]
+    pub fn zeroed() -> Self {
+        Self {
+            id: 0,
+            name: String::new(),
+            real_name: String::new(),
+            eye_position: Vec3::new(0.0, 0.0, 0.0),
+            Position: Vec3::new(0.0, 0.0, 0.0),
+            Pitch: 0.0,
+            Yaw: 0.0,
+            RotX: 0.0,
+            RotY: 0.0,
+            RotZ: 0.0,
+        }
+    }
+
     pub fn from_entity_id(id: u8) -> Option<Self> {
         ENTITIES.with(|cell| {
             let entities = &*cell.borrow();
@@ -363,8 +389,11 @@ impl PlayerSnapshot {
             let eye_position = entity.get_eye_position();
             let head = entity.get_head();
             let rot = entity.get_rot();
+            let name = remove_color(entity.get_display_name());
             Some(Self {
                 id,
+                real_name: name.clone(),
+                name,
                 Position: position,
                 eye_position,
                 Pitch: head[0],
@@ -0,0 +1,355 @@
+//! a look inside our thread_local maps for `cef debug state` and crash
+//! reports, and what's wrong between them
+//!
+//! every map is copied with `try_borrow` so this is safe from the panic hook;
+//! a map that was borrowed is None and left out of the checks
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fmt::{self, Display},
+    fs,
+    os::raw::c_int,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    audio,
+    cef::Cef,
+    entity_manager::EntityManager,
+    error::{Result, ResultExt},
+    player,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityState {
+    pub id: usize,
+    pub name: Option<String>,
+    pub type_name: &'static str,
+    pub url: String,
+    pub browser_id: Option<c_int>,
+    pub texture_source: Option<usize>,
+    pub position: (f32, f32, f32),
+    pub scale: f32,
+    pub queue_len: usize,
+    pub should_send: bool,
+    pub has_update_loop: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct DebugState {
+    pub entities: Option<Vec<EntityState>>,
+    pub name_to_id: Option<Vec<(String, usize)>>,
+    pub browser_id_to_entity_id: Option<Vec<(c_int, usize)>>,
+    pub browsers: Option<Vec<c_int>>,
+    pub browser_sizes: Option<Vec<(c_int, (u16, u16))>>,
+    pub waiting_javascript: Option<usize>,
+    pub timelapses: Option<Vec<usize>>,
+    /// name, is running
+    pub loops: Vec<(&'static str, bool)>,
+}
+
+impl DebugState {
+    pub fn capture() -> Self {
+        let mut state = Self::default();
+        EntityManager::fill_debug_state(&mut state);
+        Cef::fill_debug_state(&mut state);
+
+        state.loops = vec![
+            ("mixer", player::mixer::is_running()),
+            ("native audio", audio::is_running()),
+            ("volume fade", player::is_fading()),
+        ];
+
+        state.sort();
+        state
+    }
+
+    /// writes cef/debug-state-<timestamp>.txt
+    pub fn save(&self) -> Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain_err(|| "system time before epoch")?
+            .as_secs();
+
+        let dir = env::current_dir()
+            .chain_err(|| "current_dir() None")?
+            .join("cef");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("debug-state-{timestamp}.txt"));
+        fs::write(&path, self.to_string())?;
+
+        Ok(path)
+    }
+
+    fn sort(&mut self) {
+        if let Some(entities) = &mut self.entities {
+            entities.sort_by_key(|entity| entity.id);
+        }
+        if let Some(name_to_id) = &mut self.name_to_id {
+            name_to_id.sort();
+        }
+        if let Some(ids) = &mut self.browser_id_to_entity_id {
+            ids.sort_unstable();
+        }
+        if let Some(browsers) = &mut self.browsers {
+            browsers.sort_unstable();
+        }
+        if let Some(sizes) = &mut self.browser_sizes {
+            sizes.sort_unstable();
+        }
+        if let Some(timelapses) = &mut self.timelapses {
+            timelapses.sort_unstable();
+        }
+    }
+
+    /// things that point at something that isn't there
+    pub fn find_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let entities = self.entities.as_ref().map(|entities| {
+            entities
+                .iter()
+                .map(|entity| (entity.id, entity))
+                .collect::<HashMap<_, _>>()
+        });
+        let browsers = self
+            .browsers
+            .as_ref()
+            .map(|browsers| browsers.iter().copied().collect::<HashSet<_>>());
+        let browser_id_to_entity_id = self
+            .browser_id_to_entity_id
+            .as_ref()
+            .map(|ids| ids.iter().copied().collect::<HashMap<_, _>>());
+
+        if let (Some(entity_list), Some(entities)) = (&self.entities, &entities) {
+            for entity in entity_list {
+                if let Some(browser_id) = entity.browser_id {
+                    if browsers
+                        .as_ref()
+                        .is_some_and(|browsers| !browsers.contains(&browser_id))
+                    {
+                        problems.push(format!(
+                            "entity #{} has browser {} that isn't open",
+                            entity.id, browser_id
+                        ));
+                    }
+                    if browser_id_to_entity_id
+                        .as_ref()
+                        .is_some_and(|ids| ids.get(&browser_id) != Some(&entity.id))
+                    {
+                        problems.push(format!(
+                            "entity #{} has browser {} that doesn't map back to it",
+                            entity.id, browser_id
+                        ));
+                    }
+                }
+
+                if let Some(source_id) = entity.texture_source
+                    && !entities.contains_key(&source_id)
+                {
+                    problems.push(format!(
+                        "entity #{} shows missing entity #{}",
+                        entity.id, source_id
+                    ));
+                }
+
+                if let (Some(name), Some(name_to_id)) = (&entity.name, &self.name_to_id)
+                    && !name_to_id
+                        .iter()
+                        .any(|(mapped_name, id)| mapped_name == name && *id == entity.id)
+                {
+                    problems.push(format!(
+                        "entity #{} is named {:?} but the name doesn't map to it",
+                        entity.id, name
+                    ));
+                }
+            }
+
+            if let Some(name_to_id) = &self.name_to_id {
+                for (name, id) in name_to_id {
+                    match entities.get(id) {
+                        None => {
+                            problems.push(format!("name {name:?} points to missing entity #{id}"))
+                        }
+                        Some(entity) if entity.name.as_ref() != Some(name) => {
+                            problems.push(format!(
+                                "name {:?} points to entity #{} named {:?}",
+                                name, id, entity.name
+                            ));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+
+            if let Some(ids) = &self.browser_id_to_entity_id {
+                for (browser_id, id) in ids {
+                    if !entities.contains_key(id) {
+                        problems.push(format!(
+                            "browser {browser_id} points to missing entity #{id}"
+                        ));
+                    }
+                }
+            }
+
+            if let Some(timelapses) = &self.timelapses {
+                for id in timelapses {
+                    if !entities.contains_key(id) {
+                        problems.push(format!("timelapse running for missing entity #{id}"));
+                    }
+                }
+            }
+        }
+
+        if let (Some(browser_list), Some(browsers)) = (&self.browsers, &browsers) {
+            for browser_id in browser_list {
+                if browser_id_to_entity_id
+                    .as_ref()
+                    .is_some_and(|ids| !ids.contains_key(browser_id))
+                {
+                    problems.push(format!("browser {browser_id} has no entity"));
+                }
+            }
+
+            if let Some(sizes) = &self.browser_sizes {
+                for (browser_id, _) in sizes {
+                    if !browsers.contains(browser_id) {
+                        problems.push(format!("size kept for closed browser {browser_id}"));
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// shows None lists as borrowed
+struct Listed<'a, T>(&'a Option<Vec<T>>);
+
+impl<T: fmt::Debug> Display for Listed<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(list) => write!(f, "{list:?}"),
+            None => f.write_str("<borrowed>"),
+        }
+    }
+}
+
+impl Display for DebugState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.entities {
+            Some(entities) => {
+                writeln!(f, "entities: {}", entities.len())?;
+                for entity in entities {
+                    let (x, y, z) = entity.position;
+                    writeln!(
+                        f,
+                        "  #{} {:?} {} {:?} browser {:?} at ({}, {}, {}) scale {} queue {} \
+                         source {:?} send {} update loop {}",
+                        entity.id,
+                        entity.name,
+                        entity.type_name,
+                        entity.url,
+                        entity.browser_id,
+                        x,
+                        y,
+                        z,
+                        entity.scale,
+                        entity.queue_len,
+                        entity.texture_source,
+                        entity.should_send,
+                        entity.has_update_loop
+                    )?;
+                }
+            }
+            None => writeln!(f, "entities: <borrowed>")?,
+        }
+
+        writeln!(f, "name_to_id: {}", Listed(&self.name_to_id))?;
+        writeln!(
+            f,
+            "browser_id_to_entity_id: {}",
+            Listed(&self.browser_id_to_entity_id)
+        )?;
+        writeln!(f, "browsers: {}", Listed(&self.browsers))?;
+        writeln!(f, "browser_sizes: {}", Listed(&self.browser_sizes))?;
+        match self.waiting_javascript {
+            Some(count) => writeln!(f, "waiting javascript: {count}")?,
+            None => writeln!(f, "waiting javascript: <borrowed>")?,
+        }
+        writeln!(f, "timelapses: {}", Listed(&self.timelapses))?;
+        for (name, is_running) in &self.loops {
+            writeln!(
+                f,
+                "{name} loop: {}",
+                if *is_running { "running" } else { "stopped" }
+            )?;
+        }
+
+        let problems = self.find_problems();
+        writeln!(f, "problems: {}", problems.len())?;
+        for problem in problems {
+            writeln!(f, "  {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_entity(id: usize, name: Option<&str>, browser_id: Option<c_int>) -> EntityState {
+    EntityState {
+        id,
+        name: name.map(str::to_string),
+        type_name: "Web",
+        url: String::new(),
+        browser_id,
+        texture_source: None,
+        position: (0.0, 0.0, 0.0),
+        scale: 0.25,
+        queue_len: 0,
+        should_send: true,
+        has_update_loop: false,
+    }
+}
+
+#[test]
+fn test_find_problems() {
+    let mut state = DebugState {
+        entities: Some(vec![
+            test_entity(0, Some("lobby"), Some(1)),
+            test_entity(1, None, Some(2)),
+        ]),
+        name_to_id: Some(vec![("lobby".to_string(), 0)]),
+        browser_id_to_entity_id: Some(vec![(1, 0), (2, 1)]),
+        browsers: Some(vec![1, 2]),
+        browser_sizes: Some(vec![(1, (1920, 1080))]),
+        waiting_javascript: Some(0),
+        timelapses: Some(vec![]),
+        loops: vec![],
+    };
+    assert!(state.find_problems().is_empty());
+
+    state.browsers = Some(vec![1, 3]);
+    state.name_to_id = Some(vec![("lobby".to_string(), 0), ("arena".to_string(), 5)]);
+    state.timelapses = Some(vec![7]);
+    assert_eq!(
+        state.find_problems(),
+        vec![
+            "entity #1 has browser 2 that isn't open".to_string(),
+            "name \"arena\" points to missing entity #5".to_string(),
+            "timelapse running for missing entity #7".to_string(),
+            "browser 3 has no entity".to_string(),
+        ]
+    );
+
+    // borrowed maps aren't checked
+    state.browsers = None;
+    state.name_to_id = None;
+    state.timelapses = None;
+    assert!(state.find_problems().is_empty());
+    assert!(state.to_string().contains("browsers: <borrowed>"));
+}
@@ -0,0 +1,445 @@
+//! screens moving smoothly between transforms, for `cef move-to` and
+//! keyframe paths saved in `cef/paths`
+
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::CefEntity;
+use crate::error::{Error, Result, ResultExt, bail, ensure};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum Ease {
+    Linear,
+    In,
+    Out,
+    InOut,
+}
+
+impl Ease {
+    /// `t` from 0 to 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Self::Linear => t,
+            Self::In => t * t * t,
+            Self::Out => 1.0 - (1.0 - t).powi(3),
+            Self::InOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: (f32, f32, f32),
+    pub yaw: f32,
+    pub pitch: f32,
+    pub scale: f32,
+}
+
+impl Transform {
+    pub fn from_entity(entity: &CefEntity) -> Self {
+        let e = &entity.entity;
+
+        Self {
+            position: (e.Position.x, e.Position.y, e.Position.z),
+            yaw: e.RotY,
+            pitch: e.RotX,
+            scale: entity.get_scale(),
+        }
+    }
+
+    pub fn apply(&self, entity: &mut CefEntity) {
+        let (x, y, z) = self.position;
+        entity.entity.Position.set(x, y, z);
+        entity.entity.RotY = self.yaw;
+        entity.entity.RotX = self.pitch;
+        entity.set_scale(self.scale);
+    }
+
+    /// angles turn the short way around
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp_angle = |a: f32, b: f32| a + ((b - a + 540.0).rem_euclid(360.0) - 180.0) * t;
+
+        Self {
+            position: (
+                lerp(self.position.0, other.position.0),
+                lerp(self.position.1, other.position.1),
+                lerp(self.position.2, other.position.2),
+            ),
+            yaw: lerp_angle(self.yaw, other.yaw),
+            pitch: lerp_angle(self.pitch, other.pitch),
+            scale: lerp(self.scale, other.scale),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub transform: Transform,
+    /// time to get here from the last keyframe
+    pub seconds: f32,
+    pub ease: Ease,
+}
+
+/// `x,y,z,yaw,pitch,scale,seconds,ease`
+impl Display for Keyframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Transform {
+            position: (x, y, z),
+            yaw,
+            pitch,
+            scale,
+        } = self.transform;
+        let ease = self
+            .ease
+            .to_possible_value()
+            .ok_or(fmt::Error)?
+            .get_name()
+            .to_string();
+
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{ease}",
+            round(x, 2),
+            round(y, 2),
+            round(z, 2),
+            round(yaw, 1),
+            round(pitch, 1),
+            round(scale, 3),
+            round(self.seconds, 2),
+        )
+    }
+}
+
+/// short enough for chat, without trailing zeros
+fn round(value: f32, decimals: usize) -> String {
+    let s = format!("{value:.decimals$}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+impl FromStr for Keyframe {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.trim().split(',').collect::<Vec<_>>();
+        let [x, y, z, yaw, pitch, scale, seconds, ease] = parts[..] else {
+            bail!("keyframe {:?} isn't x,y,z,yaw,pitch,scale,seconds,ease", s);
+        };
+
+        let seconds = seconds.parse::<f32>()?;
+        ensure!(
+            seconds.is_finite() && seconds >= 0.0,
+            "keyframe seconds can't be negative"
+        );
+
+        Ok(Self {
+            transform: Transform {
+                position: (x.parse()?, y.parse()?, z.parse()?),
+                yaw: yaw.parse()?,
+                pitch: pitch.parse()?,
+                scale: scale.parse()?,
+            },
+            seconds,
+            ease: Ease::from_str(ease, true).map_err(|_| format!("unknown ease {ease:?}"))?,
+        })
+    }
+}
+
+/// keyframes from `cef animate`'s arguments, split by `;` or by spaces
+///
+/// long `;` lines get wrapped over chat messages, which adds spaces in the
+/// middle of keyframes, so those are joined back up
+pub fn parse_keyframes(args: &[String]) -> Result<Vec<Keyframe>> {
+    let text = args.join(" ");
+    if text.contains(';') {
+        text.split(';')
+            .map(|keyframe| keyframe.split_whitespace().collect::<String>())
+            .filter(|keyframe| !keyframe.is_empty())
+            .map(|keyframe| keyframe.parse())
+            .collect()
+    } else {
+        text.split_whitespace().map(str::parse).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    start: Transform,
+    keyframes: Vec<Keyframe>,
+    looping: bool,
+    /// synced, so people joining late see the same part
+    elapsed: f32,
+}
+
+impl Animation {
+    pub fn new(start: Transform, keyframes: Vec<Keyframe>, looping: bool) -> Self {
+        Self {
+            start,
+            keyframes,
+            looping,
+            elapsed: 0.0,
+        }
+    }
+
+    fn get_duration(&self) -> f32 {
+        self.keyframes.iter().map(|keyframe| keyframe.seconds).sum()
+    }
+
+    /// where we are after `elapsed` seconds, None once finished
+    fn get_transform(&self, elapsed: f32) -> Option<Transform> {
+        let last = self.keyframes.last()?;
+        let duration = self.get_duration();
+
+        // after the first time through, loops go from the last keyframe
+        // back to the first
+        let (mut from, mut time) = if elapsed < duration {
+            (self.start, elapsed)
+        } else if self.looping && duration > 0.0 {
+            (last.transform, (elapsed - duration) % duration)
+        } else {
+            return None;
+        };
+
+        for keyframe in &self.keyframes {
+            if time < keyframe.seconds {
+                let t = keyframe.ease.apply(time / keyframe.seconds);
+                return Some(from.lerp(&keyframe.transform, t));
+            }
+
+            time -= keyframe.seconds;
+            from = keyframe.transform;
+        }
+
+        Some(last.transform)
+    }
+
+    fn advance(&mut self, delta: f32) {
+        self.elapsed += delta;
+
+        // after the first pass every loop looks the same, so keep elapsed
+        // within one more loop instead of growing until floats get coarse
+        let duration = self.get_duration();
+        if self.looping && duration > 0.0 && self.elapsed >= duration * 2.0 {
+            self.elapsed = duration + (self.elapsed - duration) % duration;
+        }
+    }
+
+    /// moves `entity` along, returns false once finished
+    pub fn update(&mut self, entity: &mut CefEntity, delta: f32) -> bool {
+        self.advance(delta);
+
+        if let Some(transform) = self.get_transform(self.elapsed) {
+            transform.apply(entity);
+            true
+        } else {
+            if let Some(last) = self.keyframes.last() {
+                last.transform.apply(entity);
+            }
+            false
+        }
+    }
+}
+
+fn get_paths_dir() -> Result<PathBuf> {
+    Ok(env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("paths"))
+}
+
+fn get_path_file(name: &str) -> Result<PathBuf> {
+    ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "path names can only have letters, numbers, - and _"
+    );
+
+    Ok(get_paths_dir()?.join(format!("{name}.txt")))
+}
+
+/// keyframes of a saved path, one per line
+pub fn load_path(name: &str) -> Result<Vec<Keyframe>> {
+    let file = get_path_file(name)?;
+    let text = fs::read_to_string(&file).chain_err(|| format!("no path named {name:?}"))?;
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+pub fn save_path(name: &str, keyframes: &[Keyframe]) -> Result<()> {
+    let file = get_path_file(name)?;
+    fs::create_dir_all(get_paths_dir()?)?;
+
+    let mut lines = keyframes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    lines.push(String::new());
+    fs::write(&file, lines.join("\n"))
+        .chain_err(|| format!("couldn't write {}", file.display()))?;
+
+    Ok(())
+}
+
+pub fn remove_path(name: &str) -> Result<()> {
+    let file = get_path_file(name)?;
+    fs::remove_file(&file).chain_err(|| format!("no path named {name:?}"))?;
+
+    Ok(())
+}
+
+pub fn list_paths() -> Result<Vec<String>> {
+    let dir = get_paths_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "txt")
+            && let Some(name) = path.file_stem().and_then(|name| name.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+#[cfg(test)]
+fn assert_near(a: f32, b: f32) {
+    assert!((a - b).abs() < 0.0001, "{a} != {b}");
+}
+
+#[test]
+fn test_ease() {
+    for ease in Ease::value_variants() {
+        assert_near(ease.apply(0.0), 0.0);
+        assert_near(ease.apply(1.0), 1.0);
+        assert!(ease.apply(0.25) < ease.apply(0.75), "{ease:?}");
+    }
+
+    assert!(Ease::In.apply(0.5) < 0.5);
+    assert!(Ease::Out.apply(0.5) > 0.5);
+    assert_near(Ease::InOut.apply(0.5), 0.5);
+    assert_near(Ease::Linear.apply(2.0), 1.0);
+}
+
+#[test]
+fn test_keyframe_parse() {
+    let keyframe: Keyframe = "1,2.5,-3,90,0,0.25,2,in-out".parse().unwrap();
+    assert_eq!(keyframe.transform.position, (1.0, 2.5, -3.0));
+    assert_near(keyframe.transform.yaw, 90.0);
+    assert_near(keyframe.transform.scale, 0.25);
+    assert_near(keyframe.seconds, 2.0);
+    assert_eq!(keyframe.ease, Ease::InOut);
+    assert_eq!(keyframe.to_string().parse::<Keyframe>().unwrap(), keyframe);
+
+    let keyframe: Keyframe = "0.123456,-0.001,3,90.06,0,1,1.5,linear".parse().unwrap();
+    assert_eq!(keyframe.to_string(), "0.12,0,3,90.1,0,1,1.5,linear");
+
+    assert!("1,2,3".parse::<Keyframe>().is_err());
+    assert!("1,2,3,0,0,1,-1,linear".parse::<Keyframe>().is_err());
+    assert!("1,2,3,0,0,1,1,bouncy".parse::<Keyframe>().is_err());
+}
+
+#[test]
+fn test_animation() {
+    fn transform(x: f32, yaw: f32) -> Transform {
+        Transform {
+            position: (x, 0.0, 0.0),
+            yaw,
+            pitch: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    fn keyframe(x: f32, yaw: f32, seconds: f32) -> Keyframe {
+        Keyframe {
+            transform: transform(x, yaw),
+            seconds,
+            ease: Ease::Linear,
+        }
+    }
+
+    let keyframes = vec![keyframe(10.0, 350.0, 1.0), keyframe(20.0, 10.0, 2.0)];
+
+    let animation = Animation::new(transform(0.0, 0.0), keyframes.clone(), false);
+    let at = |elapsed| animation.get_transform(elapsed).unwrap();
+    assert_eq!(at(0.0), transform(0.0, 0.0));
+    assert_near(at(0.5).position.0, 5.0);
+    // the short way around, through 0
+    assert_near(at(0.5).yaw, -5.0);
+    assert_near(at(2.0).position.0, 15.0);
+    assert_near(at(2.0).yaw, 360.0);
+    assert_eq!(animation.get_transform(3.0), None);
+
+    let animation = Animation::new(transform(0.0, 0.0), keyframes, true);
+    let at = |elapsed| animation.get_transform(elapsed).unwrap();
+    // back to the first keyframe from the last
+    assert_near(at(3.5).position.0, 15.0);
+    assert_near(at(4.0).position.0, 10.0);
+    assert_near(at(6.0).position.0, 20.0);
+
+    let mut looping = animation.clone();
+    for _ in 0..1000 {
+        looping.advance(0.5);
+    }
+    assert!(looping.elapsed >= 3.0 && looping.elapsed < 6.0);
+    assert_near(
+        looping.get_transform(looping.elapsed).unwrap().position.0,
+        at(500.0).position.0,
+    );
+
+    assert_eq!(
+        Animation::new(transform(0.0, 0.0), Vec::new(), true).get_transform(0.0),
+        None
+    );
+}
+
+#[test]
+fn test_parse_keyframes() {
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    let keyframes = parse_keyframes(&args(&["1,2,3,0,0,1,1,in", "4,5,6,0,0,1,2,out"])).unwrap();
+    assert_eq!(keyframes.len(), 2);
+    assert_eq!(keyframes[1].transform.position, (4.0, 5.0, 6.0));
+
+    // wrapped onto the next chat line in the middle of a keyframe
+    let keyframes =
+        parse_keyframes(&args(&["1,2,3,0,0,1,1,in;4,5,", "6,0,0,1,2,in-", "out;"])).unwrap();
+    assert_eq!(keyframes.len(), 2);
+    assert_eq!(keyframes[1].transform.position, (4.0, 5.0, 6.0));
+    assert_eq!(keyframes[1].ease, Ease::InOut);
+
+    assert!(parse_keyframes(&args(&["1,2,3,0,0,1,1,in", "4,5,"])).is_err());
+}
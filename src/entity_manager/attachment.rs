@@ -0,0 +1,202 @@
+//! screens that follow a player around, see `cef attach`
+
+use std::time::{Duration, Instant};
+
+use classicube_helpers::{WithInner, tab_list::remove_color};
+use classicube_sys::{Camera, Entity, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::ENTITIES;
+
+/// how long the target can be gone, like while respawning, before we
+/// give up on them
+const DETACH_AFTER: Duration = Duration::from_secs(5);
+
+/// just above their head
+pub const DEFAULT_OFFSET: (f32, f32, f32) = (0.0, 2.0, 0.0);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    /// display name without colors
+    pub target: String,
+    /// right, up and forward from the target's feet, turns with them
+    pub offset: (f32, f32, f32),
+    /// face the camera instead of behind the target
+    pub billboard: bool,
+
+    /// where we last found the target, their id changes if they rejoin,
+    /// None until we've found them once
+    #[serde(skip)]
+    entity_id: Option<u8>,
+    #[serde(skip)]
+    missing_since: Option<Instant>,
+}
+
+impl Attachment {
+    pub fn new(target: String, offset: (f32, f32, f32), billboard: bool) -> Self {
+        Self {
+            target,
+            offset,
+            billboard,
+            entity_id: None,
+            missing_since: None,
+        }
+    }
+
+    /// position and body yaw of the target
+    pub fn find_target(&mut self) -> Option<(Vec3, f32)> {
+        let target = &self.target;
+        let (entity_id, position, yaw) = ENTITIES
+            .with_inner(|entities| {
+                let cached = self
+                    .entity_id
+                    .and_then(|id| Some((id, entities.get(id)?.upgrade()?)));
+
+                cached
+                    .into_iter()
+                    .chain(
+                        entities
+                            .get_all()
+                            .filter_map(|(id, entity)| Some((id, entity.upgrade()?))),
+                    )
+                    .find(|(_id, entity)| {
+                        remove_color(entity.get_display_name()).eq_ignore_ascii_case(target)
+                    })
+                    .map(|(id, entity)| (id, entity.get_position(), entity.get_rot()[1]))
+            })
+            .flatten()?;
+
+        self.entity_id = Some(entity_id);
+        Some((position, yaw))
+    }
+
+    /// moves `entity` to the target, returns false once the target has
+    /// been gone too long
+    pub fn update(&mut self, entity: &mut Entity, t: f32) -> bool {
+        let Some((target_position, target_yaw)) = self.find_target() else {
+            // people who joined after the attach get it from sync before
+            // the target's entity exists, so only start counting once
+            // we've seen them
+            if self.entity_id.is_none() {
+                return true;
+            }
+
+            let missing_since = *self.missing_since.get_or_insert_with(Instant::now);
+            return missing_since.elapsed() < DETACH_AFTER;
+        };
+        self.missing_since = None;
+
+        let position = get_offset_position(target_position, target_yaw, self.offset);
+        entity.Position = position;
+
+        if self.billboard {
+            if let Some(camera_position) = get_camera_position(t) {
+                let (yaw, pitch) = get_facing_angles(position, camera_position);
+                entity.RotY = yaw;
+                entity.RotX = pitch;
+            }
+        } else {
+            // like a TV on their back
+            entity.RotY = target_yaw + 180.0;
+            entity.RotX = 0.0;
+        }
+
+        true
+    }
+}
+
+fn get_camera_position(t: f32) -> Option<Vec3> {
+    unsafe {
+        if Camera.Active.is_null() {
+            return None;
+        }
+        let camera = &*Camera.Active;
+        camera.GetPosition.map(|f| f(t))
+    }
+}
+
+/// `offset` is right, up and forward of someone at `position` facing `yaw`
+fn get_offset_position(position: Vec3, yaw: f32, (x, y, z): (f32, f32, f32)) -> Vec3 {
+    let (sin, cos) = yaw.to_radians().sin_cos();
+    let right = Vec3::new(cos, 0.0, sin);
+    let forward = Vec3::new(sin, 0.0, -cos);
+
+    Vec3::new(
+        position.x + right.x * x + forward.x * z,
+        position.y + y,
+        position.z + right.z * x + forward.z * z,
+    )
+}
+
+/// yaw and pitch for a screen at `position` to face `eye_position`,
+/// the same as `cef here` would give
+fn get_facing_angles(position: Vec3, eye_position: Vec3) -> (f32, f32) {
+    let dir = position - eye_position;
+    let length = dir.length_squared().sqrt();
+    if length == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let yaw = dir.x.atan2(-dir.z).to_degrees();
+    let pitch = (-dir.y / length).asin().to_degrees();
+
+    (
+        (yaw + 180.0).rem_euclid(360.0),
+        (360.0 - pitch).rem_euclid(360.0),
+    )
+}
+
+#[test]
+fn test_offset_position() {
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length_squared() < 0.0001, "{a:?} != {b:?}");
+    }
+
+    let position = Vec3::new(10.0, 5.0, 10.0);
+
+    // yaw 0 looks towards -z
+    assert_near(
+        get_offset_position(position, 0.0, (0.0, 2.0, -0.5)),
+        Vec3::new(10.0, 7.0, 10.5),
+    );
+    assert_near(
+        get_offset_position(position, 0.0, (1.0, 0.0, 0.0)),
+        Vec3::new(11.0, 5.0, 10.0),
+    );
+    // yaw 90 looks towards +x
+    assert_near(
+        get_offset_position(position, 90.0, (1.0, 0.0, 1.0)),
+        Vec3::new(11.0, 5.0, 11.0),
+    );
+}
+
+#[test]
+fn test_facing_angles() {
+    fn assert_angles((yaw, pitch): (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (yaw - expected.0).abs() < 0.01 && (pitch - expected.1).abs() < 0.01,
+            "{:?} != {:?}",
+            (yaw, pitch),
+            expected
+        );
+    }
+
+    let eye_position = Vec3::new(0.0, 0.0, 0.0);
+
+    // straight ahead at yaw 0
+    assert_angles(
+        get_facing_angles(Vec3::new(0.0, 0.0, -2.0), eye_position),
+        (180.0, 0.0),
+    );
+    // to the right, at yaw 90
+    assert_angles(
+        get_facing_angles(Vec3::new(2.0, 0.0, 0.0), eye_position),
+        (270.0, 0.0),
+    );
+    // looking down 45 degrees
+    assert_angles(
+        get_facing_angles(Vec3::new(0.0, -1.0, -1.0), eye_position),
+        (180.0, 315.0),
+    );
+    assert_angles(get_facing_angles(eye_position, eye_position), (0.0, 0.0));
+}
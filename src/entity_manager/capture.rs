@@ -0,0 +1,293 @@
+//! copies painted browser frames out of `cef_paint_callback` and saves them
+//! as png screenshots
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fs,
+    os::raw::{c_int, c_void},
+    path::PathBuf,
+    slice,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use classicube_helpers::async_manager;
+use futures::{channel::oneshot, future::RemoteHandle, prelude::*};
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use tracing::{debug, warn};
+
+use super::EntityManager;
+use crate::{
+    cef::Browser,
+    error::{Error, Result, ResultExt},
+};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const PNG_COMPRESSION_LEVEL: u8 = 6;
+
+// browser_id, waiting captures
+thread_local!(
+    static WAITING_CAPTURES: RefCell<HashMap<c_int, Vec<oneshot::Sender<Frame>>>> =
+        RefCell::default();
+);
+
+// entity_id, periodic capture task
+thread_local!(
+    static TIMELAPSES: RefCell<HashMap<usize, RemoteHandle<()>>> = RefCell::default();
+);
+
+/// a frame as RGBA rows, top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// convert CEF's BGRA paint buffer
+    pub fn from_bgra(bgra: &[u8], width: usize, height: usize) -> Self {
+        let mut rgba = bgra[..width * height * 4].to_vec();
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    pub fn encode_png(&self) -> Vec<u8> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bit depth, RGBA, deflate, adaptive filtering, no interlace
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        // every row starts with its filter type, we always use None
+        let stride = self.width * 4;
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        for row in self.rgba.chunks_exact(stride) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        let idat = compress_to_vec_zlib(&raw, PNG_COMPRESSION_LEVEL);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(&mut png, *b"IHDR", &ihdr);
+        write_png_chunk(&mut png, *b"IDAT", &idat);
+        write_png_chunk(&mut png, *b"IEND", &[]);
+        png
+    }
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(&kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&[&kind, data]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// called from `cef_paint_callback` with the browser's BGRA pixels
+pub fn on_paint(browser_id: c_int, pixels: *const c_void, width: c_int, height: c_int) {
+    let Some(senders) = WAITING_CAPTURES.with(|cell| cell.borrow_mut().remove(&browser_id)) else {
+        return;
+    };
+
+    let (Ok(width), Ok(height)) = (usize::try_from(width), usize::try_from(height)) else {
+        return;
+    };
+
+    let bgra = unsafe { slice::from_raw_parts(pixels.cast::<u8>(), width * height * 4) };
+    let frame = Frame::from_bgra(bgra, width, height);
+
+    for sender in senders {
+        let _ignore = sender.send(frame.clone());
+    }
+}
+
+/// wait for the browser's next painted frame
+pub async fn capture(browser: &Browser) -> Result<Frame> {
+    let (sender, receiver) = oneshot::channel();
+    WAITING_CAPTURES.with(|cell| {
+        cell.borrow_mut()
+            .entry(browser.get_identifier())
+            .or_default()
+            .push(sender);
+    });
+
+    // pages that aren't animating won't paint on their own
+    browser.invalidate()?;
+
+    let frame = async_manager::timeout_local(Duration::from_secs(5), receiver)
+        .await
+        .chain_err(|| "timed out waiting for a frame")??;
+
+    Ok(frame)
+}
+
+/// encode and write `frame` to screenshots/cef-<name>-<timestamp>.png
+pub async fn save_screenshot(frame: Frame, name: &str) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .chain_err(|| "system time before epoch")?
+        .as_millis();
+
+    let dir = env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("screenshots");
+    let path = dir.join(format!("cef-{name}-{timestamp}.png"));
+
+    let result_path = path.clone();
+    async_manager::spawn(async move {
+        let png = frame.encode_png();
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, png)?;
+        Ok::<_, Error>(())
+    })
+    .await??;
+
+    Ok(result_path)
+}
+
+/// capture and save the entity's browser, returns the saved path
+pub async fn screenshot(entity_id: usize) -> Result<PathBuf> {
+    let name = EntityManager::with_entity(entity_id, |entity| {
+        Ok(entity.name.clone().unwrap_or_else(|| entity_id.to_string()))
+    })?;
+    let browser = EntityManager::get_browser_by_entity_id(entity_id)?;
+
+    let frame = capture(&browser).await?;
+    save_screenshot(frame, &name).await
+}
+
+/// save a screenshot every `interval` until stopped or the entity is removed
+pub fn start_timelapse(entity_id: usize, interval: Duration) {
+    let (f, remote_handle) = async move {
+        loop {
+            async_manager::sleep(interval).await;
+
+            match screenshot(entity_id).await {
+                Ok(path) => debug!("timelapse {} saved {}", entity_id, path.display()),
+                Err(e) => {
+                    warn!("timelapse {} stopped: {}", entity_id, e);
+                    break;
+                }
+            }
+        }
+
+        // let the handle drop without cancelling ourselves
+        if let Some(handle) = TIMELAPSES.with(|cell| cell.borrow_mut().remove(&entity_id)) {
+            handle.forget();
+        }
+    }
+    .remote_handle();
+
+    // replaces and cancels any running timelapse
+    TIMELAPSES.with(|cell| cell.borrow_mut().insert(entity_id, remote_handle));
+    async_manager::spawn_local_on_main_thread(f);
+}
+
+/// returns false if there was no timelapse running
+pub fn stop_timelapse(entity_id: usize) -> bool {
+    TIMELAPSES
+        .with(|cell| cell.borrow_mut().remove(&entity_id))
+        .is_some()
+}
+
+/// None if borrowed
+pub fn get_timelapse_entity_ids() -> Option<Vec<usize>> {
+    TIMELAPSES.with(|cell| Some(cell.try_borrow().ok()?.keys().copied().collect()))
+}
+
+pub fn shutdown() {
+    TIMELAPSES.with(|cell| cell.borrow_mut().clear());
+
+    // dropping the senders cancels anyone still waiting
+    WAITING_CAPTURES.with(|cell| cell.borrow_mut().clear());
+}
+
+/// only understands what `encode_png` writes
+#[cfg(test)]
+fn decode_png(png: &[u8]) -> Frame {
+    assert_eq!(png[..8], PNG_SIGNATURE);
+
+    let mut rest = &png[8..];
+    let mut size = None;
+    let mut idat = Vec::new();
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let data = &rest[8..8 + len];
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(crc32(&[kind, data]), crc, "bad crc");
+
+        match kind {
+            b"IHDR" => {
+                assert_eq!(data[8..], [8, 6, 0, 0, 0], "expected 8 bit RGBA");
+                size = Some((
+                    u32::from_be_bytes(data[..4].try_into().unwrap()) as usize,
+                    u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+                ));
+            }
+            b"IDAT" => idat.extend_from_slice(data),
+            _ => {}
+        }
+
+        rest = &rest[12 + len..];
+    }
+
+    let (width, height) = size.unwrap();
+    let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&idat).unwrap();
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in raw.chunks_exact(width * 4 + 1) {
+        assert_eq!(row[0], 0, "expected filter type None");
+        rgba.extend_from_slice(&row[1..]);
+    }
+
+    Frame {
+        width,
+        height,
+        rgba,
+    }
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(&[b"IEND"]), 0xAE42_6082);
+    assert_eq!(crc32(&[b"123", b"456789"]), 0xCBF4_3926);
+}
+
+#[test]
+fn test_capture_golden() {
+    // what OnPaint gives us: 4x2 BGRA, red green blue white then
+    // half transparent black, yellow, cyan, transparent
+    #[rustfmt::skip]
+    let bgra = [
+        0, 0, 255, 255,    0, 255, 0, 255,    255, 0, 0, 255,    255, 255, 255, 255,
+        0, 0, 0, 128,      0, 255, 255, 255,  255, 255, 0, 255,  0, 0, 0, 0,
+    ];
+
+    let frame = Frame::from_bgra(&bgra, 4, 2);
+    let png = frame.encode_png();
+
+    let golden = decode_png(include_bytes!("golden/frame_4x2.png"));
+    assert_eq!(decode_png(&png), golden);
+    assert_eq!(frame, golden);
+}
@@ -3,8 +3,11 @@ use std::os::raw::{c_int, c_void};
 use classicube_sys::Bitmap;
 use tracing::warn;
 
-use super::EntityManager;
-use crate::cef::RustRefBrowser;
+use super::{EntityManager, capture};
+use crate::{
+    cef::RustRefBrowser,
+    error::{Error, ResultExt},
+};
 
 /// This gets called from cef browser's OnPaint
 #[tracing::instrument(fields(browser = browser.get_identifier(), new_pixels))]
@@ -16,18 +19,37 @@ pub extern "C" fn cef_paint_callback(
 ) {
     let browser_id = browser.get_identifier();
 
-    if let Err(e) = EntityManager::with_by_browser_id(browser_id, |entity| {
-        if entity.get_scale() != 0.0 {
-            let part = Bitmap {
-                scan0: new_pixels as *mut _,
-                width: new_width,
-                height: new_height,
-            };
+    capture::on_paint(browser_id, new_pixels, new_width, new_height);
 
-            entity.update_texture(part);
+    if let Err(e) = EntityManager::with_all_entities(|entities| {
+        let entity_id = EntityManager::get_by_browser_id(browser_id, entities)
+            .map(|entity| entity.id)
+            .chain_err(|| format!("No entity found with browser id {browser_id}!"))?;
+
+        // wall tiles and mirrors draw our texture, so keep uploading
+        // while any of them are visible
+        let is_visible = entities.values().any(|entity| {
+            (entity.id == entity_id || entity.texture_source == Some(entity_id))
+                && (entity.get_scale() != 0.0 || entity.hud.is_some())
+        });
+
+        for entity in entities.values_mut() {
+            if entity.id == entity_id {
+                if is_visible {
+                    let part = Bitmap {
+                        scan0: new_pixels as *mut _,
+                        width: new_width,
+                        height: new_height,
+                    };
+
+                    entity.update_texture(part);
+                }
+            } else if entity.texture_source == Some(entity_id) {
+                entity.update_uv(new_width, new_height);
+            }
         }
 
-        Ok(())
+        Ok::<_, Error>(())
     }) {
         warn!("cef_paint_callback: {}", e);
     }
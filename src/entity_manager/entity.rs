@@ -1,7 +1,7 @@
 use std::{
     collections::VecDeque,
     mem,
-    os::raw::c_short,
+    os::raw::{c_int, c_short},
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -9,21 +9,26 @@ use std::{
 use classicube_helpers::{async_manager, color::SILVER};
 use classicube_sys::{
     Bitmap, Entity, Entity_Init, Entity_SetModel, EntityVTABLE, Gfx_UpdateTexturePart,
-    LocationUpdate, Model_Render, OwnedGfxTexture, OwnedString, PACKEDCOL_WHITE, PackedCol,
-    Texture, TextureRec, cc_int16,
+    GfxResourceID, LocationUpdate, Model_Render, OwnedGfxTexture, OwnedString, PACKEDCOL_WHITE,
+    PackedCol, Texture, TextureRec, cc_int16,
 };
 use futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
-use super::{BROWSER_ID_TO_ENTITY_ID, TEXTURE_HEIGHT, TEXTURE_WIDTH};
+use super::{
+    BROWSER_ID_TO_ENTITY_ID, TEXTURE_HEIGHT, TEXTURE_WIDTH, animation::Animation,
+    attachment::Attachment, hud::HudSettings, local_overrides::LocalOverrides,
+};
 use crate::{
     api,
-    cef::{Cef, RustRefBrowser},
+    cef::{Browser, Cef},
     chat::Chat,
     entity_manager::{DEFAULT_MODEL_HEIGHT, DEFAULT_MODEL_WIDTH},
     error::{Error, Result, ResultExt},
     helpers::format_duration,
     player::{Player, PlayerTrait, WebPlayer},
+    userscripts,
 };
 
 pub struct CefEntity {
@@ -31,19 +36,54 @@ pub struct CefEntity {
     pub name: Option<String>,
 
     pub entity: Box<Entity>,
-    pub browser: Option<RustRefBrowser>,
+    pub browser: Option<Browser>,
 
     pub player: Player,
     pub queue: VecDeque<(Player, Arc<Mutex<Option<String>>>)>,
     pub should_send: bool,
     pub background_color: u32,
 
+    /// entity whose browser and texture we show instead of our own,
+    /// for video wall tiles and mirrors
+    pub texture_source: Option<usize>,
+    /// part of the browser we show
+    pub texture_rect: TextureRect,
+    /// also drawn flat on our own screen, never synced
+    pub hud: Option<HudSettings>,
+    /// your own mute/hide/volume changes, never synced
+    pub local: LocalOverrides,
+    /// real name of the player who created this screen, if it wasn't us
+    pub creator: Option<String>,
+    /// player we follow around
+    pub attachment: Option<Attachment>,
+    /// tween or keyframe path we're moving along
+    pub animation: Option<Animation>,
+
     v_table: Box<EntityVTABLE>,
-    texture: OwnedGfxTexture,
+    /// None when we show another entity's texture
+    texture: Option<OwnedGfxTexture>,
 
     page_loaded_senders: Vec<oneshot::Sender<()>>,
 }
 
+/// a sub-rectangle of a browser, as fractions of its width and height
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TextureRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl TextureRect {
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+}
+
 impl CefEntity {
     pub fn register(
         id: usize,
@@ -53,17 +93,6 @@ impl CefEntity {
         should_send: bool,
         background_color: u32,
     ) -> Self {
-        let entity = Box::new(unsafe { mem::zeroed() });
-
-        let v_table = Box::new(EntityVTABLE {
-            Tick: Some(Self::tick),
-            Despawn: Some(Self::despawn),
-            SetLocation: Some(Self::set_location),
-            GetCol: Some(Self::get_col),
-            RenderModel: Some(Self::c_render_model),
-            ShouldRenderName: Some(Self::should_render_name),
-        });
-
         let mut pixels: Vec<u32> =
             vec![background_color; TEXTURE_WIDTH as usize * TEXTURE_HEIGHT as usize];
 
@@ -76,12 +105,21 @@ impl CefEntity {
         let texture =
             OwnedGfxTexture::new(&mut bmp, true, false).expect("create CEF entity texture");
 
+        let texture_id = texture.resource_id;
+        let local = LocalOverrides::load(name.as_deref());
         let mut this = Self {
             id,
             name,
-            entity,
-            v_table,
-            texture,
+            entity: Box::new(unsafe { mem::zeroed() }),
+            v_table: Self::create_v_table(),
+            texture: Some(texture),
+            texture_source: None,
+            texture_rect: TextureRect::FULL,
+            hud: None,
+            local,
+            creator: None,
+            attachment: None,
+            animation: None,
             browser: None,
             player,
             // TODO spawn lookups here?
@@ -94,11 +132,89 @@ impl CefEntity {
             page_loaded_senders: Vec::new(),
         };
 
-        this.register_entity();
+        this.register_entity(texture_id);
+
+        this
+    }
+
+    /// an entity without a browser that draws `source`'s texture
+    ///
+    /// it has no audio and is synced along with `source`
+    pub fn register_linked(id: usize, name: Option<String>, source: &CefEntity) -> Self {
+        let mut this = Self {
+            id,
+            name,
+            entity: Box::new(unsafe { mem::zeroed() }),
+            v_table: Self::create_v_table(),
+            texture: None,
+            texture_source: Some(source.id),
+            texture_rect: TextureRect::FULL,
+            hud: None,
+            local: LocalOverrides::default(),
+            creator: None,
+            attachment: None,
+            animation: None,
+            browser: None,
+            player: Player::Web(WebPlayer::blank_page()),
+            queue: VecDeque::new(),
+            should_send: false,
+            background_color: source.background_color,
+            page_loaded_senders: Vec::new(),
+        };
+
+        this.register_entity(source.entity.TextureId);
+
+        this.entity.Position = source.entity.Position;
+        this.entity.RotX = source.entity.RotX;
+        this.entity.RotY = source.entity.RotY;
+        this.entity.ModelScale = source.entity.ModelScale;
+        let (width, height) = source.get_size();
+        this.set_size(width, height);
 
         this
     }
 
+    /// an entity without a texture or game entity, for tests
+    #[cfg(test)]
+    pub fn register_fake(id: usize, player: Player, queue: VecDeque<Player>) -> Self {
+        Self {
+            id,
+            name: None,
+            entity: Box::new(unsafe { mem::zeroed() }),
+            v_table: Self::create_v_table(),
+            texture: None,
+            texture_source: None,
+            texture_rect: TextureRect::FULL,
+            hud: None,
+            local: LocalOverrides::default(),
+            creator: None,
+            attachment: None,
+            animation: None,
+            browser: None,
+            player,
+            queue: queue
+                .into_iter()
+                .map(|player| (player, Arc::new(Mutex::new(None))))
+                .collect(),
+            should_send: false,
+            background_color: 0xFFFF_FFFF,
+            page_loaded_senders: Vec::new(),
+        }
+    }
+
+    fn create_v_table() -> Box<EntityVTABLE> {
+        Box::new(EntityVTABLE {
+            Tick: Some(Self::tick),
+            Despawn: Some(Self::despawn),
+            SetLocation: Some(Self::set_location),
+            GetCol: Some(Self::get_col),
+            RenderModel: Some(Self::c_render_model),
+            ShouldRenderName: Some(Self::should_render_name),
+        })
+    }
+
+    // we aren't in ClassiCube's entity list so this is never called,
+    // see update_animation and update_attachment instead
     extern "C" fn tick(_entity: *mut Entity, _delta: f32) {}
 
     extern "C" fn despawn(_entity: *mut Entity) {}
@@ -117,12 +233,9 @@ impl CefEntity {
         0
     }
 
-    fn register_entity(&mut self) {
+    fn register_entity(&mut self, texture_id: GfxResourceID) {
         let CefEntity {
-            entity,
-            v_table,
-            texture,
-            ..
+            entity, v_table, ..
         } = self;
 
         unsafe {
@@ -137,7 +250,7 @@ impl CefEntity {
         entity.VTABLE = v_table.as_mut();
         entity.Velocity.set(0.0, 0.0, 0.0);
         entity.RotZ = 180.0;
-        entity.TextureId = texture.resource_id;
+        entity.TextureId = texture_id;
 
         entity.Position.set(0.0, 0.0, 0.0);
 
@@ -160,17 +273,36 @@ impl CefEntity {
     }
 
     pub fn update_texture(&mut self, mut part: Bitmap) {
-        // update uv's
-        self.entity.NameTex.uv.u2 = part.width as f32 / TEXTURE_WIDTH as f32;
-        self.entity.NameTex.uv.v2 = part.height as f32 / TEXTURE_HEIGHT as f32;
+        self.update_uv(part.width, part.height);
 
-        unsafe {
-            Gfx_UpdateTexturePart(self.texture.resource_id, 0, 0, &raw mut part, 0);
+        if let Some(texture) = &self.texture {
+            unsafe {
+                Gfx_UpdateTexturePart(texture.resource_id, 0, 0, &raw mut part, 0);
+            }
         }
     }
 
+    /// point our uv's at `texture_rect` of a browser painting at this size
+    pub fn update_uv(&mut self, browser_width: c_int, browser_height: c_int) {
+        let u = browser_width as f32 / TEXTURE_WIDTH as f32;
+        let v = browser_height as f32 / TEXTURE_HEIGHT as f32;
+        let TextureRect {
+            x,
+            y,
+            width,
+            height,
+        } = self.texture_rect;
+
+        self.entity.NameTex.uv = TextureRec {
+            u1: x * u,
+            v1: y * v,
+            u2: (x + width) * u,
+            v2: (y + height) * v,
+        };
+    }
+
     pub fn render_model(&mut self) {
-        if self.get_scale() != 0.0 {
+        if self.get_scale() != 0.0 && !self.local.is_hidden() {
             let entity = self.entity.as_mut();
             unsafe {
                 Model_Render(entity.Model, entity);
@@ -178,6 +310,33 @@ impl CefEntity {
         }
     }
 
+    /// move along our animation, called every frame before rendering
+    pub fn update_animation(&mut self, delta: f32) {
+        if let Some(mut animation) = self.animation.take()
+            && animation.update(self, delta)
+        {
+            self.animation = Some(animation);
+        }
+    }
+
+    /// follow our attachment's target, called every frame before rendering
+    pub fn update_attachment(&mut self, t: f32) {
+        let Some(attachment) = &mut self.attachment else {
+            return;
+        };
+
+        if !attachment.update(&mut self.entity, t) {
+            let target = attachment.target.clone();
+            debug!("#{} detached from {:?}", self.id, target);
+            self.attachment = None;
+
+            // not while rendering
+            async_manager::spawn_local_on_main_thread(async move {
+                Chat::print(format!("{SILVER}{target} left, screen detached"));
+            });
+        }
+    }
+
     pub fn set_scale(&mut self, scale: f32) {
         let CefEntity { entity, .. } = self;
 
@@ -295,7 +454,7 @@ impl CefEntity {
 
         // CEF doesn't preserve SetAudioMuted across LoadURL, so reapply
         // before navigating so the new page starts in the correct state.
-        browser.set_audio_muted(Cef::should_mute_for_focus())?;
+        self.update_audio_muted(browser)?;
 
         browser.load_url(url)?;
 
@@ -313,7 +472,7 @@ impl CefEntity {
         Ok(())
     }
 
-    pub fn attach_browser(&mut self, browser: RustRefBrowser) {
+    pub fn attach_browser(&mut self, browser: Browser) {
         let browser_id = browser.get_identifier();
 
         BROWSER_ID_TO_ENTITY_ID.with(|ids| {
@@ -322,15 +481,28 @@ impl CefEntity {
             ids.insert(browser_id, self.id);
             self.browser = Some(browser);
         });
+
+        if self.local.is_muted()
+            && let Some(browser) = &self.browser
+            && let Err(e) = browser.set_audio_muted(true)
+        {
+            warn!("attach_browser set_audio_muted: {}", e);
+        }
     }
 
-    pub fn on_page_loaded(&mut self, browser: &RustRefBrowser) -> Result<()> {
+    /// focus mute or our own local mute
+    pub fn update_audio_muted(&self, browser: &Browser) -> Result<()> {
+        browser.set_audio_muted(Cef::should_mute_for_focus() || self.local.is_muted())
+    }
+
+    pub fn on_page_loaded(&mut self, browser: &Browser) -> Result<()> {
         // Reassert the focus-mute state after navigation in case any new
         // audio streams (e.g. cross-origin iframes) were spun up by the
         // newly loaded page.
-        browser.set_audio_muted(Cef::should_mute_for_focus())?;
+        self.update_audio_muted(browser)?;
 
         self.player.on_page_loaded(self.id, browser);
+        self.apply_userscripts(browser);
 
         for sender in self.page_loaded_senders.drain(..) {
             let _ignore = sender.send(());
@@ -339,6 +511,13 @@ impl CefEntity {
         Ok(())
     }
 
+    /// also ran after in-page navigation, scripts only apply once per page
+    pub fn apply_userscripts(&self, browser: &Browser) {
+        if let Err(e) = userscripts::apply(browser, self.name.as_deref()) {
+            warn!("userscripts on #{}: {}", self.id, e);
+        }
+    }
+
     pub fn wait_for_page_load(&mut self) -> oneshot::Receiver<()> {
         let (sender, receiver) = oneshot::channel();
         self.page_loaded_senders.push(sender);
@@ -346,3 +525,49 @@ impl CefEntity {
         receiver
     }
 }
+
+#[test]
+fn test_queue_skip_finish() {
+    use super::EntityManager;
+
+    let (entity_id, browser) =
+        EntityManager::create_fake(Player::Web(WebPlayer::blank_page()), VecDeque::new());
+
+    let first = Player::from_input("https://example.com/first.mp4").unwrap();
+    let second = Player::from_input("https://example.com/second.mp4").unwrap();
+
+    EntityManager::with_entity(entity_id, |entity| {
+        // a web page counts as finished, so this plays right away
+        assert_eq!(entity.queue(first)?, None);
+        // but a video doesn't until it ends
+        assert_eq!(entity.queue(second)?, Some(1));
+        assert_eq!(browser.loaded_urls().len(), 1);
+        assert!(browser.loaded_urls()[0].starts_with("local://media/"));
+        assert!(browser.loaded_urls()[0].contains("first.mp4"));
+
+        // same player type keeps its volume mode
+        entity.skip()?;
+        assert!(entity.queue.is_empty());
+        assert_eq!(entity.player.get_url(), "https://example.com/second.mp4");
+        assert!(browser.loaded_urls()[1].contains("second.mp4"));
+        assert!(
+            browser
+                .executed_javascript()
+                .iter()
+                .any(|code| code.contains("cefAudio"))
+        );
+
+        // nothing queued and still playing, so show a blank page
+        entity.local.muted = true;
+        entity.skip()?;
+        assert_eq!(entity.player.get_url(), WebPlayer::blank_page().get_url());
+        assert!(browser.is_audio_muted());
+
+        // a blank page is already finished
+        entity.skip()?;
+        assert_eq!(browser.loaded_urls().len(), 3);
+
+        Ok(())
+    })
+    .unwrap();
+}
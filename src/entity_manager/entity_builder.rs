@@ -2,9 +2,12 @@ use std::collections::VecDeque;
 
 use tracing::debug;
 
-use super::{CefEntity, ENTITIES, EntityManager, NAME_TO_ID};
+use super::{
+    CefEntity, ENTITIES, EntityManager, NAME_TO_ID, animation::Animation, attachment::Attachment,
+    local_overrides,
+};
 use crate::{
-    cef::Cef,
+    cef::{Cef, Profile},
     error::{Error, Result},
     options::FRAME_RATE,
     player::{Player, PlayerTrait},
@@ -15,6 +18,7 @@ pub struct EntityBuilder {
     queue: VecDeque<Player>,
 
     name: Option<String>,
+    creator: Option<String>,
     insecure: bool,
     should_send: bool,
     frame_rate: u16,
@@ -24,6 +28,9 @@ pub struct EntityBuilder {
     rotation: Option<(f32, f32)>,
     position: Option<(f32, f32, f32)>,
     background_color: Option<u32>,
+    profile: Option<Profile>,
+    attachment: Option<Attachment>,
+    animation: Option<Animation>,
 }
 
 impl EntityBuilder {
@@ -32,6 +39,7 @@ impl EntityBuilder {
             player,
             queue: VecDeque::new(),
             name: None,
+            creator: None,
             insecure: false,
             should_send: true,
             frame_rate: FRAME_RATE.get().unwrap(),
@@ -41,11 +49,18 @@ impl EntityBuilder {
             rotation: None,
             position: None,
             background_color: None,
+            profile: None,
+            attachment: None,
+            animation: None,
         }
     }
 
     pub async fn create(mut self) -> Result<usize> {
         let name = self.name.take();
+        let profile = match self.profile.take() {
+            Some(profile) => profile,
+            None => Profile::get_default()?,
+        };
         let url = self.player.on_create()?;
 
         let entity_id = EntityManager::get_new_id();
@@ -64,6 +79,11 @@ impl EntityBuilder {
                     background_color,
                 );
 
+                if let Some(creator) = self.creator {
+                    entity.local.ignored = local_overrides::is_player_ignored(&creator);
+                    entity.creator = Some(creator);
+                }
+
                 if let Some(pos) = self.position {
                     entity.entity.Position.set(pos.0, pos.1, pos.2);
                 }
@@ -77,6 +97,8 @@ impl EntityBuilder {
                     entity.set_size(size.0, size.1);
                 }
                 entity.set_scale(self.scale);
+                entity.attachment = self.attachment;
+                entity.animation = self.animation;
 
                 debug!("entity {} registered", entity_id);
                 entities.insert(entity_id, entity);
@@ -87,7 +109,8 @@ impl EntityBuilder {
 
                 async move {
                     let browser =
-                        Cef::create_browser(url, frame_rate, insecure, background_color).await?;
+                        Cef::create_browser(url, frame_rate, insecure, background_color, profile)
+                            .await?;
 
                     if let Some((width, height)) = resolution {
                         Cef::resize_browser(&browser, width, height)?;
@@ -124,6 +147,12 @@ impl EntityBuilder {
         self
     }
 
+    /// the player who asked for this screen, for `cef local ignore`
+    pub fn creator<S: Into<String>>(mut self, creator: S) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
     pub fn insecure(mut self, insecure: bool) -> Self {
         self.insecure = insecure;
         self
@@ -173,4 +202,21 @@ impl EntityBuilder {
         self.background_color = Some(background_color);
         self
     }
+
+    pub fn attachment(mut self, attachment: Attachment) -> Self {
+        self.attachment = Some(attachment);
+        self
+    }
+
+    /// carries on from where it was
+    pub fn animation(mut self, animation: Animation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    /// defaults to `cef config profile`
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
 }
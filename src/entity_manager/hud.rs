@@ -0,0 +1,170 @@
+//! draws a screen's texture flat on top of the game, like picture-in-picture
+
+use std::cell::Cell;
+
+use clap::ValueEnum;
+use classicube_sys::{Game_Draw2DHooks, PackedCol_Make, Texture, WindowInfo};
+use tracing::warn;
+
+use super::{CefEntity, ENTITIES, TEXTURE_HEIGHT, TEXTURE_WIDTH, helpers::Texture_RenderShaded};
+
+// which of the game's 2D hooks we took
+thread_local!(
+    static HOOK_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+);
+
+/// pixels between the overlay and the window edge
+const MARGIN: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HudAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+    /// as big as fits in the window, ignores size
+    Fullscreen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HudSettings {
+    pub anchor: HudAnchor,
+    /// fraction of the window's width
+    pub size: f32,
+    /// 0 to 1
+    pub opacity: f32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            anchor: HudAnchor::TopRight,
+            size: 0.3,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl HudSettings {
+    /// (x, y, width, height) in window pixels
+    fn get_rect(&self, window: (f32, f32), aspect: f32) -> (f32, f32, f32, f32) {
+        let (window_width, window_height) = window;
+
+        let (width, height) = if self.anchor == HudAnchor::Fullscreen {
+            let width = window_width.min(window_height * aspect);
+            (width, width / aspect)
+        } else {
+            let width = window_width * self.size.clamp(0.0, 1.0);
+            (width, width / aspect)
+        };
+
+        let left = MARGIN;
+        let right = window_width - width - MARGIN;
+        let top = MARGIN;
+        let bottom = window_height - height - MARGIN;
+        let center_x = (window_width - width) / 2.0;
+        let center_y = (window_height - height) / 2.0;
+
+        let (x, y) = match self.anchor {
+            HudAnchor::TopLeft => (left, top),
+            HudAnchor::TopRight => (right, top),
+            HudAnchor::BottomLeft => (left, bottom),
+            HudAnchor::BottomRight => (right, bottom),
+            HudAnchor::Center | HudAnchor::Fullscreen => (center_x, center_y),
+        };
+
+        (x, y, width, height)
+    }
+}
+
+/// draw from the game's 2D pass, after the world and the gui
+pub fn initialize() {
+    let hooks = unsafe { &mut *(&raw mut Game_Draw2DHooks) };
+
+    let Some(index) = hooks.iter().position(Option::is_none) else {
+        warn!("no free 2D hook, hud won't be drawn");
+        return;
+    };
+    hooks[index] = Some(draw_2d_hook);
+    HOOK_INDEX.set(Some(index));
+}
+
+pub fn shutdown() {
+    if let Some(index) = HOOK_INDEX.take() {
+        unsafe {
+            Game_Draw2DHooks[index] = None;
+        }
+    }
+}
+
+extern "C" fn draw_2d_hook(_delta: f32) {
+    ENTITIES.with_borrow(|entities| render(entities.values()));
+}
+
+/// draw every entity with hud settings, the game has already set up 2D
+fn render<'a, I: Iterator<Item = &'a CefEntity>>(entities: I) {
+    let window = unsafe { (WindowInfo.Width as f32, WindowInfo.Height as f32) };
+
+    // hidden with cef local hide, like in the world
+    for entity in entities.filter(|entity| !entity.local.is_hidden()) {
+        render_entity(entity, window);
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn render_entity(entity: &CefEntity, window: (f32, f32)) {
+    let Some(hud) = entity.hud else {
+        return;
+    };
+
+    // the painted part of the texture, so we keep the browser's shape
+    let uv = entity.entity.NameTex.uv;
+    let pixels_wide = (uv.u2 - uv.u1) * TEXTURE_WIDTH as f32;
+    let pixels_high = (uv.v2 - uv.v1) * TEXTURE_HEIGHT as f32;
+    if pixels_wide <= 0.0 || pixels_high <= 0.0 {
+        return;
+    }
+
+    let (x, y, width, height) = hud.get_rect(window, pixels_wide / pixels_high);
+
+    let mut texture = Texture {
+        ID: entity.entity.TextureId,
+        x: x as _,
+        y: y as _,
+        width: width as _,
+        height: height as _,
+        uv,
+    };
+
+    let alpha = (hud.opacity.clamp(0.0, 1.0) * 255.0) as u8;
+    unsafe {
+        Texture_RenderShaded(&mut texture, PackedCol_Make(255, 255, 255, alpha));
+    }
+}
+
+#[test]
+fn test_hud_rect() {
+    let window = (1000.0, 400.0);
+    let aspect = 2.0;
+
+    let hud = HudSettings {
+        anchor: HudAnchor::TopRight,
+        size: 0.5,
+        opacity: 1.0,
+    };
+    assert_eq!(hud.get_rect(window, aspect), (492.0, 8.0, 500.0, 250.0));
+
+    let hud = HudSettings {
+        anchor: HudAnchor::BottomLeft,
+        ..hud
+    };
+    assert_eq!(hud.get_rect(window, aspect), (8.0, 142.0, 500.0, 250.0));
+
+    // limited by the window's height
+    let hud = HudSettings {
+        anchor: HudAnchor::Fullscreen,
+        ..hud
+    };
+    assert_eq!(hud.get_rect(window, aspect), (100.0, 0.0, 800.0, 400.0));
+}
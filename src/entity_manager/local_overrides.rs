@@ -0,0 +1,225 @@
+//! per-screen mute/hide/volume changes that only apply on your own client
+//!
+//! these live on `CefEntity` instead of `Player` so they're never synced,
+//! and are saved per server in options by screen name
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use classicube_sys::Server;
+
+use crate::{
+    error::{Error, Result, bail, ensure},
+    options,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LocalOverrides {
+    pub muted: bool,
+    pub hidden: bool,
+    /// -1 to 1, added to the screen's 100% volume
+    pub volume_offset: f32,
+
+    /// created by an ignored player, not saved per screen
+    pub ignored: bool,
+}
+
+impl LocalOverrides {
+    pub fn is_muted(&self) -> bool {
+        self.muted || self.ignored
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden || self.ignored
+    }
+
+    pub fn get_volume_multiplier(&self) -> f32 {
+        if self.is_muted() {
+            0.0
+        } else {
+            (1.0 + self.volume_offset).max(0.0)
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        !self.muted && !self.hidden && self.volume_offset.abs() < 0.0001
+    }
+
+    /// saved overrides for a named screen on this server, unnamed screens
+    /// only keep theirs until they're removed
+    pub fn load(screen_name: Option<&str>) -> Self {
+        screen_name
+            .and_then(|name| options::get(get_screen_key(name)))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, screen_name: Option<&str>) {
+        if let Some(name) = screen_name {
+            let value = if self.is_default() {
+                String::new()
+            } else {
+                self.to_string()
+            };
+            options::set(get_screen_key(name), value);
+        }
+    }
+}
+
+/// `mute,hide,offset=-0.5`
+impl Display for LocalOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.muted {
+            parts.push("mute".to_string());
+        }
+        if self.hidden {
+            parts.push("hide".to_string());
+        }
+        if self.volume_offset.abs() >= 0.0001 {
+            parts.push(format!("offset={}", self.volume_offset));
+        }
+
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+impl FromStr for LocalOverrides {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut overrides = Self::default();
+
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once('=') {
+                None if part == "mute" => overrides.muted = true,
+                None if part == "hide" => overrides.hidden = true,
+                Some(("offset", offset)) => {
+                    let offset = offset.parse::<f32>()?;
+                    ensure!(
+                        (-1.0..=1.0).contains(&offset),
+                        "volume offset must be between -1 and 1"
+                    );
+                    overrides.volume_offset = offset;
+                }
+                _ => bail!("unknown local override {:?}", part),
+            }
+        }
+
+        Ok(overrides)
+    }
+}
+
+pub fn is_player_ignored(player_name: &str) -> bool {
+    options::get(get_ignore_key(player_name)).is_some_and(|value| value == "true")
+}
+
+pub fn set_player_ignored(player_name: &str, ignored: bool) {
+    let value = if ignored {
+        "true".to_string()
+    } else {
+        String::new()
+    };
+    options::set(get_ignore_key(player_name), value);
+}
+
+fn get_screen_key(screen_name: &str) -> String {
+    get_key("cef-local", &get_server_id(), screen_name)
+}
+
+fn get_ignore_key(player_name: &str) -> String {
+    get_key("cef-ignore", &get_server_id(), player_name)
+}
+
+fn get_key(prefix: &str, server_id: &str, name: &str) -> String {
+    format!("{}-{}-{}", prefix, sanitize(server_id), sanitize(name))
+}
+
+fn get_server_id() -> String {
+    unsafe {
+        if Server.IsSinglePlayer != 0 {
+            "singleplayer".to_string()
+        } else {
+            format!("{}:{}", Server.Address, Server.Port)
+        }
+    }
+}
+
+/// options keys are `key=value` lines, keep them boring
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_local_overrides_string() {
+    let overrides = LocalOverrides {
+        muted: true,
+        hidden: false,
+        volume_offset: -0.25,
+        ignored: true,
+    };
+    assert_eq!(overrides.to_string(), "mute,offset=-0.25");
+
+    // ignored isn't saved
+    assert_eq!(
+        "mute,offset=-0.25".parse::<LocalOverrides>().unwrap(),
+        LocalOverrides {
+            ignored: false,
+            ..overrides
+        }
+    );
+
+    assert_eq!(
+        " hide , mute ".parse::<LocalOverrides>().unwrap(),
+        LocalOverrides {
+            muted: true,
+            hidden: true,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        "".parse::<LocalOverrides>().unwrap(),
+        LocalOverrides::default()
+    );
+    assert_eq!(LocalOverrides::default().to_string(), "");
+
+    assert!("offset=2".parse::<LocalOverrides>().is_err());
+    assert!("offset=abc".parse::<LocalOverrides>().is_err());
+    assert!("loud".parse::<LocalOverrides>().is_err());
+}
+
+#[test]
+fn test_local_overrides_volume() {
+    let mut overrides = LocalOverrides {
+        volume_offset: -0.5,
+        ..Default::default()
+    };
+    assert!((overrides.get_volume_multiplier() - 0.5).abs() < f32::EPSILON);
+
+    overrides.ignored = true;
+    assert!(overrides.is_muted());
+    assert!(overrides.is_hidden());
+    assert!(overrides.get_volume_multiplier().abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_local_overrides_keys() {
+    assert_eq!(
+        get_key("cef-local", "127.0.0.1:25565", "Lobby Screen"),
+        "cef-local-127.0.0.1_25565-lobby_screen"
+    );
+    assert_eq!(
+        get_key("cef-ignore", "singleplayer", "Spiral=P"),
+        "cef-ignore-singleplayer-spiral_p"
+    );
+}
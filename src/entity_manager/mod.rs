@@ -1,9 +1,15 @@
+pub mod animation;
+pub mod attachment;
+pub mod capture;
 mod cef_paint;
 mod context_handler;
 mod entity;
 mod entity_builder;
 mod helpers;
+pub mod hud;
+pub mod local_overrides;
 mod model;
+pub mod popup;
 mod render_model_hook;
 
 use std::{
@@ -21,10 +27,15 @@ use futures::{
 };
 use tracing::{debug, warn};
 
-pub use self::{cef_paint::cef_paint_callback, entity::CefEntity, entity_builder::EntityBuilder};
+pub use self::{
+    cef_paint::cef_paint_callback,
+    entity::{CefEntity, TextureRect},
+    entity_builder::EntityBuilder,
+};
 use self::{context_handler::ContextHandler, model::CefModel};
 use crate::{
-    cef::{Cef, CefEvent, RustRefBrowser},
+    cef::{Browser, Cef, CefEvent},
+    diagnostics::{DebugState, EntityState},
     error::{Error, Result, bail},
     player::PlayerTrait,
 };
@@ -66,6 +77,7 @@ pub struct EntityManager {
 
     cef_event_page_loaded: Option<RemoteHandle<()>>,
     cef_event_title_change: Option<RemoteHandle<()>>,
+    cef_event_address_change: Option<RemoteHandle<()>>,
 }
 
 impl EntityManager {
@@ -74,6 +86,7 @@ impl EntityManager {
             context_handler: ContextHandler::new(),
             cef_event_page_loaded: None,
             cef_event_title_change: None,
+            cef_event_address_change: None,
         }
     }
 
@@ -82,6 +95,7 @@ impl EntityManager {
 
         self.context_handler.initialize();
         render_model_hook::initialize();
+        hud::initialize();
         MODEL.with(|cell| {
             let mut slot = cell.borrow_mut();
             if slot.is_none() {
@@ -129,6 +143,26 @@ impl EntityManager {
         .remote_handle();
         async_manager::spawn_local_on_main_thread(f);
         self.cef_event_title_change = Some(remote_handle);
+
+        let mut event_listener = Cef::create_event_listener();
+        let (f, remote_handle) = async move {
+            while let Ok(event) = event_listener.recv().await {
+                if let CefEvent::BrowserAddressChange(browser, url) = event {
+                    let browser_id = browser.get_identifier();
+
+                    if let Err(e) = EntityManager::with_by_browser_id(browser_id, |entity| {
+                        entity.player.on_address_change(entity.id, &browser, url);
+                        entity.apply_userscripts(&browser);
+                        Ok(())
+                    }) {
+                        warn!("{}", e);
+                    }
+                }
+            }
+        }
+        .remote_handle();
+        async_manager::spawn_local_on_main_thread(f);
+        self.cef_event_address_change = Some(remote_handle);
     }
 
     pub fn on_new_map_loaded(&mut self) {
@@ -143,9 +177,12 @@ impl EntityManager {
         debug!("shutdown entity_manager");
 
         self.context_handler.shutdown();
+        hud::shutdown();
         render_model_hook::shutdown();
+        capture::shutdown();
         self.cef_event_page_loaded.take();
         self.cef_event_title_change.take();
+        self.cef_event_address_change.take();
 
         async_manager::block_on_local(async {
             Self::remove_all_entities().await.unwrap();
@@ -175,13 +212,19 @@ impl EntityManager {
         })
     }
 
-    pub fn get_browser_by_entity_id(entity_id: usize) -> Result<RustRefBrowser> {
+    pub fn get_browser_by_entity_id(entity_id: usize) -> Result<Browser> {
         ENTITIES.with(|entities| {
             let entities = &*entities.borrow();
 
             if let Some(entity) = entities.get(&entity_id) {
                 if let Some(browser) = &entity.browser {
                     Ok(browser.clone())
+                } else if let Some(browser) = entity
+                    .texture_source
+                    .and_then(|source_id| entities.get(&source_id))
+                    .and_then(|source| source.browser.as_ref())
+                {
+                    Ok(browser.clone())
                 } else {
                     bail!("no browser for entity {}", entity_id);
                 }
@@ -191,7 +234,70 @@ impl EntityManager {
         })
     }
 
+    /// create an entity that shows `source_id`'s browser, see
+    /// [`CefEntity::register_linked`]
+    pub fn create_linked(source_id: usize, name: Option<String>) -> Result<usize> {
+        if let Some(name) = &name
+            && name.get_entity_id().is_ok()
+        {
+            bail!("a screen named {:?} already exists", name);
+        }
+
+        let entity_id = Self::get_new_id();
+        ENTITIES.with(|cell| {
+            let entities = &mut *cell.borrow_mut();
+
+            let source = entities
+                .get(&source_id)
+                .ok_or_else(|| format!("No entity found with id {source_id}!"))?;
+            if source.texture_source.is_some() {
+                bail!("can't link to a screen that is already linked");
+            }
+
+            let mut entity = CefEntity::register_linked(entity_id, name.clone(), source);
+            if let Some(browser) = &source.browser {
+                // don't show the whole texture until the next paint
+                let (width, height) = Cef::get_browser_size(browser);
+                entity.update_uv(width.into(), height.into());
+            }
+            entities.insert(entity_id, entity);
+
+            Ok::<_, Error>(())
+        })?;
+
+        if let Some(name) = name {
+            debug!("created linked entity {:?} with id {}", name, entity_id);
+
+            NAME_TO_ID.with(|cell| {
+                let name_to_id = &mut *cell.borrow_mut();
+                name_to_id.insert(name, entity_id);
+            });
+        } else {
+            debug!("created linked entity with id {}", entity_id);
+        }
+
+        Ok(entity_id)
+    }
+
+    /// a screen showing a `FakeBrowser`, for tests without cef
+    #[cfg(test)]
+    pub fn create_fake(
+        player: crate::player::Player,
+        queue: std::collections::VecDeque<crate::player::Player>,
+    ) -> (usize, crate::cef::FakeBrowser) {
+        let entity_id = Self::get_new_id();
+        let browser = crate::cef::FakeBrowser::new(c_int::try_from(entity_id).unwrap());
+
+        let mut entity = CefEntity::register_fake(entity_id, player, queue);
+        entity.attach_browser(Browser::Fake(browser.clone()));
+        ENTITIES.with(|cell| cell.borrow_mut().insert(entity_id, entity));
+
+        (entity_id, browser)
+    }
+
     pub async fn remove_entity(entity_id: usize) -> Result<()> {
+        Self::remove_linked_entities(entity_id);
+
         NAME_TO_ID.with(|cell| {
             let name_to_id = &mut *cell.borrow_mut();
             let mut keys_to_remove: Vec<String> = name_to_id
@@ -235,10 +341,39 @@ impl EntityManager {
         Ok(())
     }
 
+    /// wall tiles and mirrors can't outlive the texture they draw
+    fn remove_linked_entities(source_id: usize) {
+        let linked_ids: Vec<usize> = ENTITIES.with(|cell| {
+            let entities = &mut *cell.borrow_mut();
+
+            let linked_ids = entities
+                .values()
+                .filter(|entity| entity.texture_source == Some(source_id))
+                .map(|entity| entity.id)
+                .collect::<Vec<_>>();
+            for id in &linked_ids {
+                entities.remove(id);
+            }
+
+            linked_ids
+        });
+
+        NAME_TO_ID.with(|cell| {
+            let name_to_id = &mut *cell.borrow_mut();
+            name_to_id.retain(|_name, id| !linked_ids.contains(id));
+        });
+    }
+
     pub async fn remove_all_entities() -> Result<()> {
-        // don't drain here because we remove them in remove_entity()
-        let entity_ids: Vec<usize> =
-            Self::with_all_entities(|entities| entities.keys().copied().collect());
+        // don't drain here because we remove them in remove_entity(),
+        // which also removes the linked entities
+        let entity_ids: Vec<usize> = Self::with_all_entities(|entities| {
+            entities
+                .values()
+                .filter(|entity| entity.texture_source.is_none())
+                .map(|entity| entity.id)
+                .collect()
+        });
 
         let mut entity_ids: FuturesUnordered<_> = entity_ids
             .iter()
@@ -257,7 +392,7 @@ impl EntityManager {
         Ok(())
     }
 
-    fn on_browser_close(browser: &RustRefBrowser) {
+    fn on_browser_close(browser: &Browser) {
         let browser_id = browser.get_identifier();
 
         BROWSER_ID_TO_ENTITY_ID.with(|ids| {
@@ -366,6 +501,58 @@ impl EntityManager {
         closest
     }
 
+    /// copy our maps for `cef debug state` and crash reports, leaving
+    /// out any that were borrowed so this never panics
+    pub fn fill_debug_state(state: &mut DebugState) {
+        state.entities = ENTITIES.with(|cell| {
+            let entities = cell.try_borrow().ok()?;
+
+            Some(
+                entities
+                    .values()
+                    .map(|entity| {
+                        let position = entity.entity.Position;
+
+                        EntityState {
+                            id: entity.id,
+                            name: entity.name.clone(),
+                            type_name: entity.player.type_name(),
+                            url: entity.player.get_url(),
+                            browser_id: entity.browser.as_ref().map(Browser::get_identifier),
+                            texture_source: entity.texture_source,
+                            position: (position.x, position.y, position.z),
+                            scale: entity.get_scale(),
+                            queue_len: entity.queue.len(),
+                            should_send: entity.should_send,
+                            has_update_loop: entity.player.has_update_loop(),
+                        }
+                    })
+                    .collect(),
+            )
+        });
+
+        state.name_to_id = NAME_TO_ID.with(|cell| {
+            let name_to_id = cell.try_borrow().ok()?;
+            Some(
+                name_to_id
+                    .iter()
+                    .map(|(name, &id)| (name.clone(), id))
+                    .collect(),
+            )
+        });
+
+        state.browser_id_to_entity_id = BROWSER_ID_TO_ENTITY_ID.with(|cell| {
+            let ids = cell.try_borrow().ok()?;
+            Some(
+                ids.iter()
+                    .map(|(&browser_id, &id)| (browser_id, id))
+                    .collect(),
+            )
+        });
+
+        state.timelapses = capture::get_timelapse_entity_ids();
+    }
+
     pub fn with_all_entities<F, T>(f: F) -> T
     where
         F: FnOnce(&mut HashMap<usize, CefEntity>) -> T,
@@ -409,8 +596,12 @@ impl TargetEntity for String {
 }
 
 impl TargetEntity for Vec3 {
+    /// wall tiles and mirrors have no browser or player of their own, so
+    /// looking at one means the screen it shows
     fn get_entity_id(&self) -> Result<usize> {
-        EntityManager::with_closest(*self, |closest_entity| Ok(closest_entity.id))
+        EntityManager::with_closest(*self, |closest_entity| {
+            Ok(closest_entity.texture_source.unwrap_or(closest_entity.id))
+        })
     }
 }
 
@@ -419,3 +610,37 @@ impl TargetEntity for Box<dyn TargetEntity> {
         self.as_ref().get_entity_id()
     }
 }
+
+#[test]
+fn test_target_linked_source() {
+    use std::collections::VecDeque;
+
+    use crate::player::{Player, WebPlayer};
+
+    let (source_id, _browser) =
+        EntityManager::create_fake(Player::Web(WebPlayer::blank_page()), VecDeque::new());
+    let (mirror_id, _browser) =
+        EntityManager::create_fake(Player::Web(WebPlayer::blank_page()), VecDeque::new());
+
+    EntityManager::with_entity(source_id, |entity| {
+        entity.entity.Position.set(10.0, 0.0, 0.0);
+        Ok(())
+    })
+    .unwrap();
+    EntityManager::with_entity(mirror_id, |entity| {
+        // like EntityManager::create_linked
+        entity.texture_source = Some(source_id);
+        entity.browser = None;
+        entity.entity.Position.set(1.0, 0.0, 0.0);
+        Ok(())
+    })
+    .unwrap();
+
+    let eye_position = Vec3::new(0.0, 0.0, 0.0);
+    assert_eq!(
+        EntityManager::with_closest(eye_position, |entity| Ok(entity.id)).unwrap(),
+        mirror_id
+    );
+    assert_eq!(eye_position.get_entity_id().unwrap(), source_id);
+    assert!(EntityManager::get_browser_by_entity_id(eye_position.get_entity_id().unwrap()).is_ok());
+}
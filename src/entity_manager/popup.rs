@@ -0,0 +1,239 @@
+//! what happens when a page calls `window.open` or follows a
+//! `target=_blank` link, there's no window to open offscreen
+
+use std::{
+    cell::{Cell, RefCell},
+    ffi::CStr,
+    fmt::{self, Display},
+    os::raw::{c_char, c_int},
+    str::FromStr,
+};
+
+use classicube_helpers::async_manager;
+use ncollide3d::na::Vector3;
+use tracing::{debug, warn};
+
+use super::{ENTITIES, EntityBuilder, EntityManager};
+use crate::{
+    cef::{Cef, RustRefBrowser},
+    error::{Error, Result, bail, ensure},
+    helpers::{get_screen_axes, vec3_to_vector3},
+    options::POPUPS,
+    player::{Player, PlayerTrait, WebPlayer},
+};
+
+/// popup screens open at once, so a page can't fill the map with them
+const MAX_POPUP_SCREENS: usize = 4;
+
+/// blocks between a screen and its popup
+const POPUP_GAP: f32 = 0.25;
+
+// entity ids of popup screens, some may have been closed since
+thread_local!(
+    static POPUP_IDS: RefCell<Vec<usize>> = RefCell::default();
+);
+
+// popup screens still waiting on their browser, counted so a page calling
+// window.open in a loop can't get past MAX_POPUP_SCREENS
+thread_local!(
+    static PENDING_POPUPS: Cell<usize> = const { Cell::new(0) };
+);
+
+/// holds a popup slot until the screen is made or fails
+struct PendingPopup;
+
+impl PendingPopup {
+    fn reserve() -> Result<Self> {
+        let open_count = POPUP_IDS.with_borrow_mut(|ids| {
+            ENTITIES.with_borrow(|entities| ids.retain(|id| entities.contains_key(id)));
+            ids.len()
+        });
+        let pending_count = PENDING_POPUPS.get();
+        ensure!(
+            open_count + pending_count < MAX_POPUP_SCREENS,
+            "already {} popup screens open",
+            MAX_POPUP_SCREENS
+        );
+
+        PENDING_POPUPS.set(pending_count + 1);
+        Ok(Self)
+    }
+}
+
+impl Drop for PendingPopup {
+    fn drop(&mut self) {
+        PENDING_POPUPS.set(PENDING_POPUPS.get().saturating_sub(1));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopupPolicy {
+    /// ignore it
+    Block,
+    /// replace the page that opened it
+    SameScreen,
+    /// a new screen next to the one that opened it, only for you, and
+    /// only when clicked
+    NewScreen,
+}
+
+impl FromStr for PopupPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "same" | "same-screen" => Ok(Self::SameScreen),
+            "new" | "new-screen" => Ok(Self::NewScreen),
+            _ => bail!(
+                "unknown popup policy {:?}, use block, same-screen or new-screen",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for PopupPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Block => "block",
+            Self::SameScreen => "same-screen",
+            Self::NewScreen => "new-screen",
+        })
+    }
+}
+
+/// returns whether cef should load the url in the frame that opened it
+#[tracing::instrument(fields(browser = browser.get_identifier()))]
+pub extern "C" fn on_before_popup_callback(
+    browser: RustRefBrowser,
+    target_url: *const c_char,
+    user_gesture: bool,
+) -> bool {
+    let browser_id = browser.get_identifier();
+    let url = unsafe { CStr::from_ptr(target_url) }
+        .to_string_lossy()
+        .to_string();
+    debug!(
+        "on_before_popup {} {} user_gesture={}",
+        browser_id, url, user_gesture
+    );
+
+    let policy = POPUPS.get().unwrap_or_else(|e| {
+        warn!("{}", e);
+        POPUPS.default()
+    });
+
+    match policy {
+        PopupPolicy::Block => false,
+
+        PopupPolicy::SameScreen => true,
+
+        // pages opening screens on their own would be spam
+        PopupPolicy::NewScreen if !user_gesture => false,
+
+        PopupPolicy::NewScreen => {
+            async_manager::spawn_local_on_main_thread(async move {
+                if let Err(e) = create_popup_screen(browser_id, &url).await {
+                    warn!("popup from browser {}: {}", browser_id, e);
+                }
+            });
+
+            false
+        }
+    }
+}
+
+async fn create_popup_screen(parent_browser_id: c_int, url: &str) -> Result<()> {
+    let player = Player::Web(WebPlayer::from_input(url)?);
+
+    let _pending = PendingPopup::reserve()?;
+
+    let entity_builder = EntityManager::with_by_browser_id(parent_browser_id, |parent| {
+        let scale = parent.get_scale();
+        ensure!(scale > 0.0, "hidden screens can't open popups");
+
+        let (width, height) = parent.get_size();
+        let position = get_popup_position(
+            vec3_to_vector3(&parent.entity.Position),
+            parent.entity.RotX,
+            parent.entity.RotY,
+            scale * f32::from(width),
+        );
+
+        let mut entity_builder = EntityBuilder::new(player)
+            // everyone's page opens its own popup
+            .should_send(false)
+            .position(position.x, position.y, position.z)
+            .rotation(parent.entity.RotX, parent.entity.RotY)
+            .scale(scale)
+            .size(width, height);
+
+        if let Some(browser) = &parent.browser {
+            let (browser_width, browser_height) = Cef::get_browser_size(browser);
+            entity_builder = entity_builder.resolution(browser_width, browser_height);
+
+            // logins from a popup should be seen by the page that opened it
+            if let Some(profile) = Cef::get_browser_profile(browser) {
+                entity_builder = entity_builder.profile(profile);
+            }
+        }
+
+        if let Some(creator) = &parent.creator {
+            entity_builder = entity_builder.creator(creator.clone());
+        }
+
+        Ok(entity_builder)
+    })?;
+
+    let entity_id = entity_builder.create().await?;
+    debug!(
+        "popup from browser {} opened as entity {}",
+        parent_browser_id, entity_id
+    );
+    POPUP_IDS.with_borrow_mut(|ids| ids.push(entity_id));
+
+    Ok(())
+}
+
+/// to the right of the parent, facing the same way
+fn get_popup_position(position: Vector3<f32>, pitch: f32, yaw: f32, width: f32) -> Vector3<f32> {
+    let (right, _up) = get_screen_axes(pitch, yaw);
+    position + right * (width + POPUP_GAP)
+}
+
+#[test]
+fn test_popup_policy() {
+    for (input, policy) in [
+        ("block", PopupPolicy::Block),
+        ("Same", PopupPolicy::SameScreen),
+        ("same-screen", PopupPolicy::SameScreen),
+        ("new-screen", PopupPolicy::NewScreen),
+    ] {
+        assert_eq!(input.parse::<PopupPolicy>().unwrap(), policy);
+        assert_eq!(policy.to_string().parse::<PopupPolicy>().unwrap(), policy);
+    }
+    assert!("window".parse::<PopupPolicy>().is_err());
+}
+
+#[test]
+fn test_get_popup_position() {
+    // facing -z, right is -x
+    let position = get_popup_position(Vector3::new(10.0, 5.0, 10.0), 0.0, 0.0, 4.0);
+    assert!((position - Vector3::new(5.75, 5.0, 10.0)).norm() < 0.001);
+
+    // turned around, right is +x
+    let position = get_popup_position(Vector3::new(10.0, 5.0, 10.0), 0.0, 180.0, 4.0);
+    assert!((position - Vector3::new(14.25, 5.0, 10.0)).norm() < 0.001);
+}
+
+#[test]
+fn test_pending_popups() {
+    let pending = (0..MAX_POPUP_SCREENS)
+        .map(|_| PendingPopup::reserve().unwrap())
+        .collect::<Vec<_>>();
+    assert!(PendingPopup::reserve().is_err());
+
+    drop(pending);
+    assert!(PendingPopup::reserve().is_ok());
+}
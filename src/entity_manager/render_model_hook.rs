@@ -20,6 +20,8 @@ fn render_model(local_player_entity: *mut Entity, delta: f32, t: f32, original:
         let entities = &mut *entities.borrow_mut();
 
         for entity in entities.values_mut() {
+            entity.update_animation(delta);
+            entity.update_attachment(t);
             entity.render_model();
         }
     });
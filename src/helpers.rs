@@ -1,12 +1,24 @@
 use std::time::Duration;
 
 use classicube_sys::Vec3;
-use ncollide3d::na::Vector3;
+use ncollide3d::na::{UnitQuaternion, Vector3};
 
 pub fn vec3_to_vector3(v: &Vec3) -> Vector3<f32> {
     Vector3::new(v.x, v.y, v.z)
 }
 
+/// unit vectors pointing right and up along a screen's face, as seen
+/// from the front
+pub fn get_screen_axes(pitch: f32, yaw: f32) -> (Vector3<f32>, Vector3<f32>) {
+    // same rotation as chat's get_click_coords
+    let rot = UnitQuaternion::from_euler_angles(-pitch.to_radians(), -yaw.to_radians(), 0.0);
+
+    let right = rot.transform_vector(&-Vector3::x());
+    let up = rot.transform_vector(&Vector3::y());
+
+    (right, up)
+}
+
 // fn vector3_to_vec3(v: &Vector3<f32>) -> Vec3 {
 //     Vec3::new(v.x, v.y, v.z)
 // }
@@ -37,3 +49,88 @@ fn test_format_duration() {
         assert_eq!(&format_duration(*a), b);
     }
 }
+
+/// `512 B`, `1.5 KB`, `20.0 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[test]
+fn test_format_size() {
+    for (a, b) in &[
+        (0, "0 B"),
+        (1023, "1023 B"),
+        (1024, "1.0 KB"),
+        (1536, "1.5 KB"),
+        (20 * 1024 * 1024, "20.0 MB"),
+        (3 * 1024 * 1024 * 1024, "3.0 GB"),
+    ] {
+        assert_eq!(&format_size(*a), b);
+    }
+}
+
+/// parses `90`, `90s`, `500ms`, `5m`, `1h` or `1m30s`
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f32 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "ms" => number / 1000.0,
+            "" | "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 60.0 * 60.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+
+        total += Duration::try_from_secs_f32(seconds).ok()?;
+    }
+
+    Some(total)
+}
+
+#[test]
+fn test_parse_duration() {
+    for (a, b) in &[
+        ("2", Some(Duration::from_secs(2))),
+        ("2s", Some(Duration::from_secs(2))),
+        ("1.5s", Some(Duration::from_millis(1500))),
+        ("500ms", Some(Duration::from_millis(500))),
+        ("5m", Some(Duration::from_secs(5 * 60))),
+        ("1h", Some(Duration::from_secs(60 * 60))),
+        ("1m30s", Some(Duration::from_secs(90))),
+        ("", None),
+        ("s", None),
+        ("5x", None),
+        ("-5s", None),
+    ] {
+        assert_eq!(&parse_duration(a), b, "{a:?}");
+    }
+}
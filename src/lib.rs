@@ -14,9 +14,12 @@
     clippy::unused_self
 )]
 
+mod adblock;
 mod api;
+mod audio;
 mod cef;
 mod chat;
+mod diagnostics;
 mod entity_manager;
 mod error;
 mod helpers;
@@ -25,6 +28,8 @@ mod options;
 mod panic;
 mod player;
 mod plugin;
+mod scheduler;
+mod userscripts;
 
 use std::{os::raw::c_int, ptr};
 
@@ -106,6 +111,8 @@ pub static mut Plugin_Component: IGameComponent = IGameComponent {
 
 test_noop_static!(Entities);
 test_noop_static!(Camera);
+test_noop_static!(World);
+test_noop_static!(Blocks);
 
 test_noop_fn!(Entity_SetModel);
 test_noop_fn!(Options_Get);
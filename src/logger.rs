@@ -1,24 +1,65 @@
-use std::{fs::File, io::BufWriter, sync::Once};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{Mutex, Once, OnceLock},
+};
 
-use tracing_flame::FlameLayer;
+use tracing::span;
+use tracing_flame::{FlameLayer, FlushGuard};
 use tracing_subscriber::{
+    Registry,
     filter::EnvFilter,
     fmt::{Layer, time::SystemTime},
+    layer::{Context, Layered},
     prelude::*,
+    reload,
 };
 
-enum Guard {
-    #[allow(dead_code)]
-    Appender(tracing_appender::non_blocking::WorkerGuard),
-    #[allow(dead_code)]
-    Flame(tracing_flame::FlushGuard<BufWriter<File>>),
+use crate::error::{Result, ResultExt, bail};
+
+type FlameWriter = BufWriter<File>;
+type FilterSubscriber = Layered<reload::Layer<Profiler, Registry>, Registry>;
+
+/// a flame layer while profiling
+///
+/// not an `Option<FlameLayer>` because a `None` layer hints that nothing
+/// should be enabled, which would turn off all logging
+struct Profiler(Option<FlameLayer<Registry, FlameWriter>>);
+
+impl tracing_subscriber::Layer<Registry> for Profiler {
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, Registry>) {
+        if let Some(flame) = &self.0 {
+            flame.on_enter(id, ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, Registry>) {
+        if let Some(flame) = &self.0 {
+            flame.on_exit(id, ctx);
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, Registry>) {
+        if let Some(flame) = &self.0 {
+            flame.on_close(id, ctx);
+        }
+    }
 }
 
 // Held for the lifetime of the process. The tracing subscriber is
 // installed exactly once (via `Once`); on plugin reload we must keep
 // the appender's worker thread alive so log lines after the second
 // `Init` still reach `cef.log`.
-static mut GUARDS: Option<Vec<Guard>> = None;
+static mut GUARD: Option<tracing_appender::non_blocking::WorkerGuard> = None;
+
+// Also live as long as the subscriber, so `cef log` and `cef profile`
+// keep working after a Free → Init reload.
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, FilterSubscriber>> = OnceLock::new();
+static FLAME_HANDLE: OnceLock<reload::Handle<Profiler, Registry>> = OnceLock::new();
+
+// set while a profile is recording, with the file it's going to
+static FLAME_GUARD: Mutex<Option<(FlushGuard<FlameWriter>, PathBuf)>> = Mutex::new(None);
 
 pub fn initialize(debug: bool, module_filter: Option<&str>, flame: bool) {
     static ONCE: Once = Once::new();
@@ -43,20 +84,25 @@ pub fn initialize(debug: bool, module_filter: Option<&str>, flame: bool) {
             .with_default_directive(default_directive)
             .from_env_lossy();
 
-        let mut guards = Vec::with_capacity(2);
-
         let (file_writer, guard) =
             tracing_appender::non_blocking(tracing_appender::rolling::never(".", "cef.log"));
-        guards.push(Guard::Appender(guard));
-
-        let subscriber = tracing_subscriber::fmt()
-            .with_env_filter(filter)
-            .with_target(false)
-            .with_thread_ids(false)
-            .with_thread_names(false)
-            .with_ansi(true)
-            .without_time()
-            .finish()
+
+        // the flame layer goes under the filter so profiles only have
+        // what's currently logged
+        let (flame_layer, flame_handle) = reload::Layer::new(Profiler(None));
+        let (filter_layer, filter_handle) = reload::Layer::new(filter);
+
+        let result = tracing_subscriber::registry()
+            .with(flame_layer)
+            .with(filter_layer)
+            .with(
+                Layer::default()
+                    .with_target(false)
+                    .with_thread_ids(false)
+                    .with_thread_names(false)
+                    .with_ansi(true)
+                    .without_time(),
+            )
             .with(
                 Layer::default()
                     .with_writer(file_writer)
@@ -65,34 +111,95 @@ pub fn initialize(debug: bool, module_filter: Option<&str>, flame: bool) {
                     .with_thread_names(false)
                     .with_ansi(false)
                     .with_timer(SystemTime),
-            );
-
-        let result = if flame {
-            let (flame_layer, guard) = FlameLayer::with_file("./flame.log").unwrap();
-            guards.push(Guard::Flame(guard));
-
-            subscriber.with(flame_layer).try_init()
-        } else {
-            subscriber.try_init()
-        };
+            )
+            .try_init();
         if let Err(e) = result {
             eprintln!("failed to init tracing subscriber: {e}");
         }
 
+        let _ignore = FILTER_HANDLE.set(filter_handle);
+        let _ignore = FLAME_HANDLE.set(flame_handle);
+
         unsafe {
-            GUARDS = Some(guards);
+            GUARD = Some(guard);
         }
     });
+
+    if flame && let Err(e) = start_profile("flame.log") {
+        eprintln!("failed to start flame profile: {e}");
+    }
+}
+
+/// replaces the log filter, in `RUST_LOG` syntax like `info,classicube_cef_plugin=debug`
+pub fn set_filter(filter: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(filter).chain_err(|| "bad log filter")?;
+    FILTER_HANDLE
+        .get()
+        .chain_err(|| "logger not initialized")?
+        .reload(filter)
+        .chain_err(|| "couldn't change log filter")
+}
+
+pub fn get_filter() -> Result<String> {
+    FILTER_HANDLE
+        .get()
+        .chain_err(|| "logger not initialized")?
+        .with_current(ToString::to_string)
+        .chain_err(|| "couldn't read log filter")
+}
+
+/// records folded stacks of every span to `path` until `stop_profile`
+pub fn start_profile<P: AsRef<Path>>(path: P) -> Result<()> {
+    let mut flame_guard = FLAME_GUARD.lock().map_err(|_| "flame guard poisoned")?;
+    if flame_guard.is_some() {
+        bail!("already profiling");
+    }
+
+    let path = path.as_ref();
+    let (flame_layer, guard) =
+        FlameLayer::with_file(path).chain_err(|| "couldn't create flame file")?;
+    FLAME_HANDLE
+        .get()
+        .chain_err(|| "logger not initialized")?
+        .reload(Profiler(Some(flame_layer)))
+        .chain_err(|| "couldn't start flame layer")?;
+    *flame_guard = Some((guard, path.to_path_buf()));
+
+    Ok(())
 }
 
-/// Flush and drop the appender guards. Only call this from the panic hook
-/// just before `process::abort()` — the abort would otherwise skip the
-/// `WorkerGuard` destructor and the file writer would lose buffered log
-/// lines. **Don't** call this from plugin `Free`: a subsequent `Init`
-/// won't re-arm the appender (the `Once` doesn't re-run), and we'd lose
-/// file logging for the rest of the process.
+/// returns where the profile was saved
+pub fn stop_profile() -> Result<PathBuf> {
+    let (guard, path) = FLAME_GUARD
+        .lock()
+        .map_err(|_| "flame guard poisoned")?
+        .take()
+        .chain_err(|| "not profiling")?;
+
+    if let Some(handle) = FLAME_HANDLE.get() {
+        handle
+            .reload(Profiler(None))
+            .chain_err(|| "couldn't stop flame layer")?;
+    }
+    guard.flush().chain_err(|| "couldn't flush flame file")?;
+
+    Ok(path)
+}
+
+/// Flush and drop the appender guard and any running profile. Only call
+/// this from the panic hook just before `process::abort()` — the abort
+/// would otherwise skip the `WorkerGuard` destructor and the file writer
+/// would lose buffered log lines. **Don't** call this from plugin `Free`:
+/// a subsequent `Init` won't re-arm the appender (the `Once` doesn't
+/// re-run), and we'd lose file logging for the rest of the process.
 pub fn flush_for_abort() {
+    if let Ok(mut flame_guard) = FLAME_GUARD.try_lock()
+        && let Some((guard, _path)) = flame_guard.take()
+    {
+        let _ignore = guard.flush();
+    }
+
     unsafe {
-        GUARDS = None;
+        GUARD = None;
     }
 }
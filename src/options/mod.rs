@@ -1,12 +1,15 @@
+mod registry;
 mod rust_option;
 
 use std::{cell::Cell, ffi::CString, os::raw::c_char};
 
 use classicube_sys::{Options_Get, Options_Set, OwnedString, STRING_SIZE, cc_string};
 
+pub use self::registry::{OptionInfo, REGISTRY, find};
 use self::rust_option::RustOption;
+use crate::{audio::AudioOutput, cef::ProfileName, entity_manager::popup::PopupPolicy};
 
-fn get<S: Into<Vec<u8>>>(key: S) -> Option<String> {
+pub fn get<S: Into<Vec<u8>>>(key: S) -> Option<String> {
     let c_key = CString::new(key).unwrap();
     let c_default = CString::new("").unwrap();
 
@@ -30,7 +33,7 @@ fn get<S: Into<Vec<u8>>>(key: S) -> Option<String> {
     }
 }
 
-fn set<S: Into<Vec<u8>>>(key: S, value: String) {
+pub fn set<S: Into<Vec<u8>>>(key: S, value: String) {
     let c_key = CString::new(key).unwrap();
 
     let cc_string_value = OwnedString::new(value);
@@ -56,3 +59,15 @@ pub const VOLUME: RustOption<f32> = option!("cef-volume", 1.0, f32);
 pub const MAP_THEME_VOLUME: RustOption<f32> = option!("cef-map-theme-volume", 0.4, f32);
 pub const FRAME_RATE: RustOption<u16> = option!("cef-frame-rate", 30, u16);
 pub const SUBTITLES: RustOption<bool> = option!("cef-subtitles", true, bool);
+pub const OCCLUSION: RustOption<bool> = option!("cef-occlusion", true, bool);
+pub const OCCLUSION_LOW_PASS: RustOption<bool> = option!("cef-occlusion-low-pass", true, bool);
+pub const NATIVE_AUDIO: RustOption<bool> = option!("cef-native-audio", false, bool);
+pub const AUDIO_OUTPUT: RustOption<AudioOutput> =
+    option!("cef-audio-output", AudioOutput::Device, AudioOutput);
+pub const MIXER_DUCKING: RustOption<bool> = option!("cef-mixer-ducking", true, bool);
+pub const MIXER_FOCUS: RustOption<bool> = option!("cef-mixer-focus", false, bool);
+pub const PROFILE: RustOption<ProfileName> =
+    option!("cef-profile", ProfileName::DEFAULT, ProfileName);
+pub const POPUPS: RustOption<PopupPolicy> =
+    option!("cef-popups", PopupPolicy::SameScreen, PopupPolicy);
+pub const ADBLOCK: RustOption<bool> = option!("cef-adblock", true, bool);
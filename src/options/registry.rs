@@ -0,0 +1,245 @@
+//! every option `cef config` can see, with what it needs to show and check them
+
+use futures::{FutureExt, future::LocalBoxFuture};
+
+use super::{
+    ADBLOCK, AUDIO_OUTPUT, AUTOPLAY_MAP_THEMES, FRAME_RATE, MAP_THEME_VOLUME, MIXER_DUCKING,
+    MIXER_FOCUS, MUTE_LOSE_FOCUS, NATIVE_AUDIO, OCCLUSION, OCCLUSION_LOW_PASS, POPUPS, PROFILE,
+    SUBTITLES, VOLUME, rust_option::AnyOption,
+};
+use crate::{
+    cef::Cef,
+    chat::hidden_communication::CURRENT_MAP_THEME,
+    entity_manager::EntityManager,
+    error::{Result, ResultExt, bail, ensure},
+    player::mixer,
+};
+
+type OnChange = fn() -> LocalBoxFuture<'static, Result<()>>;
+
+pub struct OptionInfo {
+    option: &'static dyn AnyOption,
+
+    pub description: &'static str,
+    pub units: Option<&'static str>,
+    /// inclusive
+    pub range: Option<(f32, f32)>,
+    /// ran after the new value is saved, an error puts the old value back
+    on_change: Option<OnChange>,
+}
+
+impl OptionInfo {
+    const fn new(option: &'static dyn AnyOption, description: &'static str) -> Self {
+        Self {
+            option,
+            description,
+            units: None,
+            range: None,
+            on_change: None,
+        }
+    }
+
+    const fn units(mut self, units: &'static str) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    const fn range(mut self, min: f32, max: f32) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    const fn on_change(mut self, on_change: OnChange) -> Self {
+        self.on_change = Some(on_change);
+        self
+    }
+
+    /// what you type after `cef config`
+    pub fn name(&self) -> &'static str {
+        self.option.key().trim_start_matches("cef-")
+    }
+
+    pub fn get(&self) -> Result<String> {
+        self.option.get_string()
+    }
+
+    pub fn default(&self) -> String {
+        self.option.default_string()
+    }
+
+    pub fn format_value(&self, value: &str) -> String {
+        match self.units {
+            Some(units) => format!("{value} {units}"),
+            None => value.to_string(),
+        }
+    }
+
+    fn validate(&self, value: &str) -> Result<()> {
+        if let Some((min, max)) = self.range {
+            let number = value
+                .parse::<f32>()
+                .chain_err(|| format!("{} must be a number", self.name()))?;
+            ensure!(
+                (min..=max).contains(&number),
+                "{} must be between {} and {}",
+                self.name(),
+                min,
+                max
+            );
+        }
+
+        Ok(())
+    }
+
+    /// returns the old value
+    pub async fn set(&self, value: &str) -> Result<String> {
+        self.validate(value)?;
+
+        let old_value = self.get()?;
+        self.option.set_string(value)?;
+        self.changed(&old_value).await?;
+
+        Ok(old_value)
+    }
+
+    /// returns the old value
+    pub async fn reset(&self) -> Result<String> {
+        let old_value = self.get()?;
+        self.option.reset();
+        self.changed(&old_value).await?;
+
+        Ok(old_value)
+    }
+
+    async fn changed(&self, old_value: &str) -> Result<()> {
+        let Some(on_change) = self.on_change else {
+            return Ok(());
+        };
+        if self.get()? == old_value {
+            return Ok(());
+        }
+
+        if let Err(e) = on_change().await {
+            self.option.set_string(old_value)?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+pub const REGISTRY: &[OptionInfo] = &[
+    OptionInfo::new(
+        &MUTE_LOSE_FOCUS,
+        "Mute cef when you alt-tab out of the game",
+    ),
+    OptionInfo::new(&AUTOPLAY_MAP_THEMES, "Auto-play map themes")
+        .on_change(|| stop_map_theme().boxed_local()),
+    OptionInfo::new(&SUBTITLES, "Show subtitles/closed captions on videos"),
+    OptionInfo::new(&VOLUME, "Global volume modifier")
+        .range(0.0, 1.0)
+        .on_change(|| {
+            async {
+                mixer::refresh_volumes();
+                Ok(())
+            }
+            .boxed_local()
+        }),
+    // the mixer sets the map theme's volume every update
+    OptionInfo::new(&MAP_THEME_VOLUME, "Map theme volume").range(0.0, 1.0),
+    OptionInfo::new(
+        &FRAME_RATE,
+        "Changes default frame rate of newly created browsers",
+    )
+    .units("fps")
+    .range(1.0, 60.0),
+    OptionInfo::new(
+        &OCCLUSION,
+        "Quieten screens that have blocks between you and them",
+    ),
+    OptionInfo::new(
+        &OCCLUSION_LOW_PASS,
+        "Also muffle occluded screens with a low-pass filter",
+    ),
+    OptionInfo::new(
+        &MIXER_DUCKING,
+        "Lower the map theme while another screen is audible",
+    ),
+    OptionInfo::new(
+        &MIXER_FOCUS,
+        "Lower the other screens while you look at one",
+    ),
+    OptionInfo::new(
+        &NATIVE_AUDIO,
+        "Play screen audio through the plugin so every screen type gets positional audio, \
+         applies to screens created after changing it",
+    )
+    .on_change(|| async { Cef::set_native_audio(NATIVE_AUDIO.get()?).await }.boxed_local()),
+    OptionInfo::new(
+        &AUDIO_OUTPUT,
+        "Where native audio is played: device, file or null, applies after restarting the game",
+    ),
+    OptionInfo::new(
+        &PROFILE,
+        "Browser profile new screens use, its cookies and logins are kept in cef/cache/profiles",
+    ),
+    OptionInfo::new(
+        &POPUPS,
+        "What happens when a page opens a new window: block, same-screen or new-screen",
+    ),
+    OptionInfo::new(
+        &ADBLOCK,
+        "Block ads and trackers on new screens using the filter lists in cef/filters",
+    ),
+];
+
+/// by name or full key, ignoring case
+pub fn find(name: &str) -> Result<&'static OptionInfo> {
+    let name = name.to_lowercase();
+    let name = name.trim_start_matches("cef-");
+
+    if let Some(info) = REGISTRY.iter().find(|info| info.name() == name) {
+        Ok(info)
+    } else {
+        bail!("unknown option {:?}, see cef config list", name);
+    }
+}
+
+async fn stop_map_theme() -> Result<()> {
+    if let Some(entity_id) = CURRENT_MAP_THEME.get() {
+        CURRENT_MAP_THEME.set(None);
+        let _ignore = EntityManager::remove_entity(entity_id).await;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_registry() {
+    let mut names = REGISTRY.iter().map(OptionInfo::name).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), REGISTRY.len());
+
+    for info in REGISTRY {
+        assert!(
+            info.validate(&info.default()).is_ok(),
+            "{} default out of range",
+            info.name()
+        );
+    }
+
+    let volume = find("CEF-Volume").unwrap();
+    assert_eq!(volume.name(), "volume");
+    assert!(volume.validate("0.5").is_ok());
+    assert!(volume.validate("1.5").is_err());
+    assert!(volume.validate("loud").is_err());
+
+    let frame_rate = find("frame-rate").unwrap();
+    assert_eq!(frame_rate.format_value("30"), "30 fps");
+    assert!(frame_rate.validate("0").is_err());
+
+    // no range means the option's own parsing decides
+    assert!(find("subtitles").unwrap().validate("maybe").is_ok());
+    assert!(find("nope").is_err());
+}
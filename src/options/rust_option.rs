@@ -54,3 +54,50 @@ where
         self.default
     }
 }
+
+/// a `RustOption` of any type, as strings
+pub trait AnyOption {
+    fn key(&self) -> &'static str;
+
+    fn get_string(&'static self) -> Result<String>;
+
+    fn default_string(&'static self) -> String;
+
+    /// parses and saves `value`
+    fn set_string(&'static self, value: &str) -> Result<()>;
+
+    /// forget the saved value so the default is used
+    fn reset(&'static self);
+}
+
+impl<T> AnyOption for RustOption<T>
+where
+    T: FromStr + Display + Copy + 'static,
+    <T as FromStr>::Err: std::error::Error + Send + 'static,
+{
+    fn key(&self) -> &'static str {
+        self.key
+    }
+
+    fn get_string(&'static self) -> Result<String> {
+        Ok(self.get()?.to_string())
+    }
+
+    fn default_string(&'static self) -> String {
+        self.default().to_string()
+    }
+
+    fn set_string(&'static self, value: &str) -> Result<()> {
+        let value = value
+            .parse()
+            .chain_err(|| format!("couldn't parse {:?} for {}", value, self.key))?;
+        self.set(value);
+
+        Ok(())
+    }
+
+    fn reset(&'static self) {
+        super::set(self.key, String::new());
+        self.cache.set(None);
+    }
+}
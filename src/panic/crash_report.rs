@@ -0,0 +1,120 @@
+//! a folder with everything a developer needs to look at a crash
+//!
+//! we're inside the panic hook, so nothing here may panic; each part is
+//! written as soon as it's ready so a bad part only loses itself
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use classicube_sys::cc_datetime;
+
+use crate::{diagnostics::DebugState, options, plugin};
+
+const CEF_BINARY_VERSION: &str = include_str!("../../cef_binary_version");
+
+/// how much of the end of each log we keep
+const LOG_TAIL_BYTES: u64 = 512 * 1024;
+
+/// writes `cef/crashes/<date>/` and returns its path
+pub fn write(now: &cc_datetime, panic_details: &str) -> io::Result<PathBuf> {
+    let dir = env::current_dir()?
+        .join("cef")
+        .join("crashes")
+        .join(format!(
+            "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+            now.year, now.month, now.day, now.hour, now.minute, now.second
+        ));
+    fs::create_dir_all(&dir)?;
+
+    fs::write(
+        dir.join("panic.txt"),
+        format!(
+            "plugin version {}\ncef binary version {}\nos {} {}\n\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            CEF_BINARY_VERSION.trim(),
+            env::consts::OS,
+            env::consts::ARCH,
+            panic_details
+        ),
+    )?;
+
+    for log in ["cef.log", "cef-binary.log"] {
+        if let Ok(mut file) = File::open(log) {
+            let _ignore =
+                tail(&mut file, LOG_TAIL_BYTES).and_then(|bytes| fs::write(dir.join(log), bytes));
+        }
+    }
+
+    // the rest lives in the main thread's thread_locals, and the game's
+    // options aren't safe to read from other threads
+    if plugin::is_plugin_active() {
+        let _ignore = fs::write(dir.join("options.txt"), describe_options());
+        let _ignore = fs::write(dir.join("state.txt"), DebugState::capture().to_string());
+    } else {
+        let _ignore = fs::write(
+            dir.join("state.txt"),
+            "crashed off the main thread, no options or state\n",
+        );
+    }
+
+    Ok(dir)
+}
+
+fn describe_options() -> String {
+    let mut out = String::new();
+    for info in options::REGISTRY {
+        let value = info.get().unwrap_or_else(|e| format!("<{e}>"));
+        let _ignore = writeln!(out, "{} = {}", info.name(), value);
+    }
+
+    out
+}
+
+/// the last `max_bytes` of `reader`, starting at a line
+fn tail<R: Read + Seek>(reader: &mut R, max_bytes: u64) -> io::Result<Vec<u8>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let start = len.saturating_sub(max_bytes);
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if start > 0
+        && let Some(newline) = bytes.iter().position(|&b| b == b'\n')
+    {
+        bytes.drain(..=newline);
+    }
+
+    Ok(bytes)
+}
+
+/// `path` as shown to the player, relative to the game folder if possible
+pub fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[test]
+fn test_tail() {
+    use std::io::Cursor;
+
+    let log = b"first line\nsecond line\nthird\n".to_vec();
+
+    // everything fits
+    assert_eq!(tail(&mut Cursor::new(&log), 100).unwrap(), log);
+
+    // cut in the middle of "second line", so start at "third"
+    assert_eq!(tail(&mut Cursor::new(&log), 10).unwrap(), b"third\n");
+
+    // no newline after the cut
+    assert_eq!(tail(&mut Cursor::new(b"abcdef"), 3).unwrap(), b"def");
+}
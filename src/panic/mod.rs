@@ -1,3 +1,5 @@
+mod crash_report;
+
 use std::{
     ffi::CString,
     fs,
@@ -8,8 +10,11 @@ use std::{
 };
 
 use backtrace::Backtrace;
+use classicube_helpers::color::RED;
 use classicube_sys::{DateTime_CurrentLocal, Window_ShowDialog, cc_datetime};
 
+use crate::{chat::Chat, plugin};
+
 pub fn install_hook() {
     panic::set_hook(Box::new(panic_hook));
 }
@@ -17,7 +22,13 @@ pub fn install_hook() {
 fn panic_hook(info: &PanicHookInfo<'_>) {
     crate::logger::flush_for_abort();
 
-    let (popup_message, stderr_message, verbose_message) = {
+    let now = unsafe {
+        let mut now: cc_datetime = mem::zeroed();
+        DateTime_CurrentLocal(&raw mut now);
+        now
+    };
+
+    let (panic_summary, stderr_message, verbose_message) = {
         // The current implementation always returns `Some`.
         let panic_location = info.location().unwrap();
 
@@ -33,20 +44,13 @@ fn panic_hook(info: &PanicHookInfo<'_>) {
         let thread_name = thread.name().unwrap_or("<unnamed>");
         let bt = Backtrace::new();
 
-        let date = unsafe {
-            let mut now: cc_datetime = mem::zeroed();
-            DateTime_CurrentLocal(&raw mut now);
-            format!(
-                "{:02}/{:02}/{:04} {:02}:{:02}:{:02}",
-                now.day, now.month, now.year, now.hour, now.minute, now.second
-            )
-        };
+        let date = format!(
+            "{:02}/{:02}/{:04} {:02}:{:02}:{:02}",
+            now.day, now.month, now.year, now.hour, now.minute, now.second
+        );
 
         (
-            format!(
-                "CEF crashed: '{panic_message}', {panic_location}\nMore details were written to \
-                 'cef-crashes.log'\nPlease report this file to a developer!"
-            ),
+            format!("'{panic_message}', {panic_location}"),
             format!(
                 "thread '{thread_name}' panicked at '{panic_message}', {panic_location}\n{bt:?}"
             ),
@@ -75,6 +79,29 @@ fn panic_hook(info: &PanicHookInfo<'_>) {
         drop(file);
     }
 
+    let popup_message = match crash_report::write(&now, &verbose_message) {
+        Ok(dir) => {
+            let dir = crash_report::display_path(&dir);
+            if plugin::is_plugin_active() {
+                Chat::print(format!("{RED}CEF crashed! Crash report saved to {dir}"));
+            }
+
+            format!(
+                "CEF crashed: {panic_summary}\nA crash report was saved to '{dir}'\nPlease send \
+                 this folder to a developer!"
+            )
+        }
+
+        Err(e) => {
+            eprintln!("couldn't write crash report: {e}");
+
+            format!(
+                "CEF crashed: {panic_summary}\nMore details were written to \
+                 'cef-crashes.log'\nPlease report this file to a developer!"
+            )
+        }
+    };
+
     unsafe {
         let title = CString::new("CEF crashed!").unwrap();
         let msg = CString::new(popup_message).unwrap();
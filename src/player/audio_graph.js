@@ -0,0 +1,110 @@
+// positional audio for a page's <video>, injected by the plugin
+//
+// video -> lowpass -> stereo panner -> speakers
+//                                   `-> spatial (HRTF) panner -> speakers
+//
+// a media element can only be hooked up to one source node, so everything
+// shares the graph made on first use
+if (typeof window.cefAudio === "undefined") {
+  window.cefAudio = {
+    context: null,
+
+    create: function (video) {
+      if (this.context !== null) {
+        return;
+      }
+
+      var context = new AudioContext();
+      var source = context.createMediaElementSource(video);
+
+      var lowpass = context.createBiquadFilter();
+      lowpass.type = "lowpass";
+      lowpass.frequency.value = 22050;
+
+      var panner = context.createStereoPanner();
+
+      var spatial = context.createPanner();
+      spatial.panningModel = "HRTF";
+      spatial.distanceModel = "linear";
+      spatial.refDistance = 1;
+      spatial.rolloffFactor = 1;
+      // quieter from behind the screen
+      spatial.coneInnerAngle = 180;
+      spatial.coneOuterAngle = 360;
+      spatial.coneOuterGain = 0.4;
+
+      source.connect(lowpass);
+      lowpass.connect(panner);
+      panner.connect(context.destination);
+
+      this.context = context;
+      this.lowpass = lowpass;
+      this.panner = panner;
+      this.spatial = spatial;
+      this.spatialConnected = false;
+    },
+
+    smooth: function (param, value, seconds) {
+      param.setTargetAtTime(value, this.context.currentTime, seconds);
+    },
+
+    // -1 left to 1 right, null to reset
+    setPan: function (video, pan) {
+      if (pan === null) {
+        if (this.context !== null) {
+          this.panner.pan.value = 0;
+        }
+        return;
+      }
+
+      this.create(video);
+      this.smooth(this.panner.pan, pan, 0.02);
+    },
+
+    // cutoff in hertz, null to reset
+    setLowPass: function (video, frequency) {
+      if (frequency === null) {
+        if (this.context !== null) {
+          this.lowpass.frequency.value = 22050;
+        }
+        return;
+      }
+
+      this.create(video);
+      this.smooth(this.lowpass.frequency, frequency, 0.1);
+    },
+
+    // position and orientation are relative to the listener, who stays at
+    // the origin looking down -z
+    setSpatial: function (video, position, orientation, maxDistance) {
+      this.create(video);
+
+      var spatial = this.spatial;
+      if (!this.spatialConnected) {
+        this.panner.disconnect();
+        this.panner.connect(spatial);
+        spatial.connect(this.context.destination);
+        this.spatialConnected = true;
+      }
+
+      spatial.maxDistance = Math.max(maxDistance, spatial.refDistance + 0.01);
+      this.smooth(spatial.positionX, position[0], 0.02);
+      this.smooth(spatial.positionY, position[1], 0.02);
+      this.smooth(spatial.positionZ, position[2], 0.02);
+      this.smooth(spatial.orientationX, orientation[0], 0.02);
+      this.smooth(spatial.orientationY, orientation[1], 0.02);
+      this.smooth(spatial.orientationZ, orientation[2], 0.02);
+    },
+
+    clearSpatial: function () {
+      if (this.context === null || !this.spatialConnected) {
+        return;
+      }
+
+      this.spatial.disconnect();
+      this.panner.disconnect();
+      this.panner.connect(this.context.destination);
+      this.spatialConnected = false;
+    },
+  };
+}
@@ -1,16 +1,21 @@
 use std::{path::Path, time::Duration};
 
 use classicube_helpers::async_manager;
-use classicube_sys::{Camera, Vec3};
+use classicube_sys::{Vec2, Vec3};
 use ncollide3d::na::Vector3;
 use reqwest::Url;
 use tracing::{debug, warn};
 
-use super::{MediaPlayer, Player, PlayerTrait, VolumeMode, YouTubePlayer};
+use super::{
+    MediaPlayer, Player, PlayerTrait, VolumeMode, YouTubePlayer, mixer,
+    occlusion::{MAX_FREQUENCY, OcclusionTracker},
+};
 use crate::{
+    audio,
     entity_manager::{CefEntity, EntityManager},
     error::{Error, Result, ResultExt, bail},
     helpers::vec3_to_vector3,
+    options,
 };
 
 pub async fn start_update_loop(entity_id: usize) {
@@ -21,7 +26,10 @@ pub async fn start_update_loop(entity_id: usize) {
     }
 }
 
-fn compute_real_volume(entity: &CefEntity) -> Option<(f32, VolumeMode)> {
+pub fn compute_real_volume(
+    entity: &CefEntity,
+    occlusion: &mut OcclusionTracker,
+) -> Option<(f32, VolumeMode)> {
     let volume_mode = entity.player.get_volume_mode();
 
     if volume_mode == VolumeMode::Global {
@@ -29,69 +37,255 @@ fn compute_real_volume(entity: &CefEntity) -> Option<(f32, VolumeMode)> {
         return Some((current_volume, volume_mode));
     }
 
-    // use distance or panning volume
+    // use distance, panning or spatial volume
 
-    let (position, orientation) = unsafe {
-        if Camera.Active.is_null() {
-            warn!("Camera.Active is null!");
-            return None;
-        }
-        let camera = &*Camera.Active;
-        let position = camera.GetPosition.map(|f| f(0.0))?;
-        let orientation = camera.GetOrientation.map(|f| f())?;
-        (position, orientation)
-    };
+    let (position, orientation) = get_listener()?;
 
     let my_pos = vec3_to_vector3(&position);
     let my_forward = vec3_to_vector3(&Vec3::get_dir_vector(orientation.x, 0.0));
 
     let ent_pos = vec3_to_vector3(&entity.entity.Position);
 
-    let (panning, multiplier, distance) = match volume_mode {
+    let (multiplier, distance) = match volume_mode {
         VolumeMode::Global => unreachable!(),
 
         VolumeMode::Distance {
             multiplier,
             distance,
-        } => (false, multiplier, distance),
-        VolumeMode::Panning {
+        }
+        | VolumeMode::Panning {
             multiplier,
             distance,
             ..
-        } => (true, multiplier, distance),
+        }
+        | VolumeMode::Spatial {
+            multiplier,
+            distance,
+            ..
+        } => (multiplier, distance),
     };
 
     let diff = my_pos - ent_pos;
     let percent = diff.magnitude() / distance;
-    let percent = (1.0 - percent).clamp(0.0, 1.0) * multiplier;
+    let percent = (1.0 - percent).clamp(0.0, 1.0);
+
+    // no point ray-marching if we can't hear it anyway
+    let occlusion_volume = if percent > 0.0 {
+        occlusion.update(my_pos, ent_pos).get_volume()
+    } else {
+        1.0
+    };
+
+    match volume_mode {
+        VolumeMode::Panning { .. } => {
+            let up = Vector3::y();
+
+            let left = Vector3::cross(&my_forward, &up);
+            let left = left.normalize();
+
+            let pan = (ent_pos - my_pos).normalize().dot(&left);
+            let pan = pan * 0.8;
+
+            Some((
+                percent * multiplier * occlusion_volume,
+                VolumeMode::Panning {
+                    multiplier,
+                    distance,
+                    pan,
+                },
+            ))
+        }
+
+        VolumeMode::Spatial { .. } => {
+            let forward = vec3_to_vector3(&Vec3::get_dir_vector(orientation.x, orientation.y));
+            let facing = vec3_to_vector3(&Vec3::get_dir_vector(
+                entity.entity.RotY.to_radians(),
+                entity.entity.RotX.to_radians(),
+            ));
+
+            let position = to_listener_space(forward, ent_pos - my_pos);
+            let orientation = to_listener_space(forward, facing);
+
+            // the page's PannerNode does the distance rolloff
+            let volume = if percent > 0.0 {
+                multiplier * occlusion_volume
+            } else {
+                0.0
+            };
 
-    if panning {
-        let up = Vector3::y();
+            Some((
+                volume,
+                VolumeMode::Spatial {
+                    multiplier,
+                    distance,
+                    position: [position.x, position.y, position.z],
+                    orientation: [orientation.x, orientation.y, orientation.z],
+                },
+            ))
+        }
 
-        let left = Vector3::cross(&my_forward, &up);
-        let left = left.normalize();
+        VolumeMode::Global | VolumeMode::Distance { .. } => {
+            Some((percent * multiplier * occlusion_volume, volume_mode))
+        }
+    }
+}
 
-        let pan = (ent_pos - my_pos).normalize().dot(&left);
-        let pan = pan * 0.8;
+/// camera position and orientation, where positional audio is heard from
+#[cfg(not(test))]
+fn get_listener() -> Option<(Vec3, Vec2)> {
+    use classicube_sys::Camera;
 
-        Some((
-            percent,
-            VolumeMode::Panning {
-                multiplier,
-                distance,
-                pan,
-            },
-        ))
+    unsafe {
+        if Camera.Active.is_null() {
+            warn!("Camera.Active is null!");
+            return None;
+        }
+        let camera = &*Camera.Active;
+        let position = camera.GetPosition.map(|f| f(0.0))?;
+        let orientation = camera.GetOrientation.map(|f| f())?;
+        Some((position, orientation))
+    }
+}
+
+#[cfg(test)]
+thread_local!(
+    static TEST_LISTENER: std::cell::Cell<Option<(Vec3, Vec2)>> =
+        const { std::cell::Cell::new(None) };
+);
+
+/// there's no game camera in tests, so they place the listener themselves
+#[cfg(test)]
+fn get_listener() -> Option<(Vec3, Vec2)> {
+    TEST_LISTENER.get()
+}
+
+/// `relative` in WebAudio's listener space: x right, y up, -z forward
+fn to_listener_space(forward: Vector3<f32>, relative: Vector3<f32>) -> Vector3<f32> {
+    let flat_forward = Vector3::new(forward.x, 0.0, forward.z);
+    let right = if flat_forward.magnitude() > 0.0001 {
+        flat_forward.cross(&Vector3::y()).normalize()
     } else {
-        Some((percent, volume_mode))
+        // looking straight up or down
+        Vector3::x()
+    };
+    let up = right.cross(&forward).normalize();
+
+    Vector3::new(
+        relative.dot(&right),
+        relative.dot(&up),
+        -relative.dot(&forward),
+    )
+}
+
+const AUDIO_GRAPH_JS: &str = include_str!("audio_graph.js");
+
+/// javascript that defines `cefAudio` and applies `mode`, run once when
+/// the page loads so updates only have to send the small calls below
+pub fn get_audio_graph_javascript(video: &str, mode: VolumeMode) -> String {
+    format!(
+        "{AUDIO_GRAPH_JS}\n{}",
+        get_volume_mode_javascript(video, mode)
+    )
+}
+
+/// runs `code` with `video` set to the page's media element, once
+/// `cefAudio` is there
+fn get_audio_call_javascript(video: &str, code: &str) -> String {
+    format!(
+        "(() => {{\n  var video = {video};\n  if (window.cefAudio && video) {{\n    {code}\n  }}\n}})();\n"
+    )
+}
+
+/// javascript that applies the mode's panning to `video`
+pub fn get_volume_mode_javascript(video: &str, mode: VolumeMode) -> String {
+    let code = match mode {
+        VolumeMode::Global | VolumeMode::Distance { .. } => {
+            "cefAudio.clearSpatial(); cefAudio.setPan(video, null);".to_string()
+        }
+
+        VolumeMode::Panning { pan, .. } => {
+            format!("cefAudio.clearSpatial(); cefAudio.setPan(video, {pan});")
+        }
+
+        VolumeMode::Spatial {
+            distance,
+            position: [x, y, z],
+            orientation: [ox, oy, oz],
+            ..
+        } => format!(
+            "cefAudio.setPan(video, null); cefAudio.setSpatial(video, [{x}, {y}, {z}], [{ox}, \
+             {oy}, {oz}], {distance});"
+        ),
+    };
+
+    get_audio_call_javascript(video, &code)
+}
+
+/// javascript that sets `video`'s low-pass cutoff, `None` to remove
+pub fn get_low_pass_javascript(video: &str, frequency: Option<f32>) -> String {
+    let code = match frequency {
+        Some(frequency) => format!("cefAudio.setLowPass(video, {frequency});"),
+        None => "cefAudio.setLowPass(video, null);".to_string(),
+    };
+
+    get_audio_call_javascript(video, &code)
+}
+
+fn update_low_pass(entity: &mut CefEntity, occlusion: &mut OcclusionTracker) {
+    let Some(browser) = entity.browser.as_ref() else {
+        return;
+    };
+
+    let frequency = if options::OCCLUSION_LOW_PASS.get().unwrap_or(false)
+        && entity.player.get_volume_mode() != VolumeMode::Global
+    {
+        Some(occlusion.get().get_low_pass_frequency())
+    } else {
+        None
+    };
+
+    // only talk to the page when the cutoff actually changes
+    let last = occlusion.low_pass_frequency;
+    let changed = match (frequency, last) {
+        (Some(frequency), Some(last)) => (frequency - last).abs() > 1.0,
+        (Some(frequency), None) => frequency < MAX_FREQUENCY,
+        (None, Some(_)) => true,
+        (None, None) => false,
+    };
+    if !changed {
+        return;
     }
+
+    // remember it even if unsupported so we don't retry every update
+    let _ignore = entity.player.set_low_pass(browser, frequency);
+    occlusion.low_pass_frequency = frequency;
 }
 
 async fn start_loop(entity_id: usize) -> Result<()> {
+    let mut occlusion = OcclusionTracker::default();
+
     loop {
         // update volume
         EntityManager::with_entity(entity_id, |entity| {
-            if let Some((volume, volume_mode)) = compute_real_volume(entity) {
+            let is_capturing = entity
+                .browser
+                .as_ref()
+                .is_some_and(|browser| audio::is_capturing(browser.get_identifier()));
+
+            if is_capturing && entity.player.get_volume_mode() != VolumeMode::Global {
+                // the audio module does distance, panning and muffling for us
+                let _ignore = entity.player.set_volume(entity.browser.as_ref(), 1.0);
+                return Ok(());
+            }
+
+            if let Some((volume, volume_mode)) = compute_real_volume(entity, &mut occlusion) {
+                mixer::report_volume(entity_id, volume);
+                let volume = if volume_mode == VolumeMode::Global {
+                    volume
+                } else {
+                    volume * mixer::get_gain(entity_id)
+                };
+
                 let _ignore = entity.player.set_volume(entity.browser.as_ref(), volume);
 
                 let _ignore = entity
@@ -99,6 +293,8 @@ async fn start_loop(entity_id: usize) -> Result<()> {
                     .set_volume_mode(entity.browser.as_ref(), volume_mode);
             }
 
+            update_low_pass(entity, &mut occlusion);
+
             Ok(())
         })?;
 
@@ -220,3 +416,75 @@ fn test_get_ext() {
         "ogg"
     );
 }
+
+#[test]
+fn test_to_listener_space() {
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 0.0001, "{a:?} != {b:?}");
+    }
+
+    // looking down -z is already listener space
+    let forward = Vector3::new(0.0, 0.0, -1.0);
+    assert_near(
+        to_listener_space(forward, Vector3::new(1.0, 2.0, -3.0)),
+        Vector3::new(1.0, 2.0, -3.0),
+    );
+
+    // turned to face +x, so +z is on our right
+    let forward = Vector3::x();
+    assert_near(
+        to_listener_space(forward, Vector3::new(5.0, 0.0, 0.0)),
+        Vector3::new(0.0, 0.0, -5.0),
+    );
+    assert_near(
+        to_listener_space(forward, Vector3::new(0.0, 0.0, 2.0)),
+        Vector3::new(2.0, 0.0, 0.0),
+    );
+
+    // looking 45 degrees up, something level in front is below us
+    let forward = Vector3::new(0.0, 1.0, -1.0).normalize();
+    let relative = to_listener_space(forward, Vector3::new(0.0, 0.0, -1.0));
+    assert!(relative.y < 0.0);
+    assert!(relative.z < 0.0);
+    assert!(relative.x.abs() < 0.0001);
+}
+
+#[test]
+fn test_update_loop_finish() {
+    use std::collections::VecDeque;
+
+    use futures::executor::block_on;
+
+    use crate::cef::RustV8Value;
+
+    let first = Player::from_input("https://example.com/first.mp4").unwrap();
+    let second = Player::from_input("https://example.com/second.mp4").unwrap();
+    let (entity_id, browser) = EntityManager::create_fake(first, VecDeque::from([second]));
+
+    // halfway to the edge of the default 28 block distance
+    TEST_LISTENER.set(Some((Vec3::new(14.0, 0.0, 0.0), Vec2 { x: 0.0, y: 0.0 })));
+
+    // evals failing stops the loop without skipping
+    block_on(start_update_loop(entity_id));
+    assert!(browser.loaded_urls().is_empty());
+    assert!(
+        browser
+            .executed_javascript()
+            .iter()
+            .any(|code| code.starts_with("window.setVolume(0.5"))
+    );
+
+    browser.set_eval_result("playerFinished", RustV8Value::Bool(true));
+    browser.set_eval_result("getCurrentTime()", RustV8Value::Double(12.0));
+    block_on(start_update_loop(entity_id));
+
+    // finishing plays the next in the queue
+    EntityManager::with_entity(entity_id, |entity| {
+        assert_eq!(entity.player.get_url(), "https://example.com/second.mp4");
+        assert!(entity.queue.is_empty());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(browser.loaded_urls().len(), 1);
+    assert!(browser.loaded_urls()[0].contains("second.mp4"));
+}
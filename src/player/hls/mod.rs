@@ -10,16 +10,21 @@ use url::Url;
 
 use super::{
     PlayerTrait, VolumeMode, WebPlayer,
-    helpers::{get_ext, start_update_loop},
+    helpers::{
+        get_audio_graph_javascript, get_ext, get_low_pass_javascript, get_volume_mode_javascript,
+        start_update_loop,
+    },
+    subtitles,
 };
 use crate::{
-    cef::{RustRefBrowser, RustV8Value},
+    cef::{Browser, RustV8Value},
     chat::Chat,
     error::{Result, bail},
     options,
 };
 
 const PAGE_HTML: &str = include_str!("page.html");
+const VIDEO_ELEMENT_JS: &str = "window.player";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HlsPlayer {
@@ -28,6 +33,8 @@ pub struct HlsPlayer {
     // 0-1
     volume: f32,
     volume_mode: VolumeMode,
+    /// url of a SRT or WebVTT file
+    subtitles: Option<String>,
 
     #[serde(skip)]
     pub update_loop_handle: Option<RemoteHandle<()>>,
@@ -45,6 +52,7 @@ impl Default for HlsPlayer {
                 multiplier: 1.0,
                 distance: 28.0,
             },
+            subtitles: None,
             update_loop_handle: None,
             last_title: String::new(),
         }
@@ -57,6 +65,7 @@ impl Clone for HlsPlayer {
             url: self.url.clone(),
             volume: self.volume,
             volume_mode: self.volume_mode,
+            subtitles: self.subtitles.clone(),
             ..Default::default()
         }
     }
@@ -97,13 +106,22 @@ impl PlayerTrait for HlsPlayer {
         ))
     }
 
-    fn on_page_loaded(&mut self, entity_id: usize, _browser: &RustRefBrowser) {
+    fn on_page_loaded(&mut self, entity_id: usize, browser: &Browser) {
         let (f, remote_handle) = start_update_loop(entity_id).remote_handle();
         self.update_loop_handle = Some(remote_handle);
         async_manager::spawn_local_on_main_thread(f);
+
+        let _ignore = browser.execute_javascript(get_audio_graph_javascript(
+            VIDEO_ELEMENT_JS,
+            self.volume_mode,
+        ));
+
+        if self.subtitles.is_some() {
+            subtitles::load(browser, VIDEO_ELEMENT_JS, self.subtitles.clone());
+        }
     }
 
-    fn on_title_change(&mut self, _entity_id: usize, _browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, _entity_id: usize, _browser: &Browser, title: String) {
         if self.last_title == title || title == "HLS Stream Loading" {
             return;
         }
@@ -118,7 +136,7 @@ impl PlayerTrait for HlsPlayer {
     }
 
     /// volume is a float between 0-1
-    fn set_volume(&mut self, browser: Option<&RustRefBrowser>, volume: f32) -> Result<()> {
+    fn set_volume(&mut self, browser: Option<&Browser>, volume: f32) -> Result<()> {
         if let Some(browser) = browser
             && (volume - self.volume).abs() > 0.0001
         {
@@ -135,18 +153,22 @@ impl PlayerTrait for HlsPlayer {
         self.volume_mode
     }
 
-    fn set_volume_mode(
-        &mut self,
-        _browser: Option<&RustRefBrowser>,
-        mode: VolumeMode,
-    ) -> Result<()> {
-        if let VolumeMode::Panning { .. } = mode {
-            bail!("panning is TODO");
+    fn set_volume_mode(&mut self, browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
+        if let Some(browser) = browser
+            && mode != self.volume_mode
+        {
+            browser.execute_javascript(get_volume_mode_javascript(VIDEO_ELEMENT_JS, mode))?;
         }
+
         self.volume_mode = mode;
         Ok(())
     }
 
+    fn set_low_pass(&mut self, browser: &Browser, frequency: Option<f32>) -> Result<()> {
+        browser.execute_javascript(get_low_pass_javascript(VIDEO_ELEMENT_JS, frequency))?;
+        Ok(())
+    }
+
     fn get_url(&self) -> String {
         self.url.clone()
     }
@@ -159,11 +181,20 @@ impl PlayerTrait for HlsPlayer {
         // TODO determine when we 404 after host closes stream
         false
     }
+
+    fn get_subtitles(&self) -> Option<String> {
+        self.subtitles.clone()
+    }
+
+    fn set_subtitles(&mut self, browser: Option<&Browser>, url: Option<String>) -> Result<()> {
+        subtitles::set(&mut self.subtitles, browser, VIDEO_ELEMENT_JS, url);
+        Ok(())
+    }
 }
 
 impl HlsPlayer {
     #[allow(dead_code)]
-    pub async fn get_real_volume(browser: &RustRefBrowser) -> Result<f32> {
+    pub async fn get_real_volume(browser: &Browser) -> Result<f32> {
         let percent = match Self::eval_player_field(browser, "volume").await? {
             RustV8Value::Double(percent) => percent as f32,
             RustV8Value::Int(percent) => percent as f32,
@@ -177,7 +208,7 @@ impl HlsPlayer {
         Ok(percent)
     }
 
-    fn get_player_field(browser: &RustRefBrowser, field: &str) {
+    fn get_player_field(browser: &Browser, field: &str) {
         let _ignore = browser.execute_javascript(format!(
             r#"
                 if (typeof window.player !== "undefined") {{
@@ -187,7 +218,7 @@ impl HlsPlayer {
         ));
     }
 
-    async fn eval_player_field(browser: &RustRefBrowser, field: &str) -> Result<RustV8Value> {
+    async fn eval_player_field(browser: &Browser, field: &str) -> Result<RustV8Value> {
         let code = format!(
             r#"
                 (() => {{
@@ -5,7 +5,7 @@ use tracing::debug;
 use url::Url;
 
 use super::{PlayerTrait, helpers::get_ext};
-use crate::{cef::RustRefBrowser, chat::Chat, error::Result, player::WebPlayer};
+use crate::{cef::Browser, chat::Chat, error::Result, player::WebPlayer};
 
 const PAGE_HTML: &str = include_str!("page.html");
 
@@ -86,7 +86,7 @@ impl PlayerTrait for ImagePlayer {
         ))
     }
 
-    fn on_title_change(&mut self, _entity_id: usize, _browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, _entity_id: usize, _browser: &Browser, title: String) {
         if self.last_title == title || title == "Image Loading" {
             return;
         }
@@ -11,15 +11,21 @@ use url::Url;
 
 use super::{
     PlayerTrait, VolumeMode, WebPlayer,
-    helpers::{get_ext, start_update_loop},
+    helpers::{
+        get_audio_graph_javascript, get_ext, get_low_pass_javascript, get_volume_mode_javascript,
+        start_update_loop,
+    },
+    subtitles,
 };
 use crate::{
-    cef::{RustRefBrowser, RustV8Value},
+    cef::{Browser, RustV8Value},
     chat::Chat,
     error::{Result, bail},
     options,
 };
 
+const VIDEO_ELEMENT_JS: &str = "window.player";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MediaPlayer {
     pub url: String,
@@ -33,6 +39,8 @@ pub struct MediaPlayer {
     should_loop: bool,
     silent: bool,
     speed: f32,
+    /// url of a SRT or WebVTT file
+    subtitles: Option<String>,
 
     #[serde(skip)]
     pub update_loop_handle: Option<RemoteHandle<()>>,
@@ -61,6 +69,7 @@ impl Default for MediaPlayer {
             should_loop: false,
             silent: false,
             speed: 1.0,
+            subtitles: None,
             update_loop_handle: None,
             last_title: String::new(),
             finished: false,
@@ -80,6 +89,7 @@ impl Clone for MediaPlayer {
             should_loop: self.should_loop,
             silent: self.silent,
             speed: self.speed,
+            subtitles: self.subtitles.clone(),
             ..Default::default()
         }
     }
@@ -157,13 +167,22 @@ impl PlayerTrait for MediaPlayer {
             .into())
     }
 
-    fn on_page_loaded(&mut self, entity_id: usize, _browser: &RustRefBrowser) {
+    fn on_page_loaded(&mut self, entity_id: usize, browser: &Browser) {
         let (f, remote_handle) = start_update_loop(entity_id).remote_handle();
         self.update_loop_handle = Some(remote_handle);
         async_manager::spawn_local_on_main_thread(f);
+
+        let _ignore = browser.execute_javascript(get_audio_graph_javascript(
+            VIDEO_ELEMENT_JS,
+            self.volume_mode,
+        ));
+
+        if self.subtitles.is_some() {
+            subtitles::load(browser, VIDEO_ELEMENT_JS, self.subtitles.clone());
+        }
     }
 
-    fn on_title_change(&mut self, _entity_id: usize, browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, _entity_id: usize, browser: &Browser, title: String) {
         if self.last_title == title || title == "Media Loading" {
             return;
         }
@@ -196,7 +215,7 @@ impl PlayerTrait for MediaPlayer {
         Ok(self.time)
     }
 
-    fn set_current_time(&mut self, browser: &RustRefBrowser, time: Duration) -> Result<()> {
+    fn set_current_time(&mut self, browser: &Browser, time: Duration) -> Result<()> {
         Self::execute(browser, &format!("setCurrentTime({})", time.as_secs_f32()))?;
         self.time = time;
 
@@ -208,7 +227,7 @@ impl PlayerTrait for MediaPlayer {
     }
 
     /// volume is a float between 0-1
-    fn set_volume(&mut self, browser: Option<&RustRefBrowser>, volume: f32) -> Result<()> {
+    fn set_volume(&mut self, browser: Option<&Browser>, volume: f32) -> Result<()> {
         if let Some(browser) = browser
             && (volume - self.volume).abs() > 0.0001
         {
@@ -225,35 +244,28 @@ impl PlayerTrait for MediaPlayer {
         self.volume_mode
     }
 
-    fn set_volume_mode(
-        &mut self,
-        browser: Option<&RustRefBrowser>,
-        mode: VolumeMode,
-    ) -> Result<()> {
-        if let Some(browser) = browser {
-            if let VolumeMode::Panning { pan, .. } = mode {
-                Self::execute(browser, &format!("handlePanning({pan})"))?;
-            } else {
-                browser.execute_javascript(
-                    r#"
-                        if (typeof window.panner !== "undefined") {
-                            window.panner.pan.value = 0.0;
-                        }
-                    "#,
-                )?;
-            }
+    fn set_volume_mode(&mut self, browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
+        if let Some(browser) = browser
+            && mode != self.volume_mode
+        {
+            browser.execute_javascript(get_volume_mode_javascript(VIDEO_ELEMENT_JS, mode))?;
         }
 
         self.volume_mode = mode;
         Ok(())
     }
 
-    fn set_autoplay(&mut self, _browser: Option<&RustRefBrowser>, autoplay: bool) -> Result<()> {
+    fn set_low_pass(&mut self, browser: &Browser, frequency: Option<f32>) -> Result<()> {
+        browser.execute_javascript(get_low_pass_javascript(VIDEO_ELEMENT_JS, frequency))?;
+        Ok(())
+    }
+
+    fn set_autoplay(&mut self, _browser: Option<&Browser>, autoplay: bool) -> Result<()> {
         self.autoplay = autoplay;
         Ok(())
     }
 
-    fn set_loop(&mut self, _browser: Option<&RustRefBrowser>, should_loop: bool) -> Result<()> {
+    fn set_loop(&mut self, _browser: Option<&Browser>, should_loop: bool) -> Result<()> {
         self.should_loop = should_loop;
         Ok(())
     }
@@ -270,7 +282,7 @@ impl PlayerTrait for MediaPlayer {
         self.finished
     }
 
-    fn set_playing(&mut self, browser: &RustRefBrowser, playing: bool) -> Result<()> {
+    fn set_playing(&mut self, browser: &Browser, playing: bool) -> Result<()> {
         Self::execute(browser, &format!("setPlaying({playing})"))?;
         Ok(())
     }
@@ -280,7 +292,7 @@ impl PlayerTrait for MediaPlayer {
         Ok(())
     }
 
-    fn set_speed(&mut self, browser: Option<&RustRefBrowser>, speed: f32) -> Result<()> {
+    fn set_speed(&mut self, browser: Option<&Browser>, speed: f32) -> Result<()> {
         if let Some(browser) = browser {
             Self::execute(browser, &format!("setPlaybackRate({speed})"))?;
         }
@@ -288,10 +300,19 @@ impl PlayerTrait for MediaPlayer {
         self.speed = speed;
         Ok(())
     }
+
+    fn get_subtitles(&self) -> Option<String> {
+        self.subtitles.clone()
+    }
+
+    fn set_subtitles(&mut self, browser: Option<&Browser>, url: Option<String>) -> Result<()> {
+        subtitles::set(&mut self.subtitles, browser, VIDEO_ELEMENT_JS, url);
+        Ok(())
+    }
 }
 
 impl MediaPlayer {
-    pub async fn real_is_finished_playing(browser: &RustRefBrowser) -> Result<bool> {
+    pub async fn real_is_finished_playing(browser: &Browser) -> Result<bool> {
         let ended = match Self::eval(browser, "playerFinished").await? {
             RustV8Value::Bool(ended) => ended,
 
@@ -303,7 +324,7 @@ impl MediaPlayer {
         Ok(ended)
     }
 
-    pub async fn get_real_time(browser: &RustRefBrowser) -> Result<Duration> {
+    pub async fn get_real_time(browser: &Browser) -> Result<Duration> {
         let seconds = match Self::eval(browser, "getCurrentTime()").await? {
             RustV8Value::Double(seconds) => seconds as f32,
             RustV8Value::Int(seconds) => seconds as f32,
@@ -316,13 +337,13 @@ impl MediaPlayer {
         Ok(Duration::from_secs_f32(seconds))
     }
 
-    fn execute(browser: &RustRefBrowser, method: &str) -> Result<()> {
+    fn execute(browser: &Browser, method: &str) -> Result<()> {
         let code = format!("window.{method};");
         browser.execute_javascript(code)?;
         Ok(())
     }
 
-    async fn eval(browser: &RustRefBrowser, method: &str) -> Result<RustV8Value> {
+    async fn eval(browser: &Browser, method: &str) -> Result<RustV8Value> {
         let code = format!("window.{method};");
         browser.eval_javascript(code).await
     }
@@ -0,0 +1,317 @@
+//! balances screens against each other: the map theme ducks while another
+//! screen is audible, and the screen you're looking at can be prioritized
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    time::Duration,
+};
+
+use classicube_helpers::async_manager;
+use classicube_sys::{Camera, Vec3};
+use futures::{future::RemoteHandle, prelude::*};
+use ncollide3d::na::Vector3;
+use tracing::warn;
+
+use super::{Fade, Player, PlayerTrait, volume_fade};
+use crate::{
+    chat::hidden_communication::CURRENT_MAP_THEME, entity_manager::EntityManager, error::Result,
+    helpers::vec3_to_vector3, options,
+};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(32);
+
+/// quieter than this doesn't duck or take focus
+const AUDIBLE_VOLUME: f32 = 0.05;
+
+/// map theme gain while another screen is audible
+const DUCK_GAIN: f32 = 0.25;
+
+/// gain of the screens you're not looking at
+const UNFOCUSED_GAIN: f32 = 0.4;
+
+/// how long gain changes fade for
+const FADE_SECONDS: f32 = 1.0;
+
+/// cosine of how far off-center a screen can be and still be looked at
+const FOCUS_COS: f32 = 0.9;
+
+// entity_id, volume its update loop last computed before mixing
+thread_local!(
+    static LEVELS: RefCell<HashMap<usize, f32>> = RefCell::default();
+);
+
+// entity_id, gain
+thread_local!(
+    static GAINS: RefCell<HashMap<usize, Fade>> = RefCell::default();
+);
+
+thread_local!(
+    static FOCUSED: Cell<Option<usize>> = Cell::default();
+);
+
+thread_local!(
+    static UPDATE_LOOP: RefCell<Option<RemoteHandle<()>>> = RefCell::default();
+);
+
+pub fn initialize() {
+    let (f, remote_handle) = async {
+        loop {
+            if let Err(e) = update() {
+                warn!("mixer: {}", e);
+            }
+            async_manager::sleep(UPDATE_INTERVAL).await;
+        }
+    }
+    .remote_handle();
+
+    UPDATE_LOOP.with_borrow_mut(|cell| *cell = Some(remote_handle));
+    async_manager::spawn_local_on_main_thread(f);
+}
+
+pub fn shutdown() {
+    UPDATE_LOOP.with_borrow_mut(|cell| cell.take());
+    LEVELS.with_borrow_mut(HashMap::clear);
+    GAINS.with_borrow_mut(HashMap::clear);
+    FOCUSED.set(None);
+}
+
+pub fn is_running() -> bool {
+    UPDATE_LOOP.with(|cell| cell.try_borrow().is_ok_and(|handle| handle.is_some()))
+}
+
+/// called by whatever computes a screen's volume, before `get_gain`
+pub fn report_volume(entity_id: usize, volume: f32) {
+    LEVELS.with_borrow_mut(|levels| levels.insert(entity_id, volume));
+}
+
+/// multiplier for a screen's positional volume
+pub fn get_gain(entity_id: usize) -> f32 {
+    GAINS.with_borrow(|gains| gains.get(&entity_id).map_or(1.0, Fade::get))
+}
+
+/// the looked-at screen, if it's prioritized
+pub fn get_focused() -> Option<usize> {
+    FOCUSED.get()
+}
+
+pub struct MixerEntry {
+    pub entity_id: usize,
+    pub name: Option<String>,
+    pub type_name: &'static str,
+    /// volume before mixing
+    pub level: f32,
+    pub gain: f32,
+    /// what you actually hear, including the global volume
+    pub effective: f32,
+    pub is_map_theme: bool,
+}
+
+/// every entity's volume as heard right now, for `cef mixer`
+pub fn get_entries() -> Vec<MixerEntry> {
+    let map_theme = CURRENT_MAP_THEME.get();
+    let volume_modifier = options::VOLUME.get().unwrap_or(1.0);
+
+    let mut entries = EntityManager::with_all_entities(|entities| {
+        entities
+            .values()
+            .map(|entity| {
+                let is_map_theme = map_theme == Some(entity.id);
+                let level = if is_map_theme {
+                    options::MAP_THEME_VOLUME.get().unwrap_or(1.0)
+                } else {
+                    get_level(entity.id, &entity.player)
+                };
+                let gain = get_gain(entity.id);
+
+                MixerEntry {
+                    entity_id: entity.id,
+                    name: entity.name.clone(),
+                    type_name: entity.player.type_name(),
+                    level,
+                    gain,
+                    effective: level * gain * volume_modifier,
+                    is_map_theme,
+                }
+            })
+            .collect::<Vec<_>>()
+    });
+    entries.sort_by_key(|entry| entry.entity_id);
+
+    entries
+}
+
+/// re-send every screen's volume to its page, for when the global
+/// volume modifier changes
+pub fn refresh_volumes() {
+    EntityManager::with_all_entities(|entities| {
+        for entity in entities.values_mut() {
+            let volume = entity.player.get_volume();
+
+            // bad hacks because we only run javascript setVolume
+            // when screen volume has changed
+            let _ignore = entity.player.set_volume(entity.browser.as_ref(), 0.0);
+            let _ignore = entity.player.set_volume(entity.browser.as_ref(), volume);
+        }
+    });
+}
+
+fn get_level(entity_id: usize, player: &Player) -> f32 {
+    if let Player::Image(_) = player {
+        return 0.0;
+    }
+
+    LEVELS
+        .with_borrow(|levels| levels.get(&entity_id).copied())
+        .unwrap_or_else(|| player.get_volume())
+}
+
+fn update() -> Result<()> {
+    let map_theme = CURRENT_MAP_THEME.get();
+    let ducking = options::MIXER_DUCKING.get()?;
+    let focus = options::MIXER_FOCUS.get()?;
+    let camera = if focus { get_camera() } else { None };
+
+    // entity_id, position, is audible, local volume multiplier
+    let screens = EntityManager::with_all_entities(|entities| {
+        LEVELS.with_borrow_mut(|levels| levels.retain(|id, _| entities.contains_key(id)));
+
+        entities
+            .values()
+            .map(|entity| {
+                let multiplier = entity.local.get_volume_multiplier();
+                let is_audible = Some(entity.id) != map_theme
+                    && LEVELS.with_borrow(|levels| {
+                        levels
+                            .get(&entity.id)
+                            .is_some_and(|&level| level * multiplier > AUDIBLE_VOLUME)
+                    });
+
+                (
+                    entity.id,
+                    vec3_to_vector3(&entity.entity.Position),
+                    is_audible,
+                    multiplier,
+                )
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let any_audible = screens.iter().any(|(_, _, is_audible, _)| *is_audible);
+
+    let focused = camera.and_then(|(position, forward)| {
+        pick_focus(
+            forward,
+            screens
+                .iter()
+                .filter(|(_, _, is_audible, _)| *is_audible)
+                .map(|(entity_id, screen_position, _, _)| (*entity_id, screen_position - position)),
+        )
+    });
+    FOCUSED.set(focused);
+
+    let targets = screens
+        .iter()
+        .map(|&(entity_id, _, is_audible, multiplier)| {
+            let target = if Some(entity_id) == map_theme {
+                if ducking && any_audible {
+                    DUCK_GAIN
+                } else {
+                    1.0
+                }
+            } else if is_audible && focused.is_some_and(|focused| focused != entity_id) {
+                UNFOCUSED_GAIN
+            } else {
+                1.0
+            };
+
+            (entity_id, target * multiplier)
+        })
+        .collect::<HashMap<_, _>>();
+
+    GAINS.with_borrow_mut(|gains| {
+        gains.retain(|entity_id, _| targets.contains_key(entity_id));
+
+        for (&entity_id, &target) in &targets {
+            let fade = gains
+                .entry(entity_id)
+                .or_insert_with(|| Fade::new(1.0, 1.0, 0.0));
+
+            if (fade.to - target).abs() > f32::EPSILON {
+                *fade = Fade::new(fade.get(), target, FADE_SECONDS);
+            }
+        }
+    });
+
+    // the map theme is global so its page volume is set directly,
+    // unless it's being faded out for a new map
+    if let Some(entity_id) = map_theme
+        && !volume_fade::is_fading()
+    {
+        let volume = options::MAP_THEME_VOLUME.get()? * get_gain(entity_id);
+
+        let _ignore = EntityManager::with_entity(entity_id, |entity| {
+            entity.player.set_volume(entity.browser.as_ref(), volume)
+        });
+    }
+
+    Ok(())
+}
+
+/// camera position and look direction
+fn get_camera() -> Option<(Vector3<f32>, Vector3<f32>)> {
+    unsafe {
+        if Camera.Active.is_null() {
+            return None;
+        }
+        let camera = &*Camera.Active;
+        let position = camera.GetPosition.map(|f| f(0.0))?;
+        let orientation = camera.GetOrientation.map(|f| f())?;
+
+        Some((
+            vec3_to_vector3(&position),
+            vec3_to_vector3(&Vec3::get_dir_vector(orientation.x, orientation.y)),
+        ))
+    }
+}
+
+/// the screen closest to the middle of your view, `relative` is each
+/// screen's position from the camera
+fn pick_focus<I>(forward: Vector3<f32>, screens: I) -> Option<usize>
+where
+    I: Iterator<Item = (usize, Vector3<f32>)>,
+{
+    screens
+        .filter_map(|(entity_id, relative)| {
+            let distance = relative.magnitude();
+            if distance < 0.0001 {
+                return None;
+            }
+
+            let cos = relative.dot(&forward) / distance;
+            (cos >= FOCUS_COS).then_some((entity_id, cos))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity_id, _)| entity_id)
+}
+
+#[test]
+fn test_pick_focus() {
+    let forward = Vector3::new(0.0, 0.0, -1.0);
+
+    // straight ahead wins over slightly off to the side
+    let screens = vec![
+        (1, Vector3::new(2.0, 0.0, -10.0)),
+        (2, Vector3::new(0.0, 0.5, -20.0)),
+        (3, Vector3::new(0.0, 0.0, 10.0)),
+    ];
+    assert_eq!(pick_focus(forward, screens.into_iter()), Some(2));
+
+    // nothing near the middle of the view
+    let screens = vec![
+        (1, Vector3::new(10.0, 0.0, -1.0)),
+        (2, Vector3::new(0.0, 0.0, 5.0)),
+        (3, Vector3::zeros()),
+    ];
+    assert_eq!(pick_focus(forward, screens.into_iter()), None);
+}
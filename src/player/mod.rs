@@ -4,6 +4,9 @@ mod helpers;
 mod hls;
 mod image;
 mod media;
+pub mod mixer;
+mod occlusion;
+mod subtitles;
 pub mod url_aliases;
 mod volume_fade;
 mod web;
@@ -14,11 +17,19 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 
 pub use self::{
-    builder::PlayerBuilder, dash::DashPlayer, hls::HlsPlayer, image::ImagePlayer,
-    media::MediaPlayer, web::WebPlayer, youtube::YouTubePlayer,
+    builder::PlayerBuilder,
+    dash::DashPlayer,
+    helpers::compute_real_volume,
+    hls::HlsPlayer,
+    image::ImagePlayer,
+    media::MediaPlayer,
+    occlusion::OcclusionTracker,
+    volume_fade::{Fade, is_fading},
+    web::WebPlayer,
+    youtube::YouTubePlayer,
 };
 use crate::{
-    cef::RustRefBrowser,
+    cef::Browser,
     error::{Result, bail},
 };
 
@@ -33,21 +44,24 @@ pub trait PlayerTrait: Clone {
     fn on_create(&mut self) -> Result<String>;
 
     /// Called after page is loaded
-    fn on_page_loaded(&mut self, _entity_id: usize, _browser: &RustRefBrowser) {}
+    fn on_page_loaded(&mut self, _entity_id: usize, _browser: &Browser) {}
 
-    fn on_title_change(&mut self, _entity_id: usize, _browser: &RustRefBrowser, _title: String) {}
+    fn on_title_change(&mut self, _entity_id: usize, _browser: &Browser, _title: String) {}
+
+    /// Called when the page navigates, even by itself
+    fn on_address_change(&mut self, _entity_id: usize, _browser: &Browser, _url: String) {}
 
     fn get_current_time(&self) -> Result<Duration> {
         bail!("getting time not supported");
     }
-    fn set_current_time(&mut self, _browser: &RustRefBrowser, _time: Duration) -> Result<()> {
+    fn set_current_time(&mut self, _browser: &Browser, _time: Duration) -> Result<()> {
         bail!("setting time not supported");
     }
 
     fn get_volume(&self) -> f32 {
         1.0
     }
-    fn set_volume(&mut self, _browser: Option<&RustRefBrowser>, percent: f32) -> Result<()> {
+    fn set_volume(&mut self, _browser: Option<&Browser>, percent: f32) -> Result<()> {
         if (percent - 1.0).abs() > 0.01 {
             bail!("setting volume not supported");
         } else {
@@ -58,11 +72,7 @@ pub trait PlayerTrait: Clone {
     fn get_volume_mode(&self) -> VolumeMode {
         VolumeMode::Global
     }
-    fn set_volume_mode(
-        &mut self,
-        _browser: Option<&RustRefBrowser>,
-        mode: VolumeMode,
-    ) -> Result<()> {
+    fn set_volume_mode(&mut self, _browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
         if mode == VolumeMode::Global {
             Ok(())
         } else {
@@ -70,7 +80,7 @@ pub trait PlayerTrait: Clone {
         }
     }
 
-    fn set_autoplay(&mut self, _browser: Option<&RustRefBrowser>, autoplay: bool) -> Result<()> {
+    fn set_autoplay(&mut self, _browser: Option<&Browser>, autoplay: bool) -> Result<()> {
         if autoplay {
             Ok(())
         } else {
@@ -78,7 +88,7 @@ pub trait PlayerTrait: Clone {
         }
     }
 
-    fn set_loop(&mut self, _browser: Option<&RustRefBrowser>, should_loop: bool) -> Result<()> {
+    fn set_loop(&mut self, _browser: Option<&Browser>, should_loop: bool) -> Result<()> {
         if should_loop {
             bail!("looping unsupported");
         } else {
@@ -92,7 +102,7 @@ pub trait PlayerTrait: Clone {
 
     fn is_finished_playing(&self) -> bool;
 
-    fn set_playing(&mut self, _browser: &RustRefBrowser, playing: bool) -> Result<()> {
+    fn set_playing(&mut self, _browser: &Browser, playing: bool) -> Result<()> {
         if playing {
             Ok(())
         } else {
@@ -108,13 +118,26 @@ pub trait PlayerTrait: Clone {
         }
     }
 
-    fn set_speed(&mut self, _browser: Option<&RustRefBrowser>, speed: f32) -> Result<()> {
+    fn set_speed(&mut self, _browser: Option<&Browser>, speed: f32) -> Result<()> {
         if (speed - 1.0).abs() > 0.01 {
             bail!("setting speed unsupported");
         } else {
             Ok(())
         }
     }
+
+    /// low-pass cutoff in hertz, `None` removes the filter
+    fn set_low_pass(&mut self, _browser: &Browser, _frequency: Option<f32>) -> Result<()> {
+        bail!("low-pass filter not supported");
+    }
+
+    fn get_subtitles(&self) -> Option<String> {
+        None
+    }
+    /// url of a SRT or WebVTT file, `None` to remove
+    fn set_subtitles(&mut self, _browser: Option<&Browser>, _url: Option<String>) -> Result<()> {
+        bail!("subtitles not supported");
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
@@ -129,6 +152,14 @@ pub enum VolumeMode {
         distance: f32,
         pan: f32,
     },
+    /// HRTF panning with elevation, `position` and `orientation` are
+    /// relative to the listener at the origin looking down -z
+    Spatial {
+        multiplier: f32,
+        distance: f32,
+        position: [f32; 3],
+        orientation: [f32; 3],
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -210,7 +241,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn on_page_loaded(&mut self, entity_id: usize, browser: &RustRefBrowser) {
+    fn on_page_loaded(&mut self, entity_id: usize, browser: &Browser) {
         match self {
             Player::YouTube(player) => player.on_page_loaded(entity_id, browser),
             Player::Dash(player) => player.on_page_loaded(entity_id, browser),
@@ -221,7 +252,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn on_title_change(&mut self, entity_id: usize, browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, entity_id: usize, browser: &Browser, title: String) {
         match self {
             Player::YouTube(player) => player.on_title_change(entity_id, browser, title),
             Player::Dash(player) => player.on_title_change(entity_id, browser, title),
@@ -232,6 +263,17 @@ impl PlayerTrait for Player {
         }
     }
 
+    fn on_address_change(&mut self, entity_id: usize, browser: &Browser, url: String) {
+        match self {
+            Player::YouTube(player) => player.on_address_change(entity_id, browser, url),
+            Player::Dash(player) => player.on_address_change(entity_id, browser, url),
+            Player::Hls(player) => player.on_address_change(entity_id, browser, url),
+            Player::Media(player) => player.on_address_change(entity_id, browser, url),
+            Player::Image(player) => player.on_address_change(entity_id, browser, url),
+            Player::Web(player) => player.on_address_change(entity_id, browser, url),
+        }
+    }
+
     fn get_current_time(&self) -> Result<Duration> {
         match self {
             Player::YouTube(player) => player.get_current_time(),
@@ -243,7 +285,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_current_time(&mut self, browser: &RustRefBrowser, time: Duration) -> Result<()> {
+    fn set_current_time(&mut self, browser: &Browser, time: Duration) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_current_time(browser, time),
             Player::Dash(player) => player.set_current_time(browser, time),
@@ -265,7 +307,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_volume(&mut self, browser: Option<&RustRefBrowser>, percent: f32) -> Result<()> {
+    fn set_volume(&mut self, browser: Option<&Browser>, percent: f32) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_volume(browser, percent),
             Player::Dash(player) => player.set_volume(browser, percent),
@@ -287,11 +329,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_volume_mode(
-        &mut self,
-        browser: Option<&RustRefBrowser>,
-        mode: VolumeMode,
-    ) -> Result<()> {
+    fn set_volume_mode(&mut self, browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_volume_mode(browser, mode),
             Player::Dash(player) => player.set_volume_mode(browser, mode),
@@ -302,7 +340,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_autoplay(&mut self, browser: Option<&RustRefBrowser>, autoplay: bool) -> Result<()> {
+    fn set_autoplay(&mut self, browser: Option<&Browser>, autoplay: bool) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_autoplay(browser, autoplay),
             Player::Dash(player) => player.set_autoplay(browser, autoplay),
@@ -313,7 +351,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_loop(&mut self, browser: Option<&RustRefBrowser>, should_loop: bool) -> Result<()> {
+    fn set_loop(&mut self, browser: Option<&Browser>, should_loop: bool) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_loop(browser, should_loop),
             Player::Dash(player) => player.set_loop(browser, should_loop),
@@ -357,7 +395,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_playing(&mut self, browser: &RustRefBrowser, playing: bool) -> Result<()> {
+    fn set_playing(&mut self, browser: &Browser, playing: bool) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_playing(browser, playing),
             Player::Dash(player) => player.set_playing(browser, playing),
@@ -379,7 +417,7 @@ impl PlayerTrait for Player {
         }
     }
 
-    fn set_speed(&mut self, browser: Option<&RustRefBrowser>, speed: f32) -> Result<()> {
+    fn set_speed(&mut self, browser: Option<&Browser>, speed: f32) -> Result<()> {
         match self {
             Player::YouTube(player) => player.set_speed(browser, speed),
             Player::Dash(player) => player.set_speed(browser, speed),
@@ -389,6 +427,56 @@ impl PlayerTrait for Player {
             Player::Web(player) => player.set_speed(browser, speed),
         }
     }
+
+    fn set_low_pass(&mut self, browser: &Browser, frequency: Option<f32>) -> Result<()> {
+        match self {
+            Player::YouTube(player) => player.set_low_pass(browser, frequency),
+            Player::Dash(player) => player.set_low_pass(browser, frequency),
+            Player::Hls(player) => player.set_low_pass(browser, frequency),
+            Player::Media(player) => player.set_low_pass(browser, frequency),
+            Player::Image(player) => player.set_low_pass(browser, frequency),
+            Player::Web(player) => player.set_low_pass(browser, frequency),
+        }
+    }
+
+    fn get_subtitles(&self) -> Option<String> {
+        match self {
+            Player::YouTube(player) => player.get_subtitles(),
+            Player::Dash(player) => player.get_subtitles(),
+            Player::Hls(player) => player.get_subtitles(),
+            Player::Media(player) => player.get_subtitles(),
+            Player::Image(player) => player.get_subtitles(),
+            Player::Web(player) => player.get_subtitles(),
+        }
+    }
+
+    fn set_subtitles(&mut self, browser: Option<&Browser>, url: Option<String>) -> Result<()> {
+        match self {
+            Player::YouTube(player) => player.set_subtitles(browser, url),
+            Player::Dash(player) => player.set_subtitles(browser, url),
+            Player::Hls(player) => player.set_subtitles(browser, url),
+            Player::Media(player) => player.set_subtitles(browser, url),
+            Player::Image(player) => player.set_subtitles(browser, url),
+            Player::Web(player) => player.set_subtitles(browser, url),
+        }
+    }
+}
+
+impl Player {
+    /// whether its volume update loop is running
+    pub fn has_update_loop(&self) -> bool {
+        match self {
+            Player::YouTube(player) => player.update_loop_handle.is_some(),
+            Player::Dash(player) => player.update_loop_handle.is_some(),
+            Player::Hls(player) => player.update_loop_handle.is_some(),
+            Player::Media(player) => player.update_loop_handle.is_some(),
+            Player::Image(_) | Player::Web(_) => false,
+        }
+    }
+}
+
+pub fn initialize() {
+    mixer::initialize();
 }
 
 pub fn on_new_map() {
@@ -401,6 +489,7 @@ pub fn on_new_map_loaded() {
 }
 
 pub fn shutdown() {
+    mixer::shutdown();
     volume_fade::shutdown();
     url_aliases::shutdown();
 }
@@ -427,6 +516,26 @@ fn test_create_player() {
         }
     }
 
+    let local_files = [
+        "local://files/intro.mp4",
+        "local://files/music/theme%201.ogg",
+        "local://files/logo.png",
+        "local://files/slides/index.html",
+    ];
+    let players = local_files.map(|url| Player::from_input(url).unwrap());
+    assert!(matches!(
+        players,
+        [
+            Player::Media(_),
+            Player::Media(_),
+            Player::Image(_),
+            Player::Web(_)
+        ]
+    ));
+    for (player, url) in players.iter().zip(local_files) {
+        assert_eq!(player.get_url(), url);
+    }
+
     let bad_web = ["classicue", "classicubenet/", "/", "localhost"];
     for url in bad_web {
         let result = Player::from_input(url);
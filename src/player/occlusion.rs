@@ -0,0 +1,231 @@
+//! muffles positional audio when there are blocks between you and a screen
+
+use std::time::{Duration, Instant};
+
+use classicube_sys::{
+    Blocks, CollideType_COLLIDE_ICE, CollideType_COLLIDE_SLIPPERY_ICE, CollideType_COLLIDE_SOLID,
+    World,
+};
+use ncollide3d::na::Vector3;
+
+use crate::options;
+
+/// ray-marching every 32ms update is wasteful, walls don't move that often
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// volume is multiplied by this for every solid block in the way
+const VOLUME_PER_BLOCK: f32 = 0.6;
+
+/// stop counting after this many, it won't get any quieter
+const MAX_BLOCKS: u32 = 6;
+
+/// low-pass cutoff with nothing in the way, effectively no filtering
+pub const MAX_FREQUENCY: f32 = 22050.0;
+
+/// low-pass cutoff behind `MAX_BLOCKS` blocks
+const MIN_FREQUENCY: f32 = 400.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Occlusion {
+    /// solid blocks between the listener and the screen
+    pub blocks: u32,
+}
+
+impl Occlusion {
+    pub const NONE: Self = Self { blocks: 0 };
+
+    /// 0-1 multiplier for the screen's volume
+    pub fn get_volume(self) -> f32 {
+        VOLUME_PER_BLOCK.powf(self.blocks.min(MAX_BLOCKS) as f32)
+    }
+
+    /// low-pass cutoff in hertz
+    pub fn get_low_pass_frequency(self) -> f32 {
+        // the first block muffles the most, like real walls
+        let t = (self.blocks.min(MAX_BLOCKS) as f32 / MAX_BLOCKS as f32).sqrt();
+
+        // lerp in log space so each block sounds like a similar step
+        MAX_FREQUENCY * (MIN_FREQUENCY / MAX_FREQUENCY).powf(t)
+    }
+}
+
+/// remembers the last result so we only ray-march every `UPDATE_INTERVAL`
+#[derive(Debug, Default)]
+pub struct OcclusionTracker {
+    occlusion: Occlusion,
+    last_update: Option<Instant>,
+
+    /// last cutoff sent to the page
+    pub low_pass_frequency: Option<f32>,
+}
+
+impl OcclusionTracker {
+    pub fn get(&self) -> Occlusion {
+        self.occlusion
+    }
+
+    pub fn update(&mut self, listener: Vector3<f32>, source: Vector3<f32>) -> Occlusion {
+        if !options::OCCLUSION.get().unwrap_or(false) {
+            self.occlusion = Occlusion::NONE;
+            self.last_update = None;
+            return self.occlusion;
+        }
+
+        let now = Instant::now();
+        if self
+            .last_update
+            .is_some_and(|last_update| now - last_update < UPDATE_INTERVAL)
+        {
+            return self.occlusion;
+        }
+        self.last_update = Some(now);
+
+        self.occlusion = Occlusion {
+            blocks: count_solid_blocks(listener, source, MAX_BLOCKS, is_solid_block),
+        };
+        self.occlusion
+    }
+}
+
+/// walks every block cell the line passes through (Amanatides & Woo),
+/// the cells at either end aren't counted since screens are usually
+/// placed right up against a wall
+pub fn count_solid_blocks<F>(
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    limit: u32,
+    mut is_solid: F,
+) -> u32
+where
+    F: FnMut(i32, i32, i32) -> bool,
+{
+    let diff = to - from;
+    let length = diff.magnitude();
+    if !length.is_finite() || length < 0.0001 {
+        return 0;
+    }
+    let dir = diff / length;
+
+    let mut cell = [
+        from.x.floor() as i32,
+        from.y.floor() as i32,
+        from.z.floor() as i32,
+    ];
+    let end = [
+        to.x.floor() as i32,
+        to.y.floor() as i32,
+        to.z.floor() as i32,
+    ];
+
+    let mut step = [0; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for (axis, &d) in dir.iter().enumerate() {
+        if d > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = ((cell[axis] + 1) as f32 - from[axis]) / d;
+            t_delta[axis] = 1.0 / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (from[axis] - cell[axis] as f32) / -d;
+            t_delta[axis] = 1.0 / -d;
+        }
+    }
+
+    let mut blocks = 0;
+    while blocks < limit {
+        let axis = if t_max[0] < t_max[1] {
+            if t_max[0] < t_max[2] { 0 } else { 2 }
+        } else if t_max[1] < t_max[2] {
+            1
+        } else {
+            2
+        };
+
+        if t_max[axis] > length {
+            break;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        if cell == end {
+            break;
+        }
+
+        if is_solid(cell[0], cell[1], cell[2]) {
+            blocks += 1;
+        }
+    }
+
+    blocks
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn is_solid_block(x: i32, y: i32, z: i32) -> bool {
+    unsafe {
+        if World.Blocks.is_null() {
+            return false;
+        }
+
+        if x < 0 || y < 0 || z < 0 || x >= World.Width || y >= World.Height || z >= World.Length {
+            return false;
+        }
+
+        let index = ((y * World.Length + z) * World.Width + x) as usize;
+        let mut block = usize::from(*World.Blocks.add(index));
+        if !World.Blocks2.is_null() {
+            block |= usize::from(*World.Blocks2.add(index)) << 8;
+        }
+        block &= World.IDMask as usize;
+
+        let collide = u32::from(Blocks.Collide[block]);
+        collide == CollideType_COLLIDE_SOLID
+            || collide == CollideType_COLLIDE_ICE
+            || collide == CollideType_COLLIDE_SLIPPERY_ICE
+    }
+}
+
+#[test]
+fn test_count_solid_blocks() {
+    // a 2 block thick wall at x = 5..=6
+    let wall = |x: i32, _y: i32, _z: i32| x == 5 || x == 6;
+
+    let from = Vector3::new(0.5, 1.5, 0.5);
+    let to = Vector3::new(10.5, 1.5, 0.5);
+    assert_eq!(count_solid_blocks(from, to, MAX_BLOCKS, wall), 2);
+    assert_eq!(count_solid_blocks(to, from, MAX_BLOCKS, wall), 2);
+    assert_eq!(count_solid_blocks(from, to, 1, wall), 1);
+
+    // diagonal through the wall
+    let to = Vector3::new(10.5, 4.5, 3.5);
+    assert!(count_solid_blocks(from, to, MAX_BLOCKS, wall) >= 2);
+
+    // screen stuck inside the wall doesn't count its own block
+    let to = Vector3::new(5.5, 1.5, 0.5);
+    assert_eq!(count_solid_blocks(from, to, MAX_BLOCKS, wall), 0);
+
+    // nothing in the way
+    let from = Vector3::new(0.5, 1.5, 0.5);
+    let to = Vector3::new(4.5, 3.5, 2.5);
+    assert_eq!(count_solid_blocks(from, to, MAX_BLOCKS, wall), 0);
+    assert_eq!(count_solid_blocks(from, from, MAX_BLOCKS, wall), 0);
+}
+
+#[test]
+fn test_occlusion_falloff() {
+    assert!((Occlusion::NONE.get_volume() - 1.0).abs() < f32::EPSILON);
+    assert!((Occlusion::NONE.get_low_pass_frequency() - MAX_FREQUENCY).abs() < 1.0);
+
+    let mut last = Occlusion::NONE;
+    for blocks in 1..=MAX_BLOCKS {
+        let occlusion = Occlusion { blocks };
+        assert!(occlusion.get_volume() < last.get_volume());
+        assert!(occlusion.get_low_pass_frequency() < last.get_low_pass_frequency());
+        last = occlusion;
+    }
+    assert!((last.get_low_pass_frequency() - MIN_FREQUENCY).abs() < 1.0);
+
+    // capped
+    let thick = Occlusion { blocks: 100 };
+    assert!((thick.get_volume() - last.get_volume()).abs() < f32::EPSILON);
+}
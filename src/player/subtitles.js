@@ -0,0 +1,23 @@
+// subtitles for a page's <video>, injected by the plugin
+//
+// replaces any track we added before, vtt null just removes it
+if (typeof window.cefSetSubtitles === "undefined") {
+  window.cefSetSubtitles = function (video, vtt) {
+    var tracks = video.querySelectorAll("track");
+    for (var i = 0; i < tracks.length; i++) {
+      URL.revokeObjectURL(tracks[i].src);
+      tracks[i].remove();
+    }
+    if (vtt === null) {
+      return;
+    }
+
+    var track = document.createElement("track");
+    track.kind = "subtitles";
+    track.label = "Subtitles";
+    track.default = true;
+    track.src = URL.createObjectURL(new Blob([vtt], { type: "text/vtt" }));
+    video.appendChild(track);
+    track.track.mode = "showing";
+  };
+}
@@ -0,0 +1,233 @@
+//! external SRT/WebVTT subtitles for the `<video>` players
+
+use std::time::Duration;
+
+use classicube_helpers::{async_manager, color::RED};
+use tracing::{debug, warn};
+
+use crate::{
+    cef::Browser,
+    chat::Chat,
+    error::{Error, Result, ResultExt, bail, ensure},
+};
+
+/// don't let someone make everyone download something huge
+const MAX_SUBTITLES_SIZE: usize = 2 * 1024 * 1024;
+
+const SUBTITLES_JS: &str = include_str!("subtitles.js");
+
+/// SRT or WebVTT text to WebVTT
+pub fn to_vtt(input: &str) -> Result<String> {
+    let input = input.trim_start_matches('\u{feff}');
+    let lines: Vec<&str> = input.lines().map(str::trim_end).collect();
+
+    if lines.first().is_some_and(|line| line.starts_with("WEBVTT")) {
+        let mut vtt = lines.join("\n");
+        vtt.push('\n');
+        return Ok(vtt);
+    }
+
+    let mut vtt = String::from("WEBVTT\n");
+    let mut cues = 0;
+
+    for block in lines.split(|line| line.is_empty()) {
+        if block.is_empty() {
+            continue;
+        }
+
+        // the cue number is optional
+        let timing_index = block
+            .iter()
+            .position(|line| line.contains("-->"))
+            .chain_err(|| format!("no timing line in cue {:?}", block[0]))?;
+        ensure!(timing_index <= 1, "unexpected text before timing line");
+
+        let (start, end) = parse_srt_timing(block[timing_index])?;
+        let text = block[timing_index + 1..].join("\n");
+
+        vtt.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            format_vtt_timestamp(start),
+            format_vtt_timestamp(end),
+            text
+        ));
+        cues += 1;
+    }
+
+    ensure!(cues > 0, "no subtitles found");
+
+    Ok(vtt)
+}
+
+/// `00:00:01,000 --> 00:00:04,000`
+fn parse_srt_timing(line: &str) -> Result<(Duration, Duration)> {
+    let (start, end) = line.split_once("-->").chain_err(|| "missing -->")?;
+
+    // some files put positions after the end time
+    let end = end.split_whitespace().next().unwrap_or_default();
+
+    Ok((
+        parse_srt_timestamp(start.trim())?,
+        parse_srt_timestamp(end)?,
+    ))
+}
+
+/// `01:02:03,456`, also accepts `.` and no hours
+fn parse_srt_timestamp(timestamp: &str) -> Result<Duration> {
+    let (clock, millis) = timestamp.split_once([',', '.']).unwrap_or((timestamp, "0"));
+
+    let parts = clock
+        .split(':')
+        .map(str::parse::<u64>)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .chain_err(|| format!("invalid timestamp {timestamp:?}"))?;
+    let seconds = match parts.as_slice() {
+        [minutes, seconds] => minutes * 60 + seconds,
+        [hours, minutes, seconds] => hours * 60 * 60 + minutes * 60 + seconds,
+        _ => bail!("invalid timestamp {:?}", timestamp),
+    };
+
+    ensure!(
+        !millis.is_empty() && millis.len() <= 3,
+        "invalid timestamp {:?}",
+        timestamp
+    );
+    // "5" means 500ms
+    let millis = format!("{millis:0<3}").parse::<u64>()?;
+
+    Ok(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+fn format_vtt_timestamp(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+        time.subsec_millis()
+    )
+}
+
+pub async fn fetch(url: &str) -> Result<String> {
+    let url = url.to_string();
+
+    let text = async_manager::spawn(async move {
+        let mut response = reqwest::get(&url).await?.error_for_status()?;
+        ensure!(
+            response
+                .content_length()
+                .is_none_or(|length| length <= MAX_SUBTITLES_SIZE as u64),
+            "subtitle file too big"
+        );
+
+        // servers can leave out or lie about the length
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            ensure!(
+                bytes.len() + chunk.len() <= MAX_SUBTITLES_SIZE,
+                "subtitle file too big"
+            );
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok::<_, Error>(String::from_utf8_lossy(&bytes).to_string())
+    })
+    .await??;
+
+    to_vtt(&text)
+}
+
+/// `set_subtitles` for the `<video>` players, `video` finds the element
+pub fn set(
+    subtitles: &mut Option<String>,
+    browser: Option<&Browser>,
+    video: &str,
+    url: Option<String>,
+) {
+    if let Some(browser) = browser {
+        load(browser, video, url.clone());
+    }
+
+    *subtitles = url;
+}
+
+/// fetch the subtitles and put them on the page's `video`
+pub fn load(browser: &Browser, video: &str, url: Option<String>) {
+    let browser = browser.clone();
+    let video = video.to_string();
+
+    async_manager::spawn_local_on_main_thread(async move {
+        let result = async {
+            let vtt = match url {
+                Some(url) => {
+                    debug!("loading subtitles {}", url);
+                    serde_json::to_string(&fetch(&url).await?)?
+                }
+                None => "null".to_string(),
+            };
+
+            browser.execute_javascript(format!(
+                "{SUBTITLES_JS}\n(() => {{\n  var video = {video};\n  if (video) {{\n    \
+                 cefSetSubtitles(video, {vtt});\n  }}\n}})();\n"
+            ))?;
+
+            Ok::<_, Error>(())
+        };
+
+        if let Err(e) = result.await {
+            warn!("subtitles: {}", e);
+            Chat::print(format!("{RED}couldn't load subtitles: {e}"));
+        }
+    });
+}
+
+#[test]
+fn test_srt_to_vtt() {
+    let srt = "\u{feff}1\r\n\
+               00:00:01,000 --> 00:00:04,500\r\n\
+               Hello\r\n\
+               world!\r\n\
+               \r\n\
+               2\r\n\
+               00:01:02,05 --> 01:00:00,000 X1:40 X2:600\r\n\
+               <i>Bye</i>\r\n\
+               \r\n\
+               \r\n";
+
+    assert_eq!(
+        to_vtt(srt).unwrap(),
+        "WEBVTT\n\
+         \n\
+         00:00:01.000 --> 00:00:04.500\n\
+         Hello\n\
+         world!\n\
+         \n\
+         00:01:02.050 --> 01:00:00.000\n\
+         <i>Bye</i>\n"
+    );
+
+    // without cue numbers
+    assert_eq!(
+        to_vtt("00:05,1 --> 00:06,2\nhi\n").unwrap(),
+        "WEBVTT\n\n00:00:05.100 --> 00:00:06.200\nhi\n"
+    );
+}
+
+#[test]
+fn test_vtt_passthrough() {
+    let vtt = "WEBVTT\r\n\r\n00:00:01.000 --> 00:00:02.000\r\nhi\r\n";
+    assert_eq!(
+        to_vtt(vtt).unwrap(),
+        "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nhi\n"
+    );
+}
+
+#[test]
+fn test_bad_subtitles() {
+    assert!(to_vtt("").is_err());
+    assert!(to_vtt("hello\nthere").is_err());
+    assert!(to_vtt("1\n00:00:01 -> 00:00:02\nhi").is_err());
+    assert!(to_vtt("1\n00:00:aa,000 --> 00:00:02,000\nhi").is_err());
+    assert!(to_vtt("1\n00:00:01,0000 --> 00:00:02,000\nhi").is_err());
+}
@@ -1,4 +1,7 @@
-use std::{cell::Cell, time::Duration};
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
 
 use classicube_helpers::async_manager;
 use futures::{future::RemoteHandle, prelude::*};
@@ -10,6 +13,45 @@ use crate::{
     player::{Player, PlayerTrait},
 };
 
+/// linear interpolation from one volume to another over time
+#[derive(Debug, Clone, Copy)]
+pub struct Fade {
+    pub from: f32,
+    pub to: f32,
+    start_time: Instant,
+    seconds: f32,
+}
+
+impl Fade {
+    pub fn new(from: f32, to: f32, seconds: f32) -> Self {
+        Self {
+            from,
+            to,
+            start_time: Instant::now(),
+            seconds,
+        }
+    }
+
+    pub fn get_at(&self, now: Instant) -> f32 {
+        let percent = if self.seconds > 0.0 {
+            let secs_from_start = now.saturating_duration_since(self.start_time).as_secs_f32();
+            (secs_from_start / self.seconds).min(1.0)
+        } else {
+            1.0
+        };
+
+        self.from + (self.to - self.from) * percent
+    }
+
+    pub fn get(&self) -> f32 {
+        self.get_at(Instant::now())
+    }
+
+    pub fn is_finished_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start_time).as_secs_f32() > self.seconds
+    }
+}
+
 thread_local!(
     static FADING_HANDLE: Cell<Option<RemoteHandle<()>>> = Cell::default();
 );
@@ -40,6 +82,15 @@ pub fn on_new_map_loaded() {
     });
 }
 
+pub fn is_fading() -> bool {
+    FADING_HANDLE.with(|cell| {
+        let handle = cell.take();
+        let is_fading = handle.is_some();
+        cell.set(handle);
+        is_fading
+    })
+}
+
 pub fn shutdown() {
     FADING_HANDLE.with(|cell| {
         cell.set(None);
@@ -80,3 +131,21 @@ async fn fade_all() -> Result<()> {
         async_manager::sleep(Duration::from_millis(32)).await;
     }
 }
+
+#[test]
+fn test_fade() {
+    let fade = Fade::new(1.0, 0.0, 2.0);
+    let start = fade.start_time;
+
+    assert!((fade.get_at(start) - 1.0).abs() < f32::EPSILON);
+    assert!((fade.get_at(start + Duration::from_millis(500)) - 0.75).abs() < 0.001);
+    assert!((fade.get_at(start + Duration::from_secs(1)) - 0.5).abs() < 0.001);
+    assert!(!fade.is_finished_at(start + Duration::from_secs(1)));
+
+    assert!(fade.get_at(start + Duration::from_secs(5)).abs() < f32::EPSILON);
+    assert!(fade.is_finished_at(start + Duration::from_secs(5)));
+
+    // instant
+    let fade = Fade::new(0.2, 0.8, 0.0);
+    assert!((fade.get_at(fade.start_time) - 0.8).abs() < f32::EPSILON);
+}
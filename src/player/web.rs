@@ -3,17 +3,37 @@ use serde::{Deserialize, Serialize};
 use tracing::debug;
 use url::Url;
 
-use super::PlayerTrait;
-use crate::{cef::RustRefBrowser, chat::Chat, error::Result};
-
-#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+use super::{PlayerTrait, VolumeMode};
+use crate::{
+    audio,
+    cef::{self, Browser},
+    chat::Chat,
+    error::Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WebPlayer {
     url: String,
 
+    // 0-1, only applied with native audio, kept until then
+    volume: f32,
+    volume_mode: VolumeMode,
+
     #[serde(skip)]
     last_title: String,
 }
 
+impl Default for WebPlayer {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            volume: 1.0,
+            volume_mode: VolumeMode::Global,
+            last_title: String::new(),
+        }
+    }
+}
+
 impl PlayerTrait for WebPlayer {
     fn type_name(&self) -> &'static str {
         "Web"
@@ -33,6 +53,14 @@ impl PlayerTrait for WebPlayer {
             }
         }
 
+        // from everyone's own cef/www
+        if cef::is_local_file(&url) {
+            return Ok(Self {
+                url: url.to_string(),
+                ..Default::default()
+            });
+        }
+
         if url.scheme() != "http" && url.scheme() != "https" {
             return Err("not http/https".into());
         }
@@ -65,7 +93,7 @@ impl PlayerTrait for WebPlayer {
         Ok(url)
     }
 
-    fn on_title_change(&mut self, _entity_id: usize, _browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, _entity_id: usize, _browser: &Browser, title: String) {
         if self.last_title == title || title == Self::blank_page().url {
             return;
         }
@@ -75,6 +103,49 @@ impl PlayerTrait for WebPlayer {
         self.last_title = title;
     }
 
+    fn on_address_change(&mut self, _entity_id: usize, _browser: &Browser, url: String) {
+        if self.url == url {
+            return;
+        }
+
+        // keep a url everyone else can open so sync still works
+        match Self::from_input(&url) {
+            Ok(player) => {
+                debug!("WebPlayer navigated {} -> {}", self.url, player.url);
+                self.url = player.url;
+            }
+            Err(e) => {
+                debug!("WebPlayer not following {}: {}", url, e);
+            }
+        }
+    }
+
+    fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    fn set_volume(&mut self, browser: Option<&Browser>, volume: f32) -> Result<()> {
+        if let Some(browser) = browser {
+            Self::note_native_audio(browser);
+        }
+
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn get_volume_mode(&self) -> VolumeMode {
+        self.volume_mode
+    }
+
+    fn set_volume_mode(&mut self, browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
+        if let Some(browser) = browser {
+            Self::note_native_audio(browser);
+        }
+
+        self.volume_mode = mode;
+        Ok(())
+    }
+
     fn get_url(&self) -> String {
         self.url.clone()
     }
@@ -97,6 +168,18 @@ impl WebPlayer {
             ..Default::default()
         }
     }
+
+    /// a website's audio can only be changed once we're the ones playing
+    /// it, until then the audio module picks the stored values up when
+    /// capture starts
+    fn note_native_audio(browser: &Browser) {
+        if !audio::is_capturing(browser.get_identifier()) {
+            debug!(
+                "WebPlayer volume saved for when native audio starts on {}",
+                browser.get_identifier()
+            );
+        }
+    }
 }
 
 #[test]
@@ -107,6 +190,7 @@ fn test_web() {
             "http://google.com/bap",
             "https://google.com/bap",
             "http://github.com/bap?okay=yes",
+            "local://files/slides/index.html",
         ];
 
         for &url in &okay_urls {
@@ -126,6 +210,7 @@ fn test_web() {
             "asdf",
             "ftp://google.com/file.txt",
             "data:text/html,<html>ohno</html>",
+            "local://media/",
             "",
         ];
 
@@ -134,3 +219,18 @@ fn test_web() {
         }
     }
 }
+
+#[test]
+fn test_on_address_change() {
+    use crate::cef::FakeBrowser;
+
+    let browser = Browser::Fake(FakeBrowser::new(1));
+    let mut player = WebPlayer::from_input("https://example.com/").unwrap();
+
+    player.on_address_change(0, &browser, "https://example.com/watch?v=2".to_string());
+    assert_eq!(player.get_url(), "https://example.com/watch?v=2");
+
+    // pages can go places other players can't
+    player.on_address_change(0, &browser, "data:text/html,hi".to_string());
+    assert_eq!(player.get_url(), "https://example.com/watch?v=2");
+}
@@ -13,15 +13,23 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use url::Url;
 
-use super::{PlayerTrait, VolumeMode, helpers::start_update_loop};
+use super::{
+    PlayerTrait, VolumeMode,
+    helpers::{
+        get_audio_graph_javascript, get_low_pass_javascript, get_volume_mode_javascript,
+        start_update_loop,
+    },
+};
 use crate::{
-    cef::{RustRefBrowser, RustV8Value},
+    cef::{Browser, RustV8Value},
     chat::Chat,
     error::{Result, bail},
     options,
     options::SUBTITLES,
 };
 
+const VIDEO_ELEMENT_JS: &str = r#"document.getElementsByTagName("video")[0]"#;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct YouTubePlayer {
     pub id: String,
@@ -145,13 +153,13 @@ impl PlayerTrait for YouTubePlayer {
         Ok(Url::parse_with_params("https://classicube-cef.invalid/youtube", &params)?.into())
     }
 
-    fn on_page_loaded(&mut self, entity_id: usize, _browser: &RustRefBrowser) {
+    fn on_page_loaded(&mut self, entity_id: usize, _browser: &Browser) {
         let (f, remote_handle) = start_update_loop(entity_id).remote_handle();
         self.update_loop_handle = Some(remote_handle);
         async_manager::spawn_local_on_main_thread(f);
     }
 
-    fn on_title_change(&mut self, _entity_id: usize, browser: &RustRefBrowser, title: String) {
+    fn on_title_change(&mut self, _entity_id: usize, browser: &Browser, title: String) {
         if self.last_title == title || title == "YouTube Loading" {
             return;
         }
@@ -162,6 +170,13 @@ impl PlayerTrait for YouTubePlayer {
 
         self.last_title = title;
 
+        // the video lives in youtube's iframe, which isn't there yet when
+        // our page finishes loading
+        let _ignore = browser.execute_javascript_on_frame(
+            "https://www.youtube.com",
+            get_audio_graph_javascript(VIDEO_ELEMENT_JS, self.volume_mode),
+        );
+
         // playlists will show multiple titles
         if self.autoplay && !self.is_playlist {
             let now = Instant::now();
@@ -185,7 +200,7 @@ impl PlayerTrait for YouTubePlayer {
         Ok(self.time)
     }
 
-    fn set_current_time(&mut self, browser: &RustRefBrowser, time: Duration) -> Result<()> {
+    fn set_current_time(&mut self, browser: &Browser, time: Duration) -> Result<()> {
         Self::execute(browser, &format!("setCurrentTime({})", time.as_secs_f32()))?;
         self.time = time;
 
@@ -197,7 +212,7 @@ impl PlayerTrait for YouTubePlayer {
     }
 
     /// volume is a float between 0-1
-    fn set_volume(&mut self, browser: Option<&RustRefBrowser>, volume: f32) -> Result<()> {
+    fn set_volume(&mut self, browser: Option<&Browser>, volume: f32) -> Result<()> {
         if let Some(browser) = browser
             && (volume - self.volume).abs() > 0.0001
         {
@@ -214,58 +229,34 @@ impl PlayerTrait for YouTubePlayer {
         self.volume_mode
     }
 
-    fn set_volume_mode(
-        &mut self,
-        browser: Option<&RustRefBrowser>,
-        mode: VolumeMode,
-    ) -> Result<()> {
-        if let Some(browser) = browser {
-            if let VolumeMode::Panning { pan, .. } = mode {
-                // TODO less big string!
-                let _ignore = browser.execute_javascript_on_frame(
-                    "https://www.youtube.com",
-                    format!(
-                        r#"
-                            if (typeof window.panner === "undefined") {{
-                                var video = document.getElementsByTagName("video")[0];
-                                var context = new AudioContext();
-                                var source = context.createMediaElementSource(video);
-                                var panner = context.createStereoPanner();
-                                source.connect(panner);
-                                panner.connect(context.destination);
-                                window.panner = panner;
-                                window.context = context;
-                            }}
-                            window.panner.pan.setTargetAtTime(
-                                {pan},
-                                window.context.currentTime,
-                                0.02
-                            );
-                        "#
-                    ),
-                );
-            } else {
-                let _ignore = browser.execute_javascript_on_frame(
-                    "https://www.youtube.com",
-                    r#"
-                        if (typeof window.panner !== "undefined") {
-                            window.panner.pan.value = 0.0;
-                        }
-                    "#,
-                );
-            }
+    fn set_volume_mode(&mut self, browser: Option<&Browser>, mode: VolumeMode) -> Result<()> {
+        if let Some(browser) = browser
+            && mode != self.volume_mode
+        {
+            let _ignore = browser.execute_javascript_on_frame(
+                "https://www.youtube.com",
+                get_volume_mode_javascript(VIDEO_ELEMENT_JS, mode),
+            );
         }
 
         self.volume_mode = mode;
         Ok(())
     }
 
-    fn set_autoplay(&mut self, _browser: Option<&RustRefBrowser>, autoplay: bool) -> Result<()> {
+    fn set_low_pass(&mut self, browser: &Browser, frequency: Option<f32>) -> Result<()> {
+        browser.execute_javascript_on_frame(
+            "https://www.youtube.com",
+            get_low_pass_javascript(VIDEO_ELEMENT_JS, frequency),
+        )?;
+        Ok(())
+    }
+
+    fn set_autoplay(&mut self, _browser: Option<&Browser>, autoplay: bool) -> Result<()> {
         self.autoplay = autoplay;
         Ok(())
     }
 
-    fn set_loop(&mut self, _browser: Option<&RustRefBrowser>, should_loop: bool) -> Result<()> {
+    fn set_loop(&mut self, _browser: Option<&Browser>, should_loop: bool) -> Result<()> {
         self.should_loop = should_loop;
         Ok(())
     }
@@ -287,7 +278,7 @@ impl PlayerTrait for YouTubePlayer {
         self.finished
     }
 
-    fn set_playing(&mut self, browser: &RustRefBrowser, playing: bool) -> Result<()> {
+    fn set_playing(&mut self, browser: &Browser, playing: bool) -> Result<()> {
         Self::execute(browser, &format!("setPlaying({playing})"))?;
         Ok(())
     }
@@ -297,7 +288,7 @@ impl PlayerTrait for YouTubePlayer {
         Ok(())
     }
 
-    fn set_speed(&mut self, browser: Option<&RustRefBrowser>, speed: f32) -> Result<()> {
+    fn set_speed(&mut self, browser: Option<&Browser>, speed: f32) -> Result<()> {
         if let Some(browser) = browser {
             Self::execute(browser, &format!("setPlaybackRate({speed})"))?;
         }
@@ -308,7 +299,7 @@ impl PlayerTrait for YouTubePlayer {
 }
 
 impl YouTubePlayer {
-    pub async fn real_is_finished_playing(browser: &RustRefBrowser) -> Result<bool> {
+    pub async fn real_is_finished_playing(browser: &Browser) -> Result<bool> {
         let ended = match Self::eval(browser, "playerFinished").await? {
             RustV8Value::Bool(ended) => ended,
 
@@ -320,7 +311,7 @@ impl YouTubePlayer {
         Ok(ended)
     }
 
-    pub async fn get_real_time(browser: &RustRefBrowser) -> Result<Duration> {
+    pub async fn get_real_time(browser: &Browser) -> Result<Duration> {
         let seconds = match Self::eval(browser, "getCurrentTime()").await? {
             RustV8Value::Double(seconds) => seconds as f32,
             RustV8Value::Int(seconds) => seconds as f32,
@@ -334,13 +325,13 @@ impl YouTubePlayer {
         Ok(Duration::from_secs_f32(seconds))
     }
 
-    fn execute(browser: &RustRefBrowser, method: &str) -> Result<()> {
+    fn execute(browser: &Browser, method: &str) -> Result<()> {
         let code = format!("window.{method};");
         browser.execute_javascript(code)?;
         Ok(())
     }
 
-    async fn eval(browser: &RustRefBrowser, method: &str) -> Result<RustV8Value> {
+    async fn eval(browser: &Browser, method: &str) -> Result<RustV8Value> {
         let code = format!("window.{method};");
         browser.eval_javascript(code).await
     }
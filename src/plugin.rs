@@ -7,7 +7,7 @@ use classicube_helpers::{WithInner, async_manager, color::RED};
 use classicube_sys::{Server, String_AppendConst};
 use tracing::{debug, error};
 
-use crate::{cef::Cef, chat::Chat, entity_manager::EntityManager, player};
+use crate::{cef::Cef, chat::Chat, entity_manager::EntityManager, player, scheduler};
 
 thread_local!(
     static PLUGIN: RefCell<Option<Plugin>> = const { RefCell::new(None) };
@@ -57,6 +57,7 @@ impl Plugin {
 
             async_manager::initialize();
             chat.initialize();
+            player::initialize();
 
             async_manager::spawn_local_on_main_thread(async {
                 if let Err(e) = Cef::initialize().await {
@@ -118,6 +119,7 @@ impl Plugin {
             let plugin = &mut *cell.borrow_mut();
             let mut plugin = plugin.take().unwrap();
 
+            scheduler::cancel_all();
             plugin.entity_manager.shutdown();
             plugin.chat.shutdown();
             player::shutdown();
@@ -137,6 +139,7 @@ impl Plugin {
 
         PLUGIN
             .with_inner_mut(|plugin| {
+                scheduler::cancel_all();
                 plugin.entity_manager.reset();
                 plugin.chat.reset();
             })
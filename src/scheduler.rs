@@ -0,0 +1,300 @@
+//! runs cef commands later: at a time of day, after a delay, or when a
+//! screen's media reaches a timestamp
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt, mem,
+    time::Duration,
+};
+
+use classicube_helpers::async_manager;
+use classicube_sys::{DateTime, DateTime_CurrentLocal};
+use futures::{future::RemoteHandle, prelude::*};
+use tracing::{debug, warn};
+
+use crate::{
+    chat::{PlayerSnapshot, commands},
+    entity_manager::EntityManager,
+    error::{Result, bail},
+    helpers::{format_duration, parse_duration},
+    player::PlayerTrait,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const MEDIA_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+thread_local!(
+    static NEXT_ID: Cell<usize> = const { Cell::new(1) };
+);
+
+// id, task
+thread_local!(
+    static TASKS: RefCell<BTreeMap<usize, ScheduledTask>> = RefCell::default();
+);
+
+struct ScheduledTask {
+    trigger: Trigger,
+    command: String,
+    handle: RemoteHandle<()>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// `20:00` or `20:00:30`, the next time the local clock reads this
+    TimeOfDay { seconds: u64 },
+
+    /// `+30m`, `30m` or `90s`
+    Delay(Duration),
+
+    /// `@3:10`, when the screen's media crosses this timestamp
+    MediaTime { entity_id: usize, time: Duration },
+}
+
+impl Trigger {
+    /// `entity_id` is used for media time triggers
+    pub fn parse(when: &str, entity_id: impl FnOnce() -> Result<usize>) -> Result<Self> {
+        if let Some(time) = when.strip_prefix('@') {
+            let time = parse_clock(time)
+                .or_else(|| parse_duration(time))
+                .ok_or_else(|| format!("invalid media time {time:?}"))?;
+
+            return Ok(Self::MediaTime {
+                entity_id: entity_id()?,
+                time,
+            });
+        }
+
+        if let Some(delay) = when.strip_prefix('+') {
+            return parse_duration(delay)
+                .map(Self::Delay)
+                .ok_or_else(|| format!("invalid delay {delay:?}").into());
+        }
+
+        if when.contains(':') {
+            let seconds =
+                parse_time_of_day(when).ok_or_else(|| format!("invalid time of day {when:?}"))?;
+
+            return Ok(Self::TimeOfDay { seconds });
+        }
+
+        parse_duration(when)
+            .map(Self::Delay)
+            .ok_or_else(|| format!("invalid time {when:?}").into())
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TimeOfDay { seconds } => {
+                let time = format_duration(Duration::from_secs(*seconds));
+                if *seconds < 60 * 60 {
+                    // format_duration leaves out the hours
+                    write!(f, "at 00:{time}")
+                } else {
+                    write!(f, "at {time}")
+                }
+            }
+            Self::Delay(delay) => write!(f, "after {}", format_duration(*delay)),
+            Self::MediaTime { entity_id, time } => {
+                write!(f, "at {} on screen {}", format_duration(*time), entity_id)
+            }
+        }
+    }
+}
+
+fn split_clock(input: &str) -> Option<Vec<u64>> {
+    input
+        .split(':')
+        .map(|part| {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                None
+            } else {
+                part.parse::<u64>().ok()
+            }
+        })
+        .collect()
+}
+
+/// `20:00`, `20:00:30`
+fn parse_time_of_day(input: &str) -> Option<u64> {
+    let seconds = match split_clock(input)?.as_slice() {
+        [hours, minutes] if *hours < 24 && *minutes < 60 => hours * 60 * 60 + minutes * 60,
+        [hours, minutes, seconds] if *hours < 24 && *minutes < 60 && *seconds < 60 => {
+            hours * 60 * 60 + minutes * 60 + seconds
+        }
+        _ => return None,
+    };
+
+    Some(seconds)
+}
+
+/// `3:10`, `1:02:03`
+fn parse_clock(input: &str) -> Option<Duration> {
+    let seconds = match split_clock(input)?.as_slice() {
+        [minutes, seconds] if *seconds < 60 => minutes * 60 + seconds,
+        [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+            hours * 60 * 60 + minutes * 60 + seconds
+        }
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(seconds))
+}
+
+fn seconds_since_midnight() -> u64 {
+    let mut now: DateTime = unsafe { mem::zeroed() };
+    unsafe {
+        DateTime_CurrentLocal(&raw mut now);
+    }
+
+    now.hour as u64 * 60 * 60 + now.minute as u64 * 60 + now.second as u64
+}
+
+async fn wait_for(trigger: Trigger) -> Result<()> {
+    match trigger {
+        Trigger::TimeOfDay { seconds } => {
+            let now = seconds_since_midnight();
+            let remaining = (seconds + SECONDS_PER_DAY - now) % SECONDS_PER_DAY;
+            async_manager::sleep(Duration::from_secs(remaining)).await;
+        }
+
+        Trigger::Delay(delay) => {
+            async_manager::sleep(delay).await;
+        }
+
+        Trigger::MediaTime { entity_id, time } => {
+            let get_time =
+                || EntityManager::with_entity(entity_id, |entity| entity.player.get_current_time());
+
+            // only fire when playback crosses the time, so scheduling
+            // "@3:10 skip" at 3:20 doesn't skip right away
+            let mut last = get_time()?;
+            loop {
+                async_manager::sleep(MEDIA_POLL_INTERVAL).await;
+
+                let current = get_time()?;
+                if last < time && time <= current {
+                    break;
+                }
+                last = current;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn schedule(player: PlayerSnapshot, trigger: Trigger, command: Vec<String>) -> Result<usize> {
+    if command.is_empty() {
+        bail!("no command to schedule");
+    }
+
+    let id = NEXT_ID.get();
+    NEXT_ID.set(id + 1);
+
+    let command_string = command.join(" ");
+    let (f, remote_handle) = async move {
+        let result = wait_for(trigger).await;
+
+        // let the command finish even though we're no longer listed
+        if let Some(task) = TASKS.with(|cell| cell.borrow_mut().remove(&id)) {
+            task.handle.forget();
+        }
+
+        if let Err(e) = result {
+            warn!("scheduled task {} gave up: {}", id, e);
+            return;
+        }
+
+        debug!("scheduled task {} running {:?}", id, command);
+        if let Err(e) = commands::run(player, command, true, true).await {
+            warn!("scheduled task {}: {}", id, e);
+        }
+    }
+    .remote_handle();
+
+    TASKS.with(|cell| {
+        cell.borrow_mut().insert(
+            id,
+            ScheduledTask {
+                trigger,
+                command: command_string,
+                handle: remote_handle,
+            },
+        );
+    });
+    async_manager::spawn_local_on_main_thread(f);
+
+    Ok(id)
+}
+
+/// (id, trigger, command)
+pub fn list() -> Vec<(usize, Trigger, String)> {
+    TASKS.with(|cell| {
+        cell.borrow()
+            .iter()
+            .map(|(id, task)| (*id, task.trigger, task.command.clone()))
+            .collect()
+    })
+}
+
+pub fn cancel(id: usize) -> Result<()> {
+    // dropping the handle stops the task
+    if TASKS.with(|cell| cell.borrow_mut().remove(&id)).is_none() {
+        bail!("no scheduled task with id {}", id);
+    }
+
+    Ok(())
+}
+
+pub fn cancel_all() {
+    let tasks = TASKS.with(|cell| mem::take(&mut *cell.borrow_mut()));
+    drop(tasks);
+}
+
+#[test]
+fn test_parse_trigger() {
+    let no_entity = || -> Result<usize> { bail!("no entity") };
+
+    for (a, b) in [
+        (
+            "20:00",
+            Trigger::TimeOfDay {
+                seconds: 20 * 60 * 60,
+            },
+        ),
+        (
+            "7:05:30",
+            Trigger::TimeOfDay {
+                seconds: 7 * 60 * 60 + 5 * 60 + 30,
+            },
+        ),
+        ("+30m", Trigger::Delay(Duration::from_secs(30 * 60))),
+        ("90s", Trigger::Delay(Duration::from_secs(90))),
+        ("1m30s", Trigger::Delay(Duration::from_secs(90))),
+    ] {
+        assert_eq!(Trigger::parse(a, no_entity).unwrap(), b, "{a:?}");
+    }
+
+    assert_eq!(
+        Trigger::parse("@3:10", || Ok(4)).unwrap(),
+        Trigger::MediaTime {
+            entity_id: 4,
+            time: Duration::from_secs(3 * 60 + 10),
+        }
+    );
+    assert_eq!(
+        Trigger::parse("@90s", || Ok(1)).unwrap(),
+        Trigger::MediaTime {
+            entity_id: 1,
+            time: Duration::from_secs(90),
+        }
+    );
+
+    for bad in ["24:00", "20:60", "20:", "+", "soon", "@", "@3:xx", "-5m"] {
+        assert!(Trigger::parse(bad, || Ok(1)).is_err(), "{bad:?}");
+    }
+    assert!(Trigger::parse("@3:10", no_entity).is_err());
+}
@@ -0,0 +1,327 @@
+//! custom scripts and styles for pages, read from `cef/userscripts`
+//!
+//! ```text
+//! // @match https://www.youtube.com/*
+//! // @match *://*.example.com/*
+//! // @screen lobby
+//! document.querySelector(".skip-intro")?.click();
+//! ```
+//!
+//! `.js` files are run and `.css` files are added as a `<style>`, on every
+//! page load and navigation. Without `@match` they apply to every url, and
+//! without `@screen` to every screen.
+
+use std::{
+    cell::RefCell,
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use tracing::{debug, warn};
+
+use crate::{
+    cef::Browser,
+    error::{Result, ResultExt, bail},
+    options,
+};
+
+/// file names and modified times, to notice edits without reading them
+type Stamps = Vec<(String, Option<SystemTime>)>;
+
+// what we read last time, so page loads don't read every file again
+thread_local!(
+    static CACHE: RefCell<Option<(Stamps, Vec<Userscript>)>> = RefCell::default();
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Js,
+    Css,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Userscript {
+    /// file name inside cef/userscripts
+    pub name: String,
+    pub kind: Kind,
+    /// `*` matches anything
+    pub matches: Vec<String>,
+    /// screen names
+    pub screens: Vec<String>,
+    source: String,
+}
+
+impl Userscript {
+    pub fn parse(name: &str, source: &str) -> Result<Self> {
+        let kind = if name.ends_with(".js") {
+            Kind::Js
+        } else if name.ends_with(".css") {
+            Kind::Css
+        } else {
+            bail!("userscript {:?} isn't a .js or .css file", name);
+        };
+
+        let mut matches = Vec::new();
+        let mut screens = Vec::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // the header is the comments at the top
+            let Some(comment) = ["//", "/*", "*"]
+                .iter()
+                .find_map(|start| line.strip_prefix(start))
+            else {
+                break;
+            };
+
+            let comment = comment.trim().trim_end_matches("*/").trim();
+            if let Some(pattern) = comment.strip_prefix("@match ") {
+                matches.push(pattern.trim().to_string());
+            } else if let Some(screen) = comment.strip_prefix("@screen ") {
+                screens.push(screen.trim().to_string());
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            kind,
+            matches,
+            screens,
+            source: source.to_string(),
+        })
+    }
+
+    pub fn is_match(&self, url: &str, screen_name: Option<&str>) -> bool {
+        let url_matches = self.matches.is_empty()
+            || self
+                .matches
+                .iter()
+                .any(|pattern| wildcard_match(pattern, url));
+
+        let screen_matches = self.screens.is_empty()
+            || screen_name.is_some_and(|screen_name| {
+                self.screens
+                    .iter()
+                    .any(|screen| screen.eq_ignore_ascii_case(screen_name))
+            });
+
+        url_matches && screen_matches
+    }
+
+    /// safe to run again on the same page, it only applies once
+    pub fn to_javascript(&self) -> Result<String> {
+        let id = serde_json::to_string(&format!("cef-userscript-{}", self.name))?;
+
+        Ok(match self.kind {
+            // not eval, pages with a content security policy would block it
+            Kind::Js => format!(
+                "(() => {{\n\
+                 const applied = (window.__cefUserscripts ??= {{}});\n\
+                 if (applied[{id}]) return;\n\
+                 applied[{id}] = true;\n\
+                 {}\n\
+                 }})();",
+                self.source
+            ),
+            Kind::Css => format!(
+                "(() => {{\n\
+                 if (document.getElementById({id})) return;\n\
+                 const style = document.createElement('style');\n\
+                 style.id = {id};\n\
+                 style.textContent = {};\n\
+                 (document.head ?? document.documentElement).appendChild(style);\n\
+                 }})();",
+                serde_json::to_string(&self.source)?
+            ),
+        })
+    }
+}
+
+/// `*` matches any run of characters, everything else is literal
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*`
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+fn get_dir() -> Result<PathBuf> {
+    Ok(env::current_dir()
+        .chain_err(|| "current_dir() None")?
+        .join("cef")
+        .join("userscripts"))
+}
+
+fn get_enabled_key(name: &str) -> String {
+    format!("cef-userscript-{name}")
+}
+
+pub fn is_enabled(name: &str) -> bool {
+    options::get(get_enabled_key(name)).is_none_or(|value| value != "disabled")
+}
+
+pub fn set_enabled(name: &str, enabled: bool) -> Result<()> {
+    if !load_all()?.iter().any(|script| script.name == name) {
+        bail!("no userscript {:?} in cef/userscripts", name);
+    }
+
+    let value = if enabled { "" } else { "disabled" };
+    options::set(get_enabled_key(name), value.to_string());
+
+    Ok(())
+}
+
+/// every userscript, files are only read again after they change
+pub fn load_all() -> Result<Vec<Userscript>> {
+    let dir = get_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let stamps = get_stamps(&dir)?;
+    let cached = CACHE.with_borrow(|cache| {
+        cache
+            .as_ref()
+            .filter(|(cached_stamps, _)| *cached_stamps == stamps)
+            .map(|(_, scripts)| scripts.clone())
+    });
+    if let Some(scripts) = cached {
+        return Ok(scripts);
+    }
+
+    let mut scripts = Vec::new();
+    for (name, _modified) in &stamps {
+        let path = dir.join(name);
+        // one broken file shouldn't stop the rest
+        match fs::read_to_string(&path)
+            .chain_err(|| format!("couldn't read {}", path.display()))
+            .and_then(|source| Userscript::parse(name, &source))
+        {
+            Ok(script) => scripts.push(script),
+            Err(e) => warn!("skipping userscript {}: {}", name, e),
+        }
+    }
+
+    CACHE.set(Some((stamps, scripts.clone())));
+    Ok(scripts)
+}
+
+/// forget what we read, for when a modified time didn't change
+pub fn reload() {
+    CACHE.take();
+}
+
+fn get_stamps(dir: &Path) -> Result<Stamps> {
+    let mut stamps = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(ToString::to_string) else {
+            continue;
+        };
+        if !(name.ends_with(".js") || name.ends_with(".css")) {
+            continue;
+        }
+
+        let Ok(metadata) = fs::metadata(entry.path()) else {
+            continue;
+        };
+        if metadata.is_file() {
+            stamps.push((name, metadata.modified().ok()));
+        }
+    }
+    stamps.sort();
+
+    Ok(stamps)
+}
+
+/// runs the enabled userscripts that match the page, returns how many
+pub fn apply(browser: &Browser, screen_name: Option<&str>) -> Result<usize> {
+    let url = browser.get_url();
+
+    let mut count = 0;
+    for script in load_all()? {
+        if is_enabled(&script.name) && script.is_match(&url, screen_name) {
+            debug!("userscript {} on {}", script.name, url);
+            browser.execute_javascript(script.to_javascript()?)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match(
+        "https://www.youtube.com/*",
+        "https://www.youtube.com/watch?v=1"
+    ));
+    assert!(wildcard_match(
+        "*://*.example.com/*",
+        "http://cdn.example.com/a.js"
+    ));
+    assert!(wildcard_match("*", ""));
+    assert!(wildcard_match("https://a.com/", "https://a.com/"));
+    assert!(wildcard_match("*a*a*", "banana"));
+
+    assert!(!wildcard_match("https://a.com/", "https://a.com/b"));
+    assert!(!wildcard_match(
+        "https://www.youtube.com/*",
+        "https://youtube.com/"
+    ));
+    assert!(!wildcard_match(
+        "*://*.example.com/*",
+        "https://example.org/"
+    ));
+    assert!(!wildcard_match("*aa*aa", "aaa"));
+}
+
+#[test]
+fn test_parse_userscript() {
+    let script = Userscript::parse(
+        "dark.css",
+        "/* @match https://a.com/*\n * @screen Lobby */\n\nbody { background: black; }\n\
+         /* @match ignored after the header */",
+    )
+    .unwrap();
+    assert_eq!(script.kind, Kind::Css);
+    assert_eq!(script.matches, vec!["https://a.com/*".to_string()]);
+    assert_eq!(script.screens, vec!["Lobby".to_string()]);
+
+    assert!(script.is_match("https://a.com/page", Some("lobby")));
+    assert!(!script.is_match("https://a.com/page", Some("arena")));
+    assert!(!script.is_match("https://a.com/page", None));
+    assert!(!script.is_match("https://b.com/", Some("lobby")));
+
+    let script = Userscript::parse("skip.js", "document.body.click();").unwrap();
+    assert_eq!(script.kind, Kind::Js);
+    assert!(script.is_match("https://anything/", None));
+    assert!(
+        script
+            .to_javascript()
+            .unwrap()
+            .contains("\ndocument.body.click();\n")
+    );
+
+    assert!(Userscript::parse("notes.txt", "").is_err());
+}